@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS password_category;
+DROP TABLE IF EXISTS password_history;
+DROP TABLE IF EXISTS passwords;
+DROP TABLE IF EXISTS categories;
+DROP TABLE IF EXISTS users;
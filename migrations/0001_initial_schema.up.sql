@@ -0,0 +1,43 @@
+-- הסכמה הבסיסית כפי שהייתה לפני המיגרציות. IF NOT EXISTS כדי ש-DB קיים יאמץ אותה בלי שינוי
+CREATE TABLE IF NOT EXISTS users (
+    user_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_first_name TEXT NOT NULL,
+    user_last_name TEXT NOT NULL,
+    email TEXT UNIQUE NOT NULL,
+    phone TEXT,
+    password_hash_to_login TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_login TIMESTAMP,
+    is_active BOOLEAN DEFAULT 1
+);
+
+CREATE TABLE IF NOT EXISTS categories (
+    category_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    category_name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS password_history (
+    history_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    password_id INTEGER,
+    old_password_encrypted TEXT NOT NULL,
+    changed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS passwords (
+    password_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    domain TEXT NOT NULL,
+    password_encrypted TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP,
+    UNIQUE(user_id, domain)
+);
+
+CREATE TABLE IF NOT EXISTS password_category (
+    password_id INTEGER NOT NULL,
+    category_id INTEGER NOT NULL,
+    PRIMARY KEY(password_id, category_id),
+    FOREIGN KEY(password_id) REFERENCES passwords(password_id) ON DELETE CASCADE,
+    FOREIGN KEY(category_id) REFERENCES categories(category_id) ON DELETE CASCADE
+);
@@ -0,0 +1,35 @@
+DROP INDEX IF EXISTS idx_password_category_category;
+DROP INDEX IF EXISTS idx_password_history_password;
+DROP INDEX IF EXISTS idx_categories_name;
+DROP INDEX IF EXISTS idx_users_email_lower;
+
+CREATE TABLE password_history_old (
+    history_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    password_id INTEGER,
+    old_password_encrypted TEXT NOT NULL,
+    changed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO password_history_old (history_id, password_id, old_password_encrypted, changed_at)
+    SELECT history_id, password_id, old_password_encrypted, changed_at FROM password_history;
+DELETE FROM sqlite_sequence WHERE name = 'password_history_old';
+INSERT INTO sqlite_sequence (name, seq)
+    SELECT 'password_history_old', seq FROM sqlite_sequence WHERE name = 'password_history';
+DROP TABLE password_history;
+ALTER TABLE password_history_old RENAME TO password_history;
+
+CREATE TABLE passwords_old (
+    password_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    domain TEXT NOT NULL,
+    password_encrypted TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP,
+    UNIQUE(user_id, domain)
+);
+INSERT INTO passwords_old (password_id, user_id, domain, password_encrypted, created_at, updated_at)
+    SELECT password_id, user_id, domain, password_encrypted, created_at, updated_at FROM passwords;
+DELETE FROM sqlite_sequence WHERE name = 'passwords_old';
+INSERT INTO sqlite_sequence (name, seq)
+    SELECT 'passwords_old', seq FROM sqlite_sequence WHERE name = 'passwords';
+DROP TABLE passwords;
+ALTER TABLE passwords_old RENAME TO passwords;
@@ -0,0 +1,50 @@
+-- SQLite לא מוסיף FOREIGN KEY לטבלה קיימת, לכן passwords ו-password_history נבנות מחדש.
+-- רץ עם foreign_keys=OFF (ראו utils/migrations.rs), אחרת DROP TABLE היה מוחק בשרשרת את password_category
+
+-- passwords: סיסמה שייכת למשתמש קיים ונמחקת איתו
+CREATE TABLE passwords_new (
+    password_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    domain TEXT NOT NULL,
+    password_encrypted TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP,
+    UNIQUE(user_id, domain),
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+INSERT INTO passwords_new (password_id, user_id, domain, password_encrypted, created_at, updated_at)
+    SELECT password_id, user_id, domain, password_encrypted, created_at, updated_at FROM passwords;
+-- ה-AAD קשור ל-password_id, לכן מזהים של סיסמאות שנמחקו לא חוזרים לשימוש
+DELETE FROM sqlite_sequence WHERE name = 'passwords_new';
+INSERT INTO sqlite_sequence (name, seq)
+    SELECT 'passwords_new', seq FROM sqlite_sequence WHERE name = 'passwords';
+DROP TABLE passwords;
+ALTER TABLE passwords_new RENAME TO passwords;
+
+-- password_history: כל שורה שייכת לסיסמה. שורה בלי password_id לא שייכת לאף סיסמה ואי אפשר לשייך אותה,
+-- וגם db repair לא מוצא אותה (foreign_key_check מדלג על NULL), לכן היא נמחקת כאן.
+-- run_pending מדווח כמה שורות כאלה יש לפני שהמיגרציה רצה
+DELETE FROM password_history WHERE password_id IS NULL;
+CREATE TABLE password_history_new (
+    history_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    password_id INTEGER NOT NULL,
+    old_password_encrypted TEXT NOT NULL,
+    changed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY(password_id) REFERENCES passwords(password_id) ON DELETE CASCADE
+);
+INSERT INTO password_history_new (history_id, password_id, old_password_encrypted, changed_at)
+    SELECT history_id, password_id, old_password_encrypted, changed_at FROM password_history;
+DELETE FROM sqlite_sequence WHERE name = 'password_history_new';
+INSERT INTO sqlite_sequence (name, seq)
+    SELECT 'password_history_new', seq FROM sqlite_sequence WHERE name = 'password_history';
+DROP TABLE password_history;
+ALTER TABLE password_history_new RENAME TO password_history;
+
+-- מייל אחד לחשבון בלי תלות באותיות גדולות/קטנות (כמו הבדיקה ב-email_in_use)
+CREATE UNIQUE INDEX idx_users_email_lower ON users (lower(email));
+-- קטגוריה נמצאת לפי שם (גם ביצירת סיסמה), לכן השם ייחודי
+CREATE UNIQUE INDEX idx_categories_name ON categories (category_name);
+-- היסטוריה נקראת לפי סיסמה, מהחדש לישן
+CREATE INDEX idx_password_history_password ON password_history (password_id, changed_at);
+-- המפתח הראשי מתחיל ב-password_id, החיפוש לפי קטגוריה צריך אינדקס משלו
+CREATE INDEX idx_password_category_category ON password_category (category_id);
@@ -0,0 +1,8 @@
+DROP TRIGGER IF EXISTS password_search_rename;
+DROP TRIGGER IF EXISTS password_search_unlink;
+DROP TRIGGER IF EXISTS password_search_link;
+DROP TRIGGER IF EXISTS password_search_delete;
+DROP TRIGGER IF EXISTS password_search_update;
+DROP TRIGGER IF EXISTS password_search_insert;
+DROP TABLE IF EXISTS password_search_terms;
+DROP TABLE IF EXISTS password_search;
@@ -0,0 +1,60 @@
+-- אינדקס חיפוש (FTS5) לרשומות הכספת: הדומיין ושמות הקטגוריות. rowid = password_id.
+-- הסיסמאות עצמן מוצפנות ולא נכנסות לאינדקס. user_id רק לסינון, לא לחיפוש
+CREATE VIRTUAL TABLE password_search USING fts5(
+    user_id UNINDEXED,
+    domain,
+    categories,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+-- כל המונחים שבאינדקס, להשלמת שגיאות הקלדה בחיפוש
+CREATE VIRTUAL TABLE password_search_terms USING fts5vocab(password_search, 'row');
+
+-- שמות הקטגוריות של סיסמה אחת, מופרדים ברווח
+CREATE TRIGGER password_search_insert AFTER INSERT ON passwords BEGIN
+    INSERT INTO password_search (rowid, user_id, domain, categories)
+    VALUES (NEW.password_id, NEW.user_id, NEW.domain, '');
+END;
+
+CREATE TRIGGER password_search_update AFTER UPDATE OF domain, user_id ON passwords BEGIN
+    UPDATE password_search SET domain = NEW.domain, user_id = NEW.user_id WHERE rowid = NEW.password_id;
+END;
+
+CREATE TRIGGER password_search_delete AFTER DELETE ON passwords BEGIN
+    DELETE FROM password_search WHERE rowid = OLD.password_id;
+END;
+
+CREATE TRIGGER password_search_link AFTER INSERT ON password_category BEGIN
+    UPDATE password_search SET categories = (
+        SELECT coalesce(group_concat(c.category_name, ' '), '')
+        FROM password_category pc JOIN categories c ON c.category_id = pc.category_id
+        WHERE pc.password_id = NEW.password_id
+    ) WHERE rowid = NEW.password_id;
+END;
+
+-- גם כשהקישור נמחק בשרשרת (מחיקת קטגוריה)
+CREATE TRIGGER password_search_unlink AFTER DELETE ON password_category BEGIN
+    UPDATE password_search SET categories = (
+        SELECT coalesce(group_concat(c.category_name, ' '), '')
+        FROM password_category pc JOIN categories c ON c.category_id = pc.category_id
+        WHERE pc.password_id = OLD.password_id
+    ) WHERE rowid = OLD.password_id;
+END;
+
+CREATE TRIGGER password_search_rename AFTER UPDATE OF category_name ON categories BEGIN
+    UPDATE password_search SET categories = (
+        SELECT coalesce(group_concat(c.category_name, ' '), '')
+        FROM password_category pc JOIN categories c ON c.category_id = pc.category_id
+        WHERE pc.password_id = password_search.rowid
+    ) WHERE rowid IN (SELECT password_id FROM password_category WHERE category_id = NEW.category_id);
+END;
+
+-- השורות הקיימות
+INSERT INTO password_search (rowid, user_id, domain, categories)
+    SELECT p.password_id, p.user_id, p.domain, coalesce((
+        SELECT group_concat(c.category_name, ' ')
+        FROM password_category pc JOIN categories c ON c.category_id = pc.category_id
+        WHERE pc.password_id = p.password_id
+    ), '')
+    FROM passwords p;
@@ -0,0 +1,8 @@
+-- ערכים ישנים של שדות שלא יהיו קיימים יותר
+DELETE FROM password_history WHERE field <> 'password';
+ALTER TABLE password_history DROP COLUMN field;
+
+ALTER TABLE passwords DROP COLUMN fields_encrypted;
+ALTER TABLE passwords DROP COLUMN notes_encrypted;
+ALTER TABLE passwords DROP COLUMN urls_encrypted;
+ALTER TABLE passwords DROP COLUMN username_encrypted;
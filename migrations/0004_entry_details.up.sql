@@ -0,0 +1,11 @@
+-- פרטים נוספים של רשומה בכספת. כל עמודה מוצפנת בנפרד במפתח הכספת וקשורה לשורה ולשם העמודה.
+-- NULL = אין ערך
+ALTER TABLE passwords ADD COLUMN username_encrypted TEXT;
+-- מערך JSON של כתובות, מוצפן כולו
+ALTER TABLE passwords ADD COLUMN urls_encrypted TEXT;
+ALTER TABLE passwords ADD COLUMN notes_encrypted TEXT;
+-- מערך JSON של שדות מותאמים ({name, type, value}), מוצפן כולו
+ALTER TABLE passwords ADD COLUMN fields_encrypted TEXT;
+
+-- איזה שדה של הרשומה השתנה. כל השורות הקיימות הן של הסיסמה עצמה
+ALTER TABLE password_history ADD COLUMN field TEXT NOT NULL DEFAULT 'password';
@@ -0,0 +1,11 @@
+DROP TABLE IF EXISTS key_rotation_jobs;
+DROP TABLE IF EXISTS audit_log;
+DROP TABLE IF EXISTS password_reset_tokens;
+DROP TABLE IF EXISTS email_verification_tokens;
+DROP INDEX IF EXISTS idx_login_history_user;
+DROP TABLE IF EXISTS login_history;
+DROP TABLE IF EXISTS login_throttle;
+DROP TABLE IF EXISTS recovery_codes;
+DROP TABLE IF EXISTS user_roles;
+DROP TABLE IF EXISTS roles;
+DROP TABLE IF EXISTS sessions;
@@ -0,0 +1,117 @@
+-- הטבלאות שנוצרו קודם בעליית השרת (init_table בכל controller).
+-- IF NOT EXISTS כדי ש-DB שהשרת כבר יצר בו אותן יאמץ אותן בלי שינוי
+
+-- sessions: נשמרים רק ה-hash של ה-tokens
+CREATE TABLE IF NOT EXISTS sessions (
+    session_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    access_token_hash TEXT UNIQUE NOT NULL,
+    refresh_token_hash TEXT UNIQUE NOT NULL,
+    access_expires_at TIMESTAMP NOT NULL,
+    refresh_expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP,
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- תפקידים
+CREATE TABLE IF NOT EXISTS roles (
+    role_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    role_name TEXT UNIQUE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    assigned_at TIMESTAMP NOT NULL,
+    PRIMARY KEY(user_id, role_id),
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE,
+    FOREIGN KEY(role_id) REFERENCES roles(role_id) ON DELETE CASCADE
+);
+
+INSERT OR IGNORE INTO roles (role_name) VALUES ('admin'), ('auditor'), ('user');
+
+-- משתמשים שנוצרו לפני שהיו תפקידים מקבלים את התפקיד הרגיל
+INSERT OR IGNORE INTO user_roles (user_id, role_id, assigned_at)
+    SELECT u.user_id, r.role_id, CURRENT_TIMESTAMP FROM users u, roles r WHERE r.role_name = 'user';
+
+-- קודי שחזור של אימות דו-שלבי (hash בלבד)
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    code_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    code_hash TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- ניסיונות התחברות כושלים לפי חשבון ולפי IP
+CREATE TABLE IF NOT EXISTS login_throttle (
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    failed_count INTEGER NOT NULL,
+    last_failed_at TIMESTAMP NOT NULL,
+    locked_until TIMESTAMP,
+    PRIMARY KEY(scope, key)
+);
+
+CREATE TABLE IF NOT EXISTS login_history (
+    login_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER,
+    email TEXT NOT NULL,
+    ip_address TEXT,
+    user_agent TEXT,
+    result TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS idx_login_history_user ON login_history (user_id, created_at);
+
+-- ה-token קשור לכתובת שאליה נשלח: אם המייל השתנה בינתיים, ה-token לא מאמת את החדש
+CREATE TABLE IF NOT EXISTS email_verification_tokens (
+    token_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    email TEXT NOT NULL,
+    token_hash TEXT UNIQUE NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- נשמר רק ה-hash של ה-token, used_at הופך אותו לחד-פעמי
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+    token_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    token_hash TEXT UNIQUE NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    audit_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    target_type TEXT NOT NULL,
+    target_id INTEGER NOT NULL,
+    ip_address TEXT,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS key_rotation_jobs (
+    job_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    target_key_id INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    phase TEXT NOT NULL,
+    last_id INTEGER NOT NULL DEFAULT 0,
+    total_rows INTEGER NOT NULL DEFAULT 0,
+    processed_rows INTEGER NOT NULL DEFAULT 0,
+    rewrapped_rows INTEGER NOT NULL DEFAULT 0,
+    failed_rows INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    started_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP
+);
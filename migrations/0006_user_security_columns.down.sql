@@ -0,0 +1,7 @@
+ALTER TABLE users DROP COLUMN email_verified_at;
+ALTER TABLE users DROP COLUMN totp_last_step;
+ALTER TABLE users DROP COLUMN totp_enabled;
+ALTER TABLE users DROP COLUMN totp_secret_encrypted;
+ALTER TABLE users DROP COLUMN vault_key_escrow;
+ALTER TABLE users DROP COLUMN vault_key_wrapped;
+ALTER TABLE users DROP COLUMN vault_key_salt;
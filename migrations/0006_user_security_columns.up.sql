@@ -0,0 +1,16 @@
+-- העמודות שנוספו קודם ל-users בעליית השרת. DB שהשרת כבר הוסיף בו חלק מהן
+-- מאומץ לפני המיגרציה (ראו utils/migrations.rs), ולכן היא לא רצה עליו
+
+-- מפתח הכספת, עטוף בסיסמת ההתחברות ובמפתח השרת
+ALTER TABLE users ADD COLUMN vault_key_salt TEXT;
+ALTER TABLE users ADD COLUMN vault_key_wrapped TEXT;
+ALTER TABLE users ADD COLUMN vault_key_escrow TEXT;
+
+-- הסוד מוצפן במפתח השרת, totp_last_step מונע שימוש חוזר באותו קוד
+ALTER TABLE users ADD COLUMN totp_secret_encrypted TEXT;
+ALTER TABLE users ADD COLUMN totp_enabled INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN totp_last_step INTEGER;
+
+-- NULL = ממתין לאימות. משתמשים שנרשמו לפני שהיה אימות לא ננעלים מחוץ לכספת שלהם
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMP;
+UPDATE users SET email_verified_at = created_at;
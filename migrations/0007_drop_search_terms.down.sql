@@ -0,0 +1 @@
+CREATE VIRTUAL TABLE IF NOT EXISTS password_search_terms USING fts5vocab(password_search, 'row');
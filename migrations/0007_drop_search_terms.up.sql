@@ -0,0 +1,3 @@
+-- החלופות לשגיאות הקלדה נלקחות עכשיו רק מהרשומות של המשתמש שמחפש (search_controller),
+-- ולא מרשימת המונחים של כל האינדקס, שכוללת מילים של משתמשים אחרים
+DROP TABLE IF EXISTS password_search_terms;
@@ -0,0 +1,2 @@
+-- אין מה להחזיר: העותקים שנמחקו לא נשמרו בשום מקום אחר
+SELECT 1;
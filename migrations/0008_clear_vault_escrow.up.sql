@@ -0,0 +1,3 @@
+-- העותקים של מפתחות הכספת במפתח השרת נשמרו לכל משתמש בלי שביקש.
+-- שחזור הכספת הוא עכשיו opt-in (POST /auth/vault-recovery/enable), לכן מוחקים את כולם
+UPDATE users SET vault_key_escrow = NULL;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_previous_vault_keys_user_id;
+DROP TABLE IF EXISTS previous_vault_keys;
@@ -0,0 +1,12 @@
+-- מפתחות כספת שהוחלפו באיפוס סיסמה בלי שחזור. הסיסמאות השמורות נשארות מוצפנות בהם,
+-- ונפתחות עם הסיסמה הישנה (POST /auth/vault/restore). העטיפה זהה לזו שב-users
+CREATE TABLE previous_vault_keys (
+    previous_key_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    vault_key_salt TEXT NOT NULL,
+    vault_key_wrapped TEXT NOT NULL,
+    replaced_at TIMESTAMP NOT NULL,
+    FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_previous_vault_keys_user_id ON previous_vault_keys(user_id);
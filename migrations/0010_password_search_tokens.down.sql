@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_password_search_tokens_token;
+DROP TABLE IF EXISTS password_search_tokens;
@@ -0,0 +1,10 @@
+-- מזהים עיוורים של המילים ב-username: HMAC במפתח הכספת של המשתמש, כי ה-username עצמו מוצפן.
+-- השרת כותב אותם עם הרשומה (ה-DB לא מכיר את המפתח, לכן לא ב-trigger). מחפשים רק מילה שלמה
+CREATE TABLE password_search_tokens (
+    password_id INTEGER NOT NULL,
+    token TEXT NOT NULL,
+    PRIMARY KEY (password_id, token),
+    FOREIGN KEY(password_id) REFERENCES passwords(password_id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_password_search_tokens_token ON password_search_tokens(token);
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use sqlx::{ConnectOptions, Connection};
+use crate::config::Config;
+use crate::controllers::key_rotation_controller;
+use crate::utils::{db, keys, migrations};
+
+/// פקודות תחזוקה שרצות במקום השרת ויוצאות
+pub enum Command {
+    MigrateStatus,
+    MigrateUp,
+    MigrateDown,
+    DbCheck,
+    DbRepair,
+    KeysMigrateLegacy,
+}
+
+pub struct Args {
+    pub config_file: Option<PathBuf>,
+    pub print_config: bool,
+    pub command: Option<Command>,
+}
+
+const USAGE: &str =
+    "expected [--config <path>] [--print-config | migrate <status|up|down> | db <check|repair> | keys migrate-legacy]";
+
+pub fn parse_args() -> Result<Args, String> {
+    let mut args = Args { config_file: None, print_config: false, command: None };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--print-config" => args.print_config = true,
+            "--config" => match iter.next() {
+                Some(path) => args.config_file = Some(PathBuf::from(path)),
+                None => return Err("--config needs a file path".to_string()),
+            },
+            "migrate" => {
+                args.command = Some(match iter.next().as_deref() {
+                    Some("status") => Command::MigrateStatus,
+                    Some("up") => Command::MigrateUp,
+                    Some("down") => Command::MigrateDown,
+                    _ => return Err(format!("migrate needs status, up or down ({})", USAGE)),
+                })
+            }
+            "db" => {
+                args.command = Some(match iter.next().as_deref() {
+                    Some("check") => Command::DbCheck,
+                    Some("repair") => Command::DbRepair,
+                    _ => return Err(format!("db needs check or repair ({})", USAGE)),
+                })
+            }
+            "keys" => {
+                args.command = Some(match iter.next().as_deref() {
+                    Some("migrate-legacy") => Command::KeysMigrateLegacy,
+                    _ => return Err(format!("keys needs migrate-legacy ({})", USAGE)),
+                })
+            }
+            other => match other.strip_prefix("--config=") {
+                Some(path) => args.config_file = Some(PathBuf::from(path)),
+                None => return Err(format!("unknown argument '{}' ({})", other, USAGE)),
+            },
+        }
+    }
+    Ok(args)
+}
+
+pub async fn run(command: Command, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::MigrateStatus => migrations::print_status(&config.database).await?,
+        Command::MigrateUp => {
+            migrations::run_pending(&config.database).await?;
+            println!("✅ Database schema is up to date");
+        }
+        Command::MigrateDown => match migrations::revert_last(&config.database).await? {
+            Some(version) => println!("↩️ Reverted migration {}", version),
+            None => println!("No migrations to revert"),
+        },
+        Command::DbCheck => {
+            let mut conn = db::connect_options(&config.database)?.connect().await?;
+            let counts = db::orphan_counts(&mut conn).await?;
+            if counts.is_empty() {
+                println!("✅ No rows reference missing rows");
+            }
+            for (table, count) in &counts {
+                println!("{:<24} {} orphan rows", table, count);
+            }
+            conn.close().await?;
+        }
+        Command::DbRepair => {
+            let mut conn = db::connect_options(&config.database)?.connect().await?;
+            let deleted = db::delete_orphans(&mut conn).await?;
+            if deleted.is_empty() {
+                println!("✅ Nothing to repair");
+            }
+            for (table, count) in &deleted {
+                println!("🧹 Deleted {} orphan rows from {}", count, table);
+            }
+            conn.close().await?;
+        }
+        Command::KeysMigrateLegacy => {
+            // כמו בעליית השרת: מפתחות, ואז סכמה מעודכנת
+            let provider = keys::provider_from_config(&config.keys)?;
+            keys::init_key_ring(provider.as_ref(), &config.keys)?;
+            migrations::run_pending(&config.database).await?;
+            let pool = db::connect_pool(&config.database).await?;
+            let job = key_rotation_controller::migrate_legacy(&pool).await?;
+            println!(
+                "🔑 Key rotation job #{}: {} rows checked, {} re-encrypted, {} failed",
+                job.job_id, job.processed_rows, job.rewrapped_rows, job.failed_rows
+            );
+            if job.failed_rows == 0 {
+                println!("✅ No row needs the old built-in key any more");
+            } else {
+                println!("⚠️ {} rows could not be decrypted with any key; see the warnings above", job.failed_rows);
+            }
+            pool.close().await;
+        }
+    }
+    Ok(())
+}
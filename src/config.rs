@@ -0,0 +1,466 @@
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+// קובץ ההגדרות: --config <path>, אחרת PMS_CONFIG_FILE, אחרת config.toml אם קיים
+pub const CONFIG_FILE_ENV: &str = "PMS_CONFIG_FILE";
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+// ===================== ENV NAMES =====================
+// משתני סביבה גוברים על הקובץ (ו-.env נטען לסביבה לפני כן)
+pub const BIND_ENV: &str = "PMS_BIND";
+pub const MAX_JSON_BYTES_ENV: &str = "PMS_MAX_JSON_BYTES";
+pub const DATABASE_URL_ENV: &str = "PMS_DATABASE_URL";
+pub const DB_MAX_CONNECTIONS_ENV: &str = "PMS_DB_MAX_CONNECTIONS";
+pub const DB_JOURNAL_MODE_ENV: &str = "PMS_DB_JOURNAL_MODE";
+pub const DB_SYNCHRONOUS_ENV: &str = "PMS_DB_SYNCHRONOUS";
+pub const DB_BUSY_TIMEOUT_MS_ENV: &str = "PMS_DB_BUSY_TIMEOUT_MS";
+pub const LOG_LEVEL_ENV: &str = "PMS_LOG_LEVEL";
+pub const CORS_ORIGINS_ENV: &str = "PMS_CORS_ORIGINS";
+pub const ADMIN_EMAIL_ENV: &str = "PMS_ADMIN_EMAIL";
+
+pub const MASTER_KEY_ENV: &str = "PMS_MASTER_KEY";
+pub const MASTER_KEY_FILE_ENV: &str = "PMS_MASTER_KEY_FILE";
+pub const MASTER_PASSPHRASE_ENV: &str = "PMS_MASTER_PASSPHRASE";
+pub const MASTER_KEY_SALT_FILE_ENV: &str = "PMS_MASTER_KEY_SALT_FILE";
+pub const MASTER_KEY_ID_ENV: &str = "PMS_MASTER_KEY_ID";
+pub const RETIRED_KEYS_FILE_ENV: &str = "PMS_RETIRED_KEYS_FILE";
+pub const REQUIRE_BOUND_ENV: &str = "PMS_REQUIRE_BOUND_CIPHERTEXTS";
+pub const ALLOW_LEGACY_KEY_ENV: &str = "PMS_ALLOW_LEGACY_KEY";
+
+pub const ACCESS_TOKEN_MINUTES_ENV: &str = "PMS_ACCESS_TOKEN_MINUTES";
+pub const REFRESH_TOKEN_DAYS_ENV: &str = "PMS_REFRESH_TOKEN_DAYS";
+pub const RESET_TOKEN_MINUTES_ENV: &str = "PMS_RESET_TOKEN_MINUTES";
+pub const VERIFY_TOKEN_HOURS_ENV: &str = "PMS_VERIFY_TOKEN_HOURS";
+
+pub const MAIL_TRANSPORT_ENV: &str = "PMS_MAIL_TRANSPORT";
+pub const SMTP_HOST_ENV: &str = "PMS_SMTP_HOST";
+pub const SMTP_PORT_ENV: &str = "PMS_SMTP_PORT";
+pub const MAIL_FROM_ENV: &str = "PMS_MAIL_FROM";
+pub const MAIL_DIR_ENV: &str = "PMS_MAIL_DIR";
+
+pub const MEMORY_KIB_ENV: &str = "PMS_ARGON2_MEMORY_KIB";
+pub const TIME_COST_ENV: &str = "PMS_ARGON2_TIME_COST";
+pub const PARALLELISM_ENV: &str = "PMS_ARGON2_PARALLELISM";
+pub const PEPPER_ENV: &str = "PMS_PASSWORD_PEPPER";
+pub const TARGET_MS_ENV: &str = "PMS_ARGON2_TARGET_MS";
+
+const LOG_LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+const JOURNAL_MODES: [&str; 6] = ["delete", "truncate", "persist", "memory", "wal", "off"];
+const SYNCHRONOUS_MODES: [&str; 4] = ["off", "normal", "full", "extra"];
+const MAX_DB_CONNECTIONS: u32 = 100;
+// גוף JSON קטן מזה לא מספיק אפילו לרישום משתמש
+const MIN_JSON_BYTES: usize = 1024;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum ConfigError {
+    AlreadyInitialized,
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::AlreadyInitialized => write!(f, "configuration already loaded"),
+            ConfigError::Io(path, e) => write!(f, "cannot read config file {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "invalid config file {}: {}", path.display(), e),
+            ConfigError::Invalid(problems) => write!(f, "invalid configuration: {}", problems.join("; ")),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// ===================== SECRET =====================
+/// ערך סודי: נקרא כרגיל, אבל לא מודפס (לא ב-Debug ולא ב---print-config)
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str("<redacted>")
+    }
+}
+
+// ===================== SECTIONS =====================
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // מייל של משתמש שמקבל תפקיד admin בעליית השרת
+    pub admin_email: Option<String>,
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub log: LogSettings,
+    pub cors: CorsSettings,
+    pub keys: KeySettings,
+    pub tokens: TokenSettings,
+    pub mail: MailSettings,
+    pub hashing: HashSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    // כתובות להאזנה, למשל ["127.0.0.1:8080", "[::1]:8080"]
+    pub bind: Vec<String>,
+    // הגודל המקסימלי של גוף JSON בבקשה. גדול מזה => 413
+    pub max_json_bytes: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    pub url: String,
+    pub max_connections: u32,
+    // WAL: קוראים לא נחסמים בזמן כתיבה
+    pub journal_mode: String,
+    // ב-WAL, normal לא מאבד נתונים בקריסה של התהליך, רק (אולי) את הטרנזקציה האחרונה בנפילת חשמל
+    pub synchronous: String,
+    // כמה זמן חיבור מחכה לנעילה של חיבור אחר לפני SQLITE_BUSY
+    pub busy_timeout_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSettings {
+    pub level: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsSettings {
+    // ריק = אין גישה מדפדפן מ-origin אחר. "*" = כל origin
+    pub allowed_origins: Vec<String>,
+}
+
+/// מקור המפתח הראשי (לפי סדר עדיפות: master_key, master_key_file, passphrase)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeySettings {
+    pub master_key: Option<Secret>,
+    pub master_key_file: Option<PathBuf>,
+    pub passphrase: Option<Secret>,
+    pub salt_file: PathBuf,
+    pub active_key_id: u32,
+    pub retired_keys_file: Option<PathBuf>,
+    // לסרב לערכים לא קשורים ברגע שאין יותר כאלה (ברירת מחדל). false: לקבל אותם תמיד
+    pub require_bound_ciphertexts: bool,
+    // המפתח הקבוע הישן, רק כדי לקרוא שורות שעוד לא הוצפנו מחדש (ראו keys migrate-legacy)
+    pub allow_legacy_key: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenSettings {
+    pub access_minutes: i64,
+    pub refresh_days: i64,
+    pub reset_minutes: i64,
+    pub verify_hours: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailSettings {
+    // smtp (למשל MailHog מקומי) או file (קבצי .eml בתיקייה, לבדיקות)
+    pub transport: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub from: String,
+    pub dir: PathBuf,
+}
+
+/// פרמטרים של Argon2id לסיסמאות ההתחברות (ברירת המחדל לפי ההמלצה של OWASP)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HashSettings {
+    pub memory_kib: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    // סוד של השרת שנכנס לכל hash (base64)
+    pub pepper: Option<Secret>,
+    // זמן היעד של hash אחד לבדיקה בעליית השרת. 0 מבטל את הבדיקה
+    pub target_ms: u64,
+}
+
+// ===================== DEFAULTS =====================
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings { bind: vec!["127.0.0.1:8080".to_string()], max_json_bytes: 64 * 1024 }
+    }
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        DatabaseSettings {
+            url: "sqlite://src/passwords_management_system.db".to_string(),
+            max_connections: 5,
+            journal_mode: "wal".to_string(),
+            synchronous: "normal".to_string(),
+            busy_timeout_ms: 5000,
+        }
+    }
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings { level: "info".to_string() }
+    }
+}
+
+impl Default for KeySettings {
+    fn default() -> Self {
+        KeySettings {
+            master_key: None,
+            master_key_file: None,
+            passphrase: None,
+            salt_file: PathBuf::from("src/master_key.salt"),
+            active_key_id: 1,
+            retired_keys_file: None,
+            require_bound_ciphertexts: true,
+            allow_legacy_key: false,
+        }
+    }
+}
+
+impl Default for TokenSettings {
+    fn default() -> Self {
+        TokenSettings { access_minutes: 15, refresh_days: 7, reset_minutes: 30, verify_hours: 24 }
+    }
+}
+
+impl Default for MailSettings {
+    fn default() -> Self {
+        MailSettings {
+            transport: "file".to_string(),
+            smtp_host: "127.0.0.1".to_string(),
+            smtp_port: 1025,
+            from: "no-reply@passmensystem.local".to_string(),
+            dir: PathBuf::from("mail_outbox"),
+        }
+    }
+}
+
+impl Default for HashSettings {
+    fn default() -> Self {
+        HashSettings { memory_kib: 19 * 1024, time_cost: 2, parallelism: 1, pepper: None, target_ms: 250 }
+    }
+}
+
+// ===================== LOAD =====================
+/// ברירות מחדל < קובץ TOML < .env < משתני סביבה
+pub fn load(config_file: Option<&Path>) -> Result<Config, ConfigError> {
+    // .env לא דורס משתנים שכבר מוגדרים בסביבה
+    dotenv::dotenv().ok();
+
+    let path = match config_file {
+        Some(path) => Some(path.to_path_buf()),
+        None => match std::env::var(CONFIG_FILE_ENV) {
+            Ok(path) => Some(PathBuf::from(path)),
+            Err(_) => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|p| p.exists()),
+        },
+    };
+
+    let mut config = match &path {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+            toml::from_str(&text).map_err(|e| ConfigError::Parse(path.clone(), e.to_string()))?
+        }
+        None => Config::default(),
+    };
+
+    let mut problems = Vec::new();
+    config.apply_env(&mut problems);
+    if !problems.is_empty() {
+        return Err(ConfigError::Invalid(problems));
+    }
+    Ok(config)
+}
+
+pub fn init(config: Config) -> Result<&'static Config, ConfigError> {
+    CONFIG.set(config).map_err(|_| ConfigError::AlreadyInitialized)?;
+    Ok(get())
+}
+
+/// ההגדרות שנטענו בעליית השרת
+pub fn get() -> &'static Config {
+    CONFIG.get().expect("configuration is loaded in main before anything else")
+}
+
+// ===================== ENV OVERRIDES =====================
+fn env_value<T: FromStr>(name: &str, target: &mut T, problems: &mut Vec<String>) {
+    if let Ok(value) = std::env::var(name) {
+        match value.trim().parse() {
+            Ok(parsed) => *target = parsed,
+            Err(_) => problems.push(format!("{} has an invalid value '{}'", name, value)),
+        }
+    }
+}
+
+fn env_list(name: &str, target: &mut Vec<String>) {
+    if let Ok(value) = std::env::var(name) {
+        *target = value.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect();
+    }
+}
+
+fn env_bool(name: &str, target: &mut bool, problems: &mut Vec<String>) {
+    match std::env::var(name).as_deref() {
+        Ok("1") | Ok("true") => *target = true,
+        Ok("0") | Ok("false") => *target = false,
+        Ok(other) => problems.push(format!("{} must be true or false, got '{}'", name, other)),
+        Err(_) => {}
+    }
+}
+
+impl Config {
+    fn apply_env(&mut self, problems: &mut Vec<String>) {
+        env_list(BIND_ENV, &mut self.server.bind);
+        env_value(MAX_JSON_BYTES_ENV, &mut self.server.max_json_bytes, problems);
+        env_value(DATABASE_URL_ENV, &mut self.database.url, problems);
+        env_value(DB_MAX_CONNECTIONS_ENV, &mut self.database.max_connections, problems);
+        env_value(DB_JOURNAL_MODE_ENV, &mut self.database.journal_mode, problems);
+        env_value(DB_SYNCHRONOUS_ENV, &mut self.database.synchronous, problems);
+        env_value(DB_BUSY_TIMEOUT_MS_ENV, &mut self.database.busy_timeout_ms, problems);
+        env_value(LOG_LEVEL_ENV, &mut self.log.level, problems);
+        env_list(CORS_ORIGINS_ENV, &mut self.cors.allowed_origins);
+        if let Ok(email) = std::env::var(ADMIN_EMAIL_ENV) {
+            self.admin_email = Some(email);
+        }
+
+        let keys = &mut self.keys;
+        if let Ok(value) = std::env::var(MASTER_KEY_ENV) {
+            keys.master_key = Some(Secret(value));
+        }
+        if let Ok(path) = std::env::var(MASTER_KEY_FILE_ENV) {
+            keys.master_key_file = Some(PathBuf::from(path));
+        }
+        if let Ok(passphrase) = std::env::var(MASTER_PASSPHRASE_ENV) {
+            keys.passphrase = Some(Secret(passphrase));
+        }
+        env_value(MASTER_KEY_SALT_FILE_ENV, &mut keys.salt_file, problems);
+        env_value(MASTER_KEY_ID_ENV, &mut keys.active_key_id, problems);
+        if let Ok(path) = std::env::var(RETIRED_KEYS_FILE_ENV) {
+            keys.retired_keys_file = Some(PathBuf::from(path));
+        }
+        env_bool(REQUIRE_BOUND_ENV, &mut keys.require_bound_ciphertexts, problems);
+        env_bool(ALLOW_LEGACY_KEY_ENV, &mut keys.allow_legacy_key, problems);
+
+        let tokens = &mut self.tokens;
+        env_value(ACCESS_TOKEN_MINUTES_ENV, &mut tokens.access_minutes, problems);
+        env_value(REFRESH_TOKEN_DAYS_ENV, &mut tokens.refresh_days, problems);
+        env_value(RESET_TOKEN_MINUTES_ENV, &mut tokens.reset_minutes, problems);
+        env_value(VERIFY_TOKEN_HOURS_ENV, &mut tokens.verify_hours, problems);
+
+        let mail = &mut self.mail;
+        env_value(MAIL_TRANSPORT_ENV, &mut mail.transport, problems);
+        env_value(SMTP_HOST_ENV, &mut mail.smtp_host, problems);
+        env_value(SMTP_PORT_ENV, &mut mail.smtp_port, problems);
+        env_value(MAIL_FROM_ENV, &mut mail.from, problems);
+        env_value(MAIL_DIR_ENV, &mut mail.dir, problems);
+
+        let hashing = &mut self.hashing;
+        env_value(MEMORY_KIB_ENV, &mut hashing.memory_kib, problems);
+        env_value(TIME_COST_ENV, &mut hashing.time_cost, problems);
+        env_value(PARALLELISM_ENV, &mut hashing.parallelism, problems);
+        if let Ok(pepper) = std::env::var(PEPPER_ENV) {
+            hashing.pepper = Some(Secret(pepper));
+        }
+        env_value(TARGET_MS_ENV, &mut hashing.target_ms, problems);
+    }
+
+    // ===================== VALIDATE =====================
+    /// בדיקות שלא תלויות בקבצים או ברשת. המפתח וה-pepper נבדקים כשהם נטענים
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        if self.server.bind.is_empty() {
+            problems.push("server.bind must list at least one address".to_string());
+        }
+        for address in &self.server.bind {
+            if address.parse::<SocketAddr>().is_err() {
+                problems.push(format!("server.bind: '{}' is not an ip:port address", address));
+            }
+        }
+        if self.server.max_json_bytes < MIN_JSON_BYTES {
+            problems.push(format!("server.max_json_bytes must be at least {}", MIN_JSON_BYTES));
+        }
+
+        if !self.database.url.starts_with("sqlite:") {
+            problems.push("database.url must be a sqlite: URL".to_string());
+        }
+        if !(1..=MAX_DB_CONNECTIONS).contains(&self.database.max_connections) {
+            problems.push(format!("database.max_connections must be between 1 and {}", MAX_DB_CONNECTIONS));
+        }
+        if !JOURNAL_MODES.contains(&self.database.journal_mode.as_str()) {
+            problems.push(format!("database.journal_mode must be one of: {}", JOURNAL_MODES.join(", ")));
+        }
+        if !SYNCHRONOUS_MODES.contains(&self.database.synchronous.as_str()) {
+            problems.push(format!("database.synchronous must be one of: {}", SYNCHRONOUS_MODES.join(", ")));
+        }
+
+        if !LOG_LEVELS.contains(&self.log.level.as_str()) {
+            problems.push(format!("log.level must be one of: {}", LOG_LEVELS.join(", ")));
+        }
+
+        for origin in &self.cors.allowed_origins {
+            if origin != "*" && !(origin.starts_with("http://") || origin.starts_with("https://")) {
+                problems.push(format!("cors.allowed_origins: '{}' must start with http:// or https://", origin));
+            }
+        }
+
+        let keys = &self.keys;
+        if keys.master_key.is_none() && keys.master_key_file.is_none() && keys.passphrase.is_none() {
+            problems.push(format!(
+                "no master key configured: set keys.master_key, keys.master_key_file or keys.passphrase ({}, {} or {})",
+                MASTER_KEY_ENV, MASTER_KEY_FILE_ENV, MASTER_PASSPHRASE_ENV
+            ));
+        }
+        // מזהה 0 שמור למפתחות הכספת של המשתמשים
+        if keys.active_key_id == 0 {
+            problems.push("keys.active_key_id must be 1 or more".to_string());
+        }
+
+        let tokens = &self.tokens;
+        for (name, value) in [
+            ("tokens.access_minutes", tokens.access_minutes),
+            ("tokens.refresh_days", tokens.refresh_days),
+            ("tokens.reset_minutes", tokens.reset_minutes),
+            ("tokens.verify_hours", tokens.verify_hours),
+        ] {
+            if value <= 0 {
+                problems.push(format!("{} must be positive", name));
+            }
+        }
+
+        if !["smtp", "file"].contains(&self.mail.transport.as_str()) {
+            problems.push(format!("mail.transport must be smtp or file, got '{}'", self.mail.transport));
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(ConfigError::Invalid(problems)) }
+    }
+
+    /// ההגדרות בפועל בפורמט של קובץ ההגדרות, בלי סודות
+    pub fn redacted_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap_or_else(|e| format!("# cannot print configuration: {}", e))
+    }
+}
@@ -0,0 +1,55 @@
+use actix_web::{get, web, HttpResponse};
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{SqlitePool, Row};
+use crate::errors::ApiError;
+use crate::models::audit::AuditEntry;
+
+pub const ACTION_PASSWORD_REVEAL: &str = "password.reveal";
+pub const ACTION_PASSWORD_RESET: &str = "user.password_reset";
+pub const ACTION_PASSWORD_CHANGE: &str = "user.password_change";
+pub const ACTION_VAULT_RECOVERY_ENABLE: &str = "user.vault_recovery_enable";
+pub const ACTION_VAULT_RECOVERY_DISABLE: &str = "user.vault_recovery_disable";
+pub const ACTION_VAULT_RESTORE: &str = "user.vault_restore";
+
+// ===================== RECORD =====================
+/// רושם פעולה ביומן. השורות נשארות גם אחרי שהמשתמש או הסיסמה נמחקים
+pub async fn record(
+    pool: &SqlitePool,
+    user_id: i64,
+    action: &str,
+    target_type: &str,
+    target_id: i64,
+    ip_address: Option<&str>,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO audit_log (user_id, action, target_type, target_id, ip_address, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)"
+    )
+    .bind(user_id)
+    .bind(action)
+    .bind(target_type)
+    .bind(target_id)
+    .bind(ip_address)
+    .bind(Utc::now().naive_utc())
+    .execute(pool)
+    .await?;
+    Ok(result.last_insert_rowid())
+}
+
+// ===================== READ AUDIT LOG =====================
+#[get("/audit")]
+pub async fn get_audit_log(pool: web::Data<SqlitePool>) -> Result<HttpResponse, ApiError> {
+    let rows = sqlx::query("SELECT * FROM audit_log ORDER BY audit_id DESC")
+        .fetch_all(&**pool)
+        .await?;
+    let entries: Vec<AuditEntry> = rows.iter().map(|row| AuditEntry {
+        audit_id: row.get("audit_id"),
+        user_id: row.get("user_id"),
+        action: row.get("action"),
+        target_type: row.get("target_type"),
+        target_id: row.get("target_id"),
+        ip_address: row.get("ip_address"),
+        created_at: row.get::<NaiveDateTime, _>("created_at"),
+    }).collect();
+    Ok(HttpResponse::Ok().json(entries))
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use actix_web::{post, web, HttpResponse};
+use chrono::{Duration, NaiveDateTime, Utc};
+use sqlx::{SqlitePool, Row};
+use crate::config;
+use crate::errors::{ApiError, ErrorCode};
+use crate::utils::validation::Validate;
+use crate::middleware::auth::AuthUser;
+use crate::controllers::roles_controller::bootstrap_admin;
+use crate::models::email_verification::VerifyEmailDto;
+use crate::repositories::users::NewVerificationToken;
+use crate::utils::mailer::{send_in_background, Mail, MailTransport};
+use crate::utils::tokens::{generate_token, hash_token};
+
+// לא שולחים יותר ממייל אימות אחד בדקה לאותה כתובת
+const RESEND_INTERVAL_SECONDS: i64 = 60;
+
+pub const EMAIL_NOT_VERIFIED: &str = "Verify your email address before adding passwords";
+const INVALID_VERIFY_TOKEN: &str = "Invalid or expired verification token";
+
+// כמה זמן קישור האימות תקף
+fn token_ttl() -> Duration {
+    Duration::hours(config::get().tokens.verify_hours)
+}
+
+// ===================== HELPERS =====================
+/// token חדש: את הטקסט שולחים במייל, ואת ה-hash שומרים
+pub fn new_verification_token() -> (String, NewVerificationToken) {
+    let token = generate_token();
+    let verification = NewVerificationToken {
+        token_hash: hash_token(&token),
+        expires_at: Utc::now().naive_utc() + token_ttl(),
+    };
+    (token, verification)
+}
+
+/// יוצר token חדש לכתובת ושולח אותו ברקע. מבטל tokens קודמים של המשתמש.
+/// false אם כבר נשלח מייל לאותה כתובת לפני פחות מדקה
+pub async fn send_verification(
+    pool: &SqlitePool,
+    mailer: Arc<dyn MailTransport>,
+    user_id: i64,
+    email: &str,
+) -> Result<bool, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+
+    // כתובת חדשה (אחרי שינוי מייל) מקבלת token גם אם נשלח אחד לכתובת הקודמת
+    let recent = sqlx::query(
+        "SELECT 1 FROM email_verification_tokens WHERE user_id = ? AND email = ? AND created_at > ? LIMIT 1"
+    )
+    .bind(user_id)
+    .bind(email)
+    .bind(now - Duration::seconds(RESEND_INTERVAL_SECONDS))
+    .fetch_optional(pool)
+    .await?;
+    if recent.is_some() {
+        return Ok(false);
+    }
+
+    sqlx::query("UPDATE email_verification_tokens SET used_at = ? WHERE user_id = ? AND used_at IS NULL")
+        .bind(now)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    let (token, verification) = new_verification_token();
+    sqlx::query(
+        "INSERT INTO email_verification_tokens (user_id, email, token_hash, created_at, expires_at)
+         VALUES (?, ?, ?, ?, ?)"
+    )
+    .bind(user_id)
+    .bind(email)
+    .bind(&verification.token_hash)
+    .bind(now)
+    .bind(verification.expires_at)
+    .execute(pool)
+    .await?;
+
+    send_in_background(mailer, verification_mail(email, &token, verification.expires_at));
+    Ok(true)
+}
+
+pub fn verification_mail(email: &str, token: &str, expires_at: NaiveDateTime) -> Mail {
+    Mail {
+        to: email.to_string(),
+        subject: "Verify your email address".to_string(),
+        body: format!(
+            "Welcome to PassMenSystem.\n\n\
+             Verification token: {}\n\n\
+             Send it to POST /auth/verify-email before {} UTC to start adding passwords.\n\
+             If you did not sign up, ignore this email.",
+            token,
+            expires_at.format("%Y-%m-%d %H:%M")
+        ),
+    }
+}
+
+// ===================== VERIFY EMAIL =====================
+#[post("/auth/verify-email")]
+pub async fn verify_email(pool: web::Data<SqlitePool>, body: web::Json<VerifyEmailDto>) -> Result<HttpResponse, ApiError> {
+    body.validate()?;
+    let invalid_token = || ApiError::new(ErrorCode::InvalidEmailToken, INVALID_VERIFY_TOKEN);
+    let now = Utc::now().naive_utc();
+
+    // בקשה מקבילה עם אותו token תיכשל כאן
+    let row = sqlx::query(
+        "UPDATE email_verification_tokens SET used_at = ?
+         WHERE token_hash = ? AND used_at IS NULL AND expires_at > ?
+         RETURNING user_id, email"
+    )
+    .bind(now)
+    .bind(hash_token(&body.token))
+    .bind(now)
+    .fetch_optional(&**pool)
+    .await?
+    .ok_or_else(invalid_token)?;
+
+    let result = sqlx::query("UPDATE users SET email_verified_at = ?, updated_at = ? WHERE user_id = ? AND email = ?")
+        .bind(now)
+        .bind(now)
+        .bind(row.get::<i64, _>("user_id"))
+        .bind(row.get::<String, _>("email"))
+        .execute(&**pool)
+        .await?;
+    // המייל של המשתמש השתנה מאז שה-token נשלח
+    if result.rows_affected() == 0 {
+        return Err(invalid_token());
+    }
+    // אם זה המייל של admin_email, עכשיו אפשר לתת לו admin
+    if let Err(e) = bootstrap_admin(&pool).await {
+        log::warn!("⚠️ Admin role not assigned: {}", e);
+    }
+    Ok(HttpResponse::Ok().body("Email address verified"))
+}
+
+// ===================== RESEND =====================
+#[post("/auth/verify-email/resend")]
+pub async fn resend_verification(
+    pool: web::Data<SqlitePool>,
+    mailer: web::Data<dyn MailTransport>,
+    user: AuthUser,
+) -> Result<HttpResponse, ApiError> {
+    let row = sqlx::query("SELECT email, email_verified_at FROM users WHERE user_id = ?")
+        .bind(user.user_id)
+        .fetch_optional(&**pool)
+        .await?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+    if row.get::<Option<NaiveDateTime>, _>("email_verified_at").is_some() {
+        return Err(ApiError::new(ErrorCode::Conflict, "Email address is already verified"));
+    }
+
+    let email: String = row.get("email");
+    if !send_verification(&pool, mailer.into_inner(), user.user_id, &email).await? {
+        return Err(ApiError::new(ErrorCode::RateLimited, "A verification email was sent recently, try again later")
+            .retry_after(RESEND_INTERVAL_SECONDS));
+    }
+    Ok(HttpResponse::Accepted().body("Verification email sent"))
+}
@@ -0,0 +1,554 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use actix_web::{get, post, web, HttpResponse};
+use chrono::Utc;
+use sqlx::{SqlitePool, Row};
+use crate::errors::{ApiError, ErrorCode, OrInternal};
+use crate::config;
+use crate::models::key_rotation::KeyRotationJob;
+use crate::utils::encryption::{
+    bound_value_sql, opens_with, refuse_unbound, rewrap, passwords_aad, history_field_aad, totp_aad, vault_escrow_aad,
+    vault_key_aad,
+};
+use crate::utils::keys::key_ring;
+
+const BATCH_SIZE: i64 = 100;
+
+// רק עבודת הצפנה-מחדש אחת רצה בכל רגע
+static JOB_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// ===================== PHASES =====================
+/// הטבלאות שהעבודה עוברת עליהן, לפי הסדר
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Passwords,
+    PasswordHistory,
+    Users,
+    PreviousVaultKeys,
+    TotpSecrets,
+    VaultEscrow,
+    Done,
+}
+
+impl Phase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Phase::Passwords => "passwords",
+            Phase::PasswordHistory => "password_history",
+            Phase::Users => "users",
+            Phase::PreviousVaultKeys => "previous_vault_keys",
+            Phase::TotpSecrets => "users_totp",
+            Phase::VaultEscrow => "users_vault_escrow",
+            Phase::Done => "done",
+        }
+    }
+
+    fn parse(value: &str) -> Phase {
+        match value {
+            "passwords" => Phase::Passwords,
+            "password_history" => Phase::PasswordHistory,
+            "users" => Phase::Users,
+            "previous_vault_keys" => Phase::PreviousVaultKeys,
+            "users_totp" => Phase::TotpSecrets,
+            "users_vault_escrow" => Phase::VaultEscrow,
+            _ => Phase::Done,
+        }
+    }
+
+    fn next(self) -> Phase {
+        match self {
+            Phase::Passwords => Phase::PasswordHistory,
+            Phase::PasswordHistory => Phase::Users,
+            Phase::Users => Phase::PreviousVaultKeys,
+            Phase::PreviousVaultKeys => Phase::TotpSecrets,
+            Phase::TotpSecrets => Phase::VaultEscrow,
+            _ => Phase::Done,
+        }
+    }
+
+    // (טבלה, עמודת מזהה, עמודה מוצפנת)
+    fn columns(self) -> Option<(&'static str, &'static str, &'static str)> {
+        match self {
+            Phase::Passwords => Some(("passwords", "password_id", "password_encrypted")),
+            Phase::PasswordHistory => Some(("password_history", "history_id", "old_password_encrypted")),
+            // מפתחות הכספת העטופים מוצפנים גם במפתח השרת
+            Phase::Users => Some(("users", "user_id", "vault_key_wrapped")),
+            Phase::PreviousVaultKeys => Some(("previous_vault_keys", "previous_key_id", "vault_key_wrapped")),
+            Phase::TotpSecrets => Some(("users", "user_id", "totp_secret_encrypted")),
+            Phase::VaultEscrow => Some(("users", "user_id", "vault_key_escrow")),
+            Phase::Done => None,
+        }
+    }
+
+    // עמודות נוספות שנדרשות כדי לבנות את ה-AAD של השורה
+    fn aad_columns(self) -> &'static str {
+        match self {
+            Phase::Passwords => ", user_id, domain",
+            Phase::PasswordHistory => ", password_id, field",
+            Phase::PreviousVaultKeys => ", user_id",
+            _ => "",
+        }
+    }
+
+    fn aad(self, row: &sqlx::sqlite::SqliteRow) -> Vec<u8> {
+        match self {
+            Phase::Passwords => passwords_aad(row.get("id"), row.get("user_id"), row.get("domain")),
+            Phase::PasswordHistory => history_field_aad(row.get("id"), row.get("password_id"), row.get("field")),
+            Phase::Users => vault_key_aad(row.get("id")),
+            Phase::PreviousVaultKeys => vault_key_aad(row.get("user_id")),
+            Phase::TotpSecrets => totp_aad(row.get("id")),
+            Phase::VaultEscrow => vault_escrow_aad(row.get("id")),
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn row_to_job(row: &sqlx::sqlite::SqliteRow) -> KeyRotationJob {
+    KeyRotationJob {
+        job_id: row.get("job_id"),
+        target_key_id: row.get("target_key_id"),
+        status: row.get("status"),
+        phase: row.get("phase"),
+        last_id: row.get("last_id"),
+        total_rows: row.get("total_rows"),
+        processed_rows: row.get("processed_rows"),
+        rewrapped_rows: row.get("rewrapped_rows"),
+        failed_rows: row.get("failed_rows"),
+        error: row.get("error"),
+        started_at: row.get("started_at"),
+        updated_at: row.get("updated_at"),
+        finished_at: row.get("finished_at"),
+    }
+}
+
+async fn fetch_job(pool: &SqlitePool, job_id: i64) -> Result<Option<KeyRotationJob>, sqlx::Error> {
+    let row = sqlx::query("SELECT * FROM key_rotation_jobs WHERE job_id = ?")
+        .bind(job_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.as_ref().map(row_to_job))
+}
+
+/// עבודה שלא הסתיימה (נעצרה באמצע או נכשלה) עבור המפתח הפעיל
+async fn find_unfinished_job(pool: &SqlitePool, key_id: u32) -> Result<Option<KeyRotationJob>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT * FROM key_rotation_jobs
+         WHERE target_key_id = ? AND status IN ('running', 'failed')
+         ORDER BY job_id DESC LIMIT 1"
+    )
+    .bind(key_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(row_to_job))
+}
+
+async fn create_job(pool: &SqlitePool, key_id: u32) -> Result<i64, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let total: i64 = sqlx::query(
+        "SELECT (SELECT COUNT(*) FROM passwords)
+              + (SELECT COUNT(*) FROM password_history)
+              + (SELECT COUNT(*) FROM users WHERE vault_key_wrapped IS NOT NULL)
+              + (SELECT COUNT(*) FROM previous_vault_keys)
+              + (SELECT COUNT(*) FROM users WHERE totp_secret_encrypted IS NOT NULL)
+              + (SELECT COUNT(*) FROM users WHERE vault_key_escrow IS NOT NULL) AS total"
+    )
+    .fetch_one(pool)
+    .await?
+    .get("total");
+
+    let result = sqlx::query(
+        "INSERT INTO key_rotation_jobs (target_key_id, status, phase, total_rows, started_at, updated_at)
+         VALUES (?, 'running', ?, ?, ?, ?)"
+    )
+    .bind(key_id)
+    .bind(Phase::Passwords.as_str())
+    .bind(total)
+    .bind(now)
+    .bind(now)
+    .execute(pool)
+    .await?;
+    Ok(result.last_insert_rowid())
+}
+
+// ===================== JOB RUNNER =====================
+/// מריץ את העבודה ברקע. מחזיר false אם כבר רצה עבודה אחרת
+fn spawn_job(pool: SqlitePool, job_id: i64) -> bool {
+    if JOB_RUNNING.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+        return false;
+    }
+
+    actix_web::rt::spawn(async move {
+        if let Err(e) = run_job(&pool, job_id, config::get().keys.allow_legacy_key).await {
+            log::error!("❌ Key rotation job #{} failed: {}", job_id, e);
+            let _ = sqlx::query(
+                "UPDATE key_rotation_jobs SET status = 'failed', error = ?, updated_at = ? WHERE job_id = ?"
+            )
+            .bind(e.to_string())
+            .bind(Utc::now().naive_utc())
+            .bind(job_id)
+            .execute(&pool)
+            .await;
+        }
+        JOB_RUNNING.store(false, Ordering::SeqCst);
+    });
+    true
+}
+
+/// allow_legacy: לפענח גם עם המפתח הקבוע הישן
+async fn run_job(pool: &SqlitePool, job_id: i64, allow_legacy: bool) -> Result<(), sqlx::Error> {
+    let job = fetch_job(pool, job_id).await?.ok_or(sqlx::Error::RowNotFound)?;
+    let mut phase = Phase::parse(&job.phase);
+    let mut last_id = job.last_id;
+
+    sqlx::query("UPDATE key_rotation_jobs SET status = 'running', error = NULL WHERE job_id = ?")
+        .bind(job_id)
+        .execute(pool)
+        .await?;
+    log::info!("🔄 Key rotation job #{} running ({} from id {})", job_id, phase.as_str(), last_id);
+
+    while let Some((table, id_column, value_column)) = phase.columns() {
+        let mut tx = pool.begin().await?;
+
+        let rows = sqlx::query(&format!(
+            "SELECT {id_column} AS id, {value_column} AS value{} FROM {table} WHERE {id_column} > ? AND {value_column} IS NOT NULL ORDER BY {id_column} LIMIT ?",
+            phase.aad_columns()
+        ))
+        .bind(last_id)
+        .bind(BATCH_SIZE)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if rows.is_empty() {
+            phase = phase.next();
+            last_id = 0;
+        }
+
+        let (mut rewrapped, mut failed) = (0i64, 0i64);
+        for row in &rows {
+            let id: i64 = row.get("id");
+            let value: String = row.get("value");
+            last_id = id;
+
+            let result = match rewrap(&value, &phase.aad(row), allow_legacy) {
+                // מפתח כספת שנעטף לפני שהיה קשור ל-user_id נשאר כך; הוא נקשר בהתחברות הבאה, כשהסיסמה ידועה
+                Err(_) if matches!(phase, Phase::Users | Phase::PreviousVaultKeys) => rewrap(&value, b"", allow_legacy),
+                result => result,
+            };
+            match result {
+                Ok(Some(new_value)) => {
+                    // אם השורה השתנתה בינתיים היא כבר מוצפנת עם המפתח הפעיל
+                    sqlx::query(&format!("UPDATE {table} SET {value_column} = ? WHERE {id_column} = ? AND {value_column} = ?"))
+                        .bind(&new_value)
+                        .bind(id)
+                        .bind(&value)
+                        .execute(&mut *tx)
+                        .await?;
+                    rewrapped += 1;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    log::warn!("⚠️ Cannot re-encrypt {}#{}: {}", table, id, e);
+                    failed += 1;
+                }
+            }
+        }
+
+        // ההתקדמות נשמרת באותה טרנזקציה, כך שאפשר להמשיך בדיוק מאותה נקודה
+        sqlx::query(
+            "UPDATE key_rotation_jobs SET
+                phase = ?, last_id = ?,
+                processed_rows = processed_rows + ?,
+                rewrapped_rows = rewrapped_rows + ?,
+                failed_rows = failed_rows + ?,
+                updated_at = ?
+             WHERE job_id = ?"
+        )
+        .bind(phase.as_str())
+        .bind(last_id)
+        .bind(rows.len() as i64)
+        .bind(rewrapped)
+        .bind(failed)
+        .bind(Utc::now().naive_utc())
+        .bind(job_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        if !rows.is_empty() {
+            log::info!("🔄 Key rotation job #{}: {} {} rows checked, {} re-encrypted", job_id, rows.len(), table, rewrapped);
+        }
+    }
+
+    let now = Utc::now().naive_utc();
+    sqlx::query("UPDATE key_rotation_jobs SET status = 'completed', finished_at = ?, updated_at = ? WHERE job_id = ?")
+        .bind(now)
+        .bind(now)
+        .bind(job_id)
+        .execute(pool)
+        .await?;
+    log::info!("✅ Key rotation job #{} completed", job_id);
+    if let Some(0) = enforce_bound_if_migrated(pool).await? {
+        log::info!("🔐 Every encrypted value is bound to its row; unbound values are refused from now on");
+    }
+    Ok(())
+}
+
+// ===================== ROW BINDING =====================
+/// כמה ערכים מוצפנים עוד לא קשורים לשורה שלהם: גרסה 1, בלי כותרת,
+/// או מפתח כספת שנעטף לפני שהיה קשור ל-user_id
+pub async fn count_unbound(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let mut count = 0;
+    let mut phase = Phase::Passwords;
+    while let Some((table, _, value_column)) = phase.columns() {
+        let unbound: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM {table} WHERE {value_column} IS NOT NULL AND NOT {}",
+            bound_value_sql(value_column)
+        ))
+        .fetch_one(pool)
+        .await?;
+        count += unbound;
+        if matches!(phase, Phase::Users | Phase::PreviousVaultKeys) {
+            count += count_unbound_vault_keys(pool, phase).await?;
+        }
+        phase = phase.next();
+    }
+    Ok(count)
+}
+
+/// מפתח כספת עטוף בגרסה 2 שה-AAD שלו ריק. לפי הכותרת הוא נראה קשור, לכן בודקים בפענוח, במנות
+async fn count_unbound_vault_keys(pool: &SqlitePool, phase: Phase) -> Result<i64, sqlx::Error> {
+    let Some((table, id_column, value_column)) = phase.columns() else {
+        return Ok(0);
+    };
+    let (mut count, mut last_id) = (0, 0i64);
+    loop {
+        let rows = sqlx::query(&format!(
+            "SELECT {id_column} AS id, {value_column} AS value{} FROM {table} WHERE {id_column} > ? AND {} ORDER BY {id_column} LIMIT ?",
+            phase.aad_columns(),
+            bound_value_sql(value_column)
+        ))
+        .bind(last_id)
+        .bind(BATCH_SIZE)
+        .fetch_all(pool)
+        .await?;
+        let Some(last) = rows.last() else {
+            return Ok(count);
+        };
+        last_id = last.get("id");
+        count += rows.iter().filter(|row| !opens_with(&row.get::<String, _>("value"), &phase.aad(row))).count() as i64;
+    }
+}
+
+/// בעליית השרת ובסוף כל עבודה: אם כבר אין ערכים לא קשורים, מסרבים להם מעכשיו.
+/// מחזיר כמה נשארו, או None כש-require_bound_ciphertexts כבוי
+pub async fn enforce_bound_if_migrated(pool: &SqlitePool) -> Result<Option<i64>, sqlx::Error> {
+    if !config::get().keys.require_bound_ciphertexts {
+        return Ok(None);
+    }
+    let unbound = count_unbound(pool).await?;
+    if unbound == 0 {
+        refuse_unbound();
+    }
+    Ok(Some(unbound))
+}
+
+// ===================== STARTUP =====================
+/// בעליית השרת: ממשיך עבודה שנקטעה, או מתחיל עבודה חדשה אם המפתח הפעיל עוד לא הושלם
+pub async fn resume_on_startup(pool: &SqlitePool) -> Result<Option<i64>, Box<dyn std::error::Error>> {
+    let key_id = key_ring()?.active_id();
+
+    let job_id = match find_unfinished_job(pool, key_id).await? {
+        Some(job) => job.job_id,
+        None => {
+            let completed: Option<(i64,)> = sqlx::query_as(
+                "SELECT job_id FROM key_rotation_jobs WHERE target_key_id = ? AND status = 'completed' LIMIT 1"
+            )
+            .bind(key_id)
+            .fetch_optional(pool)
+            .await?;
+            if completed.is_some() {
+                return Ok(None);
+            }
+            create_job(pool, key_id).await?
+        }
+    };
+
+    spawn_job(pool.clone(), job_id);
+    Ok(Some(job_id))
+}
+
+// ===================== LEGACY KEY =====================
+/// keys migrate-legacy: עבודה מלאה בחזית, עם המפתח הקבוע הישן גם אם הוא כבוי בהגדרות.
+/// מחזיר את העבודה בסוף. failed_rows = 0 אומר שאף שורה כבר לא תלויה במפתח הישן
+pub async fn migrate_legacy(pool: &SqlitePool) -> Result<KeyRotationJob, Box<dyn std::error::Error>> {
+    let key_id = key_ring()?.active_id();
+    let job_id = match find_unfinished_job(pool, key_id).await? {
+        Some(job) => job.job_id,
+        None => create_job(pool, key_id).await?,
+    };
+    run_job(pool, job_id, true).await?;
+    Ok(fetch_job(pool, job_id).await?.ok_or(sqlx::Error::RowNotFound)?)
+}
+
+// ===================== START / RESUME =====================
+fn job_running() -> ApiError {
+    ApiError::new(ErrorCode::Conflict, "A key rotation job is already running")
+}
+
+#[post("/admin/key-rotation")]
+pub async fn start_key_rotation(pool: web::Data<SqlitePool>) -> Result<HttpResponse, ApiError> {
+    if JOB_RUNNING.load(Ordering::SeqCst) {
+        return Err(job_running());
+    }
+
+    let key_id = key_ring().or_internal("Key error")?.active_id();
+
+    let job_id = match find_unfinished_job(&pool, key_id).await? {
+        Some(job) => job.job_id,
+        None => create_job(&pool, key_id).await?,
+    };
+
+    if !spawn_job(pool.get_ref().clone(), job_id) {
+        return Err(job_running());
+    }
+
+    let job = fetch_job(&pool, job_id).await?.ok_or_else(job_not_found)?;
+    Ok(HttpResponse::Accepted().json(job))
+}
+
+// ===================== READ ALL JOBS =====================
+#[get("/admin/key-rotation")]
+pub async fn get_key_rotation_jobs(pool: web::Data<SqlitePool>) -> Result<HttpResponse, ApiError> {
+    let rows = sqlx::query("SELECT * FROM key_rotation_jobs ORDER BY job_id DESC")
+        .fetch_all(&**pool)
+        .await?;
+    let jobs: Vec<KeyRotationJob> = rows.iter().map(row_to_job).collect();
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+// ===================== READ ONE JOB =====================
+fn job_not_found() -> ApiError {
+    ApiError::not_found("Job not found")
+}
+
+#[get("/admin/key-rotation/{id}")]
+pub async fn get_key_rotation_job(pool: web::Data<SqlitePool>, path: web::Path<i64>) -> Result<HttpResponse, ApiError> {
+    let job = fetch_job(&pool, path.into_inner()).await?.ok_or_else(job_not_found)?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose, Engine as _};
+    use crate::config::Config;
+    use crate::repositories::sqlite::SqliteStore;
+    use crate::repositories::users::UserRepository;
+    use crate::utils::db::{new_user, test_pool};
+    use crate::utils::encryption::{decrypt_password, encrypt_password, encrypt_with_key_id, needs_rewrap};
+    use crate::utils::keys::{load_test_keys, TEST_RETIRED_KEY_ID};
+
+    async fn setup(name: &str) -> (SqlitePool, i64) {
+        let _ = config::init(Config::default());
+        load_test_keys();
+        let pool = test_pool(name).await;
+        let user = UserRepository::create(&SqliteStore::new(pool.clone()), new_user("a@x.io")).await.unwrap();
+        (pool, user.user_id)
+    }
+
+    /// שורה ב-passwords. value מקבל את ה-AAD של השורה, כי ה-password_id ידוע רק אחרי ההכנסה
+    async fn insert_password(pool: &SqlitePool, user_id: i64, domain: &str, value: impl FnOnce(&[u8]) -> String) -> i64 {
+        let now = Utc::now().naive_utc();
+        let password_id = sqlx::query("INSERT INTO passwords (user_id, domain, password_encrypted, created_at, updated_at) VALUES (?, ?, '', ?, ?)")
+            .bind(user_id)
+            .bind(domain)
+            .bind(now)
+            .bind(now)
+            .execute(pool)
+            .await
+            .unwrap()
+            .last_insert_rowid();
+        sqlx::query("UPDATE passwords SET password_encrypted = ? WHERE password_id = ?")
+            .bind(value(&passwords_aad(password_id, user_id, domain)))
+            .bind(password_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        password_id
+    }
+
+    async fn stored(pool: &SqlitePool, password_id: i64) -> String {
+        sqlx::query_scalar("SELECT password_encrypted FROM passwords WHERE password_id = ?")
+            .bind(password_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn interrupted_job_resumes_after_its_last_id() {
+        let (pool, user_id) = setup("key-rotation-resume").await;
+        let retired = |aad: &[u8]| encrypt_with_key_id(TEST_RETIRED_KEY_ID, "p1", aad).unwrap();
+        let ids = [
+            insert_password(&pool, user_id, "a.com", retired).await,
+            insert_password(&pool, user_id, "b.com", retired).await,
+            insert_password(&pool, user_id, "c.com", retired).await,
+        ];
+        // ערך שלא נפתח בשום מפתח: נספר כנכשל, ונשאר לא קשור (כך העבודה לא מדליקה את BOUND_ONLY לשאר הבדיקות)
+        let broken = insert_password(&pool, user_id, "d.com", |_| "bm90IGEgY2lwaGVydGV4dA==".to_string()).await;
+
+        // העבודה נעצרה אחרי השורה השנייה, וההתקדמות נשמרה
+        let active = key_ring().unwrap().active_id();
+        let job_id = create_job(&pool, active).await.unwrap();
+        sqlx::query("UPDATE key_rotation_jobs SET last_id = ?, processed_rows = 2 WHERE job_id = ?")
+            .bind(ids[1])
+            .bind(job_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        run_job(&pool, job_id, false).await.unwrap();
+
+        for id in &ids[..2] {
+            assert!(needs_rewrap(&stored(&pool, *id).await).unwrap(), "row {} was before last_id", id);
+        }
+        let moved = stored(&pool, ids[2]).await;
+        assert!(!needs_rewrap(&moved).unwrap());
+        assert_eq!(decrypt_password(&moved, &passwords_aad(ids[2], user_id, "c.com")).unwrap(), "p1");
+        assert_eq!(stored(&pool, broken).await, "bm90IGEgY2lwaGVydGV4dA==");
+
+        let job = fetch_job(&pool, job_id).await.unwrap().unwrap();
+        assert_eq!((job.status.as_str(), job.phase.as_str()), ("completed", "done"));
+        assert_eq!((job.rewrapped_rows, job.failed_rows), (1, 1));
+        assert_eq!(job.processed_rows, job.total_rows);
+    }
+
+    #[actix_web::test]
+    async fn unbound_values_are_counted_from_the_header_and_the_vault_key_aad() {
+        let (pool, user_id) = setup("count-unbound").await;
+        assert_eq!(count_unbound(&pool).await.unwrap(), 0);
+
+        // קשור, גם במפתח ישן
+        insert_password(&pool, user_id, "a.com", |aad| encrypt_password("p1", aad).unwrap()).await;
+        insert_password(&pool, user_id, "b.com", |aad| encrypt_with_key_id(TEST_RETIRED_KEY_ID, "p1", aad).unwrap()).await;
+        assert_eq!(count_unbound(&pool).await.unwrap(), 0);
+
+        // גרסה 1, בלי כותרת, קצר מדי
+        let mut unbound = general_purpose::STANDARD.decode(encrypt_password("p1", b"").unwrap()).unwrap();
+        unbound[0] = 1;
+        let unbound = general_purpose::STANDARD.encode(unbound);
+        insert_password(&pool, user_id, "c.com", |_| unbound).await;
+        insert_password(&pool, user_id, "d.com", |_| general_purpose::STANDARD.encode([7u8; 40])).await;
+        insert_password(&pool, user_id, "e.com", |_| general_purpose::STANDARD.encode([2u8; 32])).await;
+        assert_eq!(count_unbound(&pool).await.unwrap(), 3);
+
+        // מפתח כספת שנעטף בגרסה 2 אבל בלי user_id ב-AAD
+        sqlx::query("UPDATE users SET vault_key_wrapped = ? WHERE user_id = ?")
+            .bind(encrypt_password("wrapped", b"").unwrap())
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count_unbound(&pool).await.unwrap(), 4);
+    }
+}
@@ -0,0 +1,141 @@
+use actix_web::{get, delete, web, HttpResponse};
+use chrono::{Duration, NaiveDateTime, Utc};
+use sqlx::{SqlitePool, Row};
+use crate::errors::ApiError;
+use crate::models::lockout::LoginThrottle;
+
+pub const SCOPE_ACCOUNT: &str = "account";
+pub const SCOPE_IP: &str = "ip";
+
+// כמה כישלונות מותרים לפני נעילה, לכל חשבון ולכל כתובת IP
+const ACCOUNT_FREE_ATTEMPTS: i64 = 5;
+const IP_FREE_ATTEMPTS: i64 = 20;
+// הנעילה מתחילה ב-30 שניות ומוכפלת בכל כישלון נוסף, עד 15 דקות
+const BASE_LOCK_SECONDS: i64 = 30;
+const MAX_LOCK_SECONDS: i64 = 15 * 60;
+// אחרי שעה בלי כישלונות המונה מתאפס
+const RESET_AFTER_MINUTES: i64 = 60;
+
+/// המפתח של חשבון הוא המייל כפי שהוקלד, כדי שגם מייל שלא קיים ייספר (בלי לחשוף אם הוא קיים)
+pub fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+fn free_attempts(scope: &str) -> i64 {
+    if scope == SCOPE_IP { IP_FREE_ATTEMPTS } else { ACCOUNT_FREE_ATTEMPTS }
+}
+
+fn lock_duration(scope: &str, failed_count: i64) -> Option<Duration> {
+    let over = failed_count - free_attempts(scope);
+    if over < 0 {
+        return None;
+    }
+    let seconds = BASE_LOCK_SECONDS.saturating_mul(1i64 << over.min(30));
+    Some(Duration::seconds(seconds.min(MAX_LOCK_SECONDS)))
+}
+
+// ===================== CHECK / RECORD =====================
+/// כמה שניות נשארו לנעילה של החשבון או של ה-IP. None אם אפשר לנסות
+pub async fn locked_for(pool: &SqlitePool, email: &str, ip: Option<&str>) -> Result<Option<i64>, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let row = sqlx::query(
+        "SELECT MAX(locked_until) AS locked_until FROM login_throttle
+         WHERE ((scope = ? AND key = ?) OR (scope = ? AND key = ?)) AND locked_until > ?"
+    )
+    .bind(SCOPE_ACCOUNT)
+    .bind(account_key(email))
+    .bind(SCOPE_IP)
+    .bind(ip.unwrap_or_default())
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    let locked_until: Option<NaiveDateTime> = row.get("locked_until");
+    Ok(locked_until.map(|until| (until - now).num_seconds().max(1)))
+}
+
+async fn record_failure_for(pool: &SqlitePool, scope: &str, key: &str) -> Result<(), sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    let reset_before = now - Duration::minutes(RESET_AFTER_MINUTES);
+
+    let mut tx = pool.begin().await?;
+    let failed_count: i64 = sqlx::query(
+        "INSERT INTO login_throttle (scope, key, failed_count, last_failed_at) VALUES (?, ?, 1, ?)
+         ON CONFLICT(scope, key) DO UPDATE SET
+            failed_count = CASE WHEN last_failed_at < ? THEN 1 ELSE failed_count + 1 END,
+            last_failed_at = excluded.last_failed_at
+         RETURNING failed_count"
+    )
+    .bind(scope)
+    .bind(key)
+    .bind(now)
+    .bind(reset_before)
+    .fetch_one(&mut *tx)
+    .await?
+    .get("failed_count");
+
+    if let Some(duration) = lock_duration(scope, failed_count) {
+        sqlx::query("UPDATE login_throttle SET locked_until = ? WHERE scope = ? AND key = ?")
+            .bind(now + duration)
+            .bind(scope)
+            .bind(key)
+            .execute(&mut *tx)
+            .await?;
+        log::warn!("⚠️ Login locked for {} {} ({} failures)", scope, key, failed_count);
+    }
+    tx.commit().await
+}
+
+/// כישלון התחברות: נספר גם לחשבון וגם ל-IP
+pub async fn record_failure(pool: &SqlitePool, email: &str, ip: Option<&str>) -> Result<(), sqlx::Error> {
+    record_failure_for(pool, SCOPE_ACCOUNT, &account_key(email)).await?;
+    if let Some(ip) = ip {
+        record_failure_for(pool, SCOPE_IP, ip).await?;
+    }
+    Ok(())
+}
+
+/// התחברות מוצלחת מאפסת את החשבון. מונה ה-IP נשאר, כדי שחשבון אחד
+/// של התוקף לא יאפשר לו להמשיך לנחש סיסמאות של אחרים
+pub async fn record_success(pool: &SqlitePool, email: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM login_throttle WHERE scope = ? AND key = ?")
+        .bind(SCOPE_ACCOUNT)
+        .bind(account_key(email))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// ===================== ADMIN: VIEW =====================
+#[get("/admin/lockouts")]
+pub async fn get_lockouts(pool: web::Data<SqlitePool>) -> Result<HttpResponse, ApiError> {
+    let rows = sqlx::query("SELECT * FROM login_throttle ORDER BY last_failed_at DESC")
+        .fetch_all(&**pool)
+        .await?;
+    let lockouts: Vec<LoginThrottle> = rows.iter().map(|row| LoginThrottle {
+        scope: row.get("scope"),
+        key: row.get("key"),
+        failed_count: row.get("failed_count"),
+        last_failed_at: row.get::<NaiveDateTime, _>("last_failed_at"),
+        locked_until: row.get::<Option<NaiveDateTime>, _>("locked_until"),
+    }).collect();
+    Ok(HttpResponse::Ok().json(lockouts))
+}
+
+// ===================== ADMIN: CLEAR =====================
+/// scope הוא account או ip. key הוא המייל או כתובת ה-IP
+#[delete("/admin/lockouts/{scope}/{key}")]
+pub async fn clear_lockout(pool: web::Data<SqlitePool>, path: web::Path<(String, String)>) -> Result<HttpResponse, ApiError> {
+    let (scope, key) = path.into_inner();
+    let key = if scope == SCOPE_ACCOUNT { account_key(&key) } else { key };
+
+    let result = sqlx::query("DELETE FROM login_throttle WHERE scope = ? AND key = ?")
+        .bind(&scope)
+        .bind(&key)
+        .execute(&**pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::not_found("Lockout not found"));
+    }
+    Ok(HttpResponse::Ok().body("Lockout cleared"))
+}
@@ -0,0 +1,62 @@
+use actix_web::{get, http::header, web, HttpRequest, HttpResponse};
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{SqlitePool, Row};
+use crate::errors::ApiError;
+use crate::controllers::users_controller::client_ip;
+use crate::models::login_history::LoginHistoryEntry;
+
+// תוצאות אפשריות של ניסיון התחברות
+pub const LOGIN_SUCCESS: &str = "success";
+pub const LOGIN_INVALID_CREDENTIALS: &str = "invalid_credentials";
+pub const LOGIN_LOCKED: &str = "locked";
+pub const LOGIN_INACTIVE: &str = "inactive";
+pub const LOGIN_MFA_REQUIRED: &str = "mfa_required";
+pub const LOGIN_MFA_FAILED: &str = "mfa_failed";
+
+// ===================== RECORD =====================
+/// רושם ניסיון התחברות. user_id ריק כשהמייל לא שייך לאף משתמש.
+/// כישלון ברישום לא מפיל את ההתחברות עצמה
+pub async fn record_login(pool: &SqlitePool, req: &HttpRequest, user_id: Option<i64>, email: &str, result: &str) {
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.chars().take(512).collect::<String>());
+
+    if let Err(e) = sqlx::query(
+        "INSERT INTO login_history (user_id, email, ip_address, user_agent, result, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)"
+    )
+    .bind(user_id)
+    .bind(email)
+    .bind(client_ip(req))
+    .bind(user_agent)
+    .bind(result)
+    .bind(Utc::now().naive_utc())
+    .execute(pool)
+    .await
+    {
+        log::warn!("⚠️ Failed to record login history for {}: {}", email, e);
+    }
+}
+
+// ===================== READ USER LOGINS =====================
+#[get("/users/{id}/logins")]
+pub async fn get_user_logins(pool: web::Data<SqlitePool>, path: web::Path<i64>) -> Result<HttpResponse, ApiError> {
+    let user_id = path.into_inner();
+
+    let rows = sqlx::query("SELECT * FROM login_history WHERE user_id = ? ORDER BY login_id DESC")
+        .bind(user_id)
+        .fetch_all(&**pool)
+        .await?;
+    let logins: Vec<LoginHistoryEntry> = rows.iter().map(|row| LoginHistoryEntry {
+        login_id: row.get("login_id"),
+        user_id: row.get("user_id"),
+        email: row.get("email"),
+        ip_address: row.get("ip_address"),
+        user_agent: row.get("user_agent"),
+        result: row.get("result"),
+        created_at: row.get::<NaiveDateTime, _>("created_at"),
+    }).collect();
+    Ok(HttpResponse::Ok().json(logins))
+}
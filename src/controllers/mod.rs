@@ -3,3 +3,14 @@ pub mod passwords_controller;
 pub mod categories_controller;
 pub mod password_category_controller;
 pub mod password_history_controller;
+pub mod key_rotation_controller;
+pub mod sessions_controller;
+
+pub mod roles_controller;
+pub mod audit_controller;
+pub mod totp_controller;
+pub mod lockout_controller;
+pub mod login_history_controller;
+pub mod password_reset_controller;
+pub mod email_verification_controller;
+pub mod search_controller;
\ No newline at end of file
@@ -1,127 +1,60 @@
-use actix_web::{post, delete, get, web, HttpResponse, Responder};
-use sqlx::SqlitePool;
-use crate::models::password_category::{PasswordCategory, CreatePasswordCategoryDto};
-use sqlx::Row;
-
-// ===================== INIT DATABASE =====================
-pub async fn init_db() -> Result<SqlitePool, sqlx::Error> {
-    // let database_url = "C:\\Users\\1\\Documents\\GitHub\\PassMenSystem\\src\\passwords_management_system.db";
-    let database_url = "sqlite://src/passwords_management_system.db";
-    let pool = SqlitePool::connect(database_url).await?;
-
-    println!("Creating password_category table if not exists...");
-    sqlx::query(
-        r#"
-        CREATE TABLE password_category (
-    password_id INTEGER NOT NULL,
-    category_id INTEGER NOT NULL,
-    PRIMARY KEY(password_id, category_id),
-    FOREIGN KEY(password_id) REFERENCES passwords(password_id) ON DELETE CASCADE,
-    FOREIGN KEY(category_id) REFERENCES categories(category_id) ON DELETE CASCADE
-            
-        );
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-    println!("password_category table ready");
-    Ok(pool)
-}
+use actix_web::{post, delete, get, web, HttpResponse};
+use crate::errors::ApiError;
+use crate::utils::validation::Validate;
+use crate::utils::pagination::{paged_response, ListQuery};
+use crate::controllers::passwords_controller::PASSWORD_NOT_FOUND;
+use crate::models::password_category::{PasswordCategoryFilter, CreatePasswordCategoryDto};
+use crate::repositories::RepoError;
+use crate::repositories::categories::CategoryRepository;
+use crate::repositories::passwords::PasswordRepository;
+use crate::middleware::auth::AuthUser;
 
 // ===================== CREATE =====================
 #[post("/password-category")]
 pub async fn create_password_category(
-    pool: web::Data<SqlitePool>,
-    pc: web::Json<CreatePasswordCategoryDto>
-) -> impl Responder {
-    let result = sqlx::query("INSERT INTO password_category (password_id, category_id) VALUES (?, ?)")
-        .bind(pc.password_id)
-        .bind(pc.category_id)
-        .execute(&**pool)
-        .await;
-
-    match result {
-        Ok(_) => HttpResponse::Created().body("Password-Category link created"),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
-    }
+    passwords: web::Data<dyn PasswordRepository>,
+    pc: web::Json<CreatePasswordCategoryDto>,
+    user: AuthUser,
+) -> Result<HttpResponse, ApiError> {
+    pc.validate()?;
+    // מקשרים רק סיסמה של המשתמש המחובר. קטגוריה שלא קיימת => 422, קישור שכבר קיים => 409
+    passwords
+        .link_category(user.user_id, pc.password_id, pc.category_id)
+        .await
+        .map_err(|e| match e {
+            RepoError::NotFound => ApiError::not_found(PASSWORD_NOT_FOUND),
+            e => e.into(),
+        })?;
+    Ok(HttpResponse::Created().body("Password-Category link created"))
 }
 
 // ===================== DELETE =====================
 #[delete("/password-category")]
 pub async fn delete_password_category(
-    pool: web::Data<SqlitePool>,
-    pc: web::Json<CreatePasswordCategoryDto>
-) -> impl Responder {
-    let result = sqlx::query("DELETE FROM password_category WHERE password_id = ? AND category_id = ?")
-        .bind(pc.password_id)
-        .bind(pc.category_id)
-        .execute(&**pool)
-        .await;
-
-    match result {
-        Ok(r) if r.rows_affected() > 0 => HttpResponse::Ok().body("Password-Category link deleted"),
-        Ok(_) => HttpResponse::NotFound().body("Link not found"),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
+    passwords: web::Data<dyn PasswordRepository>,
+    pc: web::Json<CreatePasswordCategoryDto>,
+    user: AuthUser,
+) -> Result<HttpResponse, ApiError> {
+    pc.validate()?;
+    if !passwords.unlink_category(user.user_id, pc.password_id, pc.category_id).await? {
+        return Err(ApiError::not_found("Link not found"));
     }
+    Ok(HttpResponse::Ok().body("Password-Category link deleted"))
 }
 
 // ===================== GET ALL =====================
 #[get("/password-category")]
-pub async fn get_all_password_categories(pool: web::Data<SqlitePool>) -> impl Responder {
-    let result = sqlx::query("SELECT password_id, category_id FROM password_category")
-        .fetch_all(&**pool)
-        .await;
-
-    match result {
-        Ok(rows) => {
-            let links: Vec<PasswordCategory> = rows.into_iter().map(|row| PasswordCategory {
-                password_id: row.get::<i64, _>("password_id"),
-                category_id: row.get::<i64, _>("category_id"),
-            }).collect();
-
-            HttpResponse::Ok().json(links)
-        }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
-    }
+pub async fn get_all_password_categories(
+    passwords: web::Data<dyn PasswordRepository>,
+    query: ListQuery<PasswordCategoryFilter>,
+    user: AuthUser,
+) -> Result<HttpResponse, ApiError> {
+    let page = passwords.list_category_links(user.user_id, &query.filters, &query.page).await?;
+    Ok(paged_response(page, |link| link))
 }
-#[derive(serde::Serialize)]
-pub struct UserShoppingInfo {
-    pub user_id: i64,
-    pub user_first_name: String,
-    pub user_last_name: String,
-    pub category_name: String,
-}
-#[get("/password_category/users_with_com")]
-pub async fn get_users_with_com_passwords(pool: web::Data<SqlitePool>) -> impl Responder {
-    // נגדיר query_result עם טיפוס מפורש
-    let query_result: Result<Vec<sqlx::sqlite::SqliteRow>, sqlx::Error> = sqlx::query(
-        r#"
-        SELECT DISTINCT 
-            u.user_id, 
-            u.user_first_name, 
-            u.user_last_name, 
-            c.category_name
-        FROM users u
-        JOIN passwords p ON u.user_id = p.user_id
-        JOIN password_category pc ON p.password_id = pc.password_id
-        JOIN categories c ON pc.category_id = c.category_id
-        WHERE c.category_name = '.com';
-        "#
-    )
-    .fetch_all(&**pool)
-    .await;
 
-    match query_result {
-        Ok(rows) => {
-            let result: Vec<UserShoppingInfo> = rows.iter().map(|row| UserShoppingInfo {
-                user_id: row.get("user_id"),
-                user_first_name: row.get("user_first_name"),
-                user_last_name: row.get("user_last_name"),
-                category_name: row.get("category_name"),
-            }).collect();
-
-            HttpResponse::Ok().json(result)
-        }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
-    }
+#[get("/password_category/users_with_com")]
+pub async fn get_users_with_com_passwords(categories: web::Data<dyn CategoryRepository>) -> Result<HttpResponse, ApiError> {
+    let result = categories.users_in(".com").await?;
+    Ok(HttpResponse::Ok().json(result))
 }
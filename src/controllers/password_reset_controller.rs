@@ -0,0 +1,247 @@
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use chrono::{Duration, NaiveDateTime, Utc};
+use sqlx::{SqlitePool, Row};
+use crate::config;
+use crate::errors::{ApiError, ErrorCode, OrInternal};
+use crate::utils::validation::Validate;
+use crate::controllers::audit_controller::{record, ACTION_PASSWORD_RESET};
+use crate::controllers::lockout_controller;
+use crate::controllers::sessions_controller::revoke_user_sessions;
+use crate::controllers::users_controller::{client_ip, weak_password};
+use crate::models::password_reset::{ForgotPasswordDto, ResetPasswordDto};
+use crate::utils::hash::hash_password;
+use crate::utils::email::normalize_email;
+use crate::utils::password_policy::check_master_password;
+use crate::utils::mailer::{send_in_background, Mail, MailTransport};
+use crate::utils::tokens::{generate_token, hash_token};
+use crate::utils::db::in_transaction;
+use crate::utils::vault::{recover_vault_key, wrap_vault_key, VaultKey, VaultSessions};
+
+// לא שולחים יותר ממייל איפוס אחד בדקה לאותו חשבון
+const RESEND_INTERVAL_SECONDS: i64 = 60;
+
+// אותה תשובה למייל קיים ולא קיים
+const FORGOT_PASSWORD_RESPONSE: &str = "If the email belongs to an active account, a reset token was sent to it";
+const INVALID_RESET_TOKEN: &str = "Invalid or expired reset token";
+
+// כמה זמן קישור האיפוס תקף
+fn token_ttl() -> Duration {
+    Duration::minutes(config::get().tokens.reset_minutes)
+}
+
+// ===================== HELPERS =====================
+/// יוצר token חדש ומבטל את כל הקודמים של המשתמש. None אם נשלח token לפני פחות מדקה
+async fn issue_token(pool: &SqlitePool, user_id: i64) -> Result<Option<(String, NaiveDateTime)>, sqlx::Error> {
+    let now = Utc::now().naive_utc();
+
+    let recent = sqlx::query("SELECT 1 FROM password_reset_tokens WHERE user_id = ? AND created_at > ? LIMIT 1")
+        .bind(user_id)
+        .bind(now - Duration::seconds(RESEND_INTERVAL_SECONDS))
+        .fetch_optional(pool)
+        .await?;
+    if recent.is_some() {
+        return Ok(None);
+    }
+
+    invalidate_tokens(pool, user_id).await?;
+
+    let token = generate_token();
+    let expires_at = now + token_ttl();
+    sqlx::query(
+        "INSERT INTO password_reset_tokens (user_id, token_hash, created_at, expires_at)
+         VALUES (?, ?, ?, ?)"
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(now)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+    Ok(Some((token, expires_at)))
+}
+
+/// מבטל את כל קישורי האיפוס הפתוחים של המשתמש (גם אחרי שינוי סיסמה)
+pub async fn invalidate_tokens(pool: &SqlitePool, user_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE password_reset_tokens SET used_at = ? WHERE user_id = ? AND used_at IS NULL")
+        .bind(Utc::now().naive_utc())
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// loses_vault: שחזור הכספת כבוי, והאיפוס יפתח כספת חדשה. הישנה נשמרת נעולה בסיסמה הישנה
+fn reset_mail(email: &str, token: &str, expires_at: NaiveDateTime, loses_vault: bool) -> Mail {
+    let warning = if loses_vault {
+        "Vault recovery is off for this account: after the reset you start with an empty vault. Your \
+         saved passwords are not deleted, but they stay locked until you unlock them with the old \
+         password (POST /auth/vault/restore). If you still know the old password, log in with it instead.\n\n"
+    } else {
+        ""
+    };
+    Mail {
+        to: email.to_string(),
+        subject: "Password reset".to_string(),
+        body: format!(
+            "A password reset was requested for your account.\n\n\
+             Reset token: {}\n\n\
+             Send it to POST /auth/reset-password with your new password before {} UTC.\n\n\
+             {}\
+             If you did not ask for this, ignore this email.",
+            token,
+            expires_at.format("%Y-%m-%d %H:%M"),
+            warning
+        ),
+    }
+}
+
+// ===================== FORGOT PASSWORD =====================
+#[post("/auth/forgot-password")]
+pub async fn forgot_password(
+    pool: web::Data<SqlitePool>,
+    mailer: web::Data<dyn MailTransport>,
+    body: web::Json<ForgotPasswordDto>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate()?;
+    // כתובת לא תקינה לא שייכת לאף חשבון, אבל התשובה זהה
+    let Ok(email) = normalize_email(&body.email) else {
+        return Ok(HttpResponse::Accepted().body(FORGOT_PASSWORD_RESPONSE));
+    };
+
+    let user: Option<(i64, String, bool)> = sqlx::query(
+        "SELECT user_id, email, vault_key_wrapped IS NOT NULL AND vault_key_escrow IS NULL AS loses_vault
+         FROM users WHERE lower(email) = ? AND is_active = 1"
+    )
+    .bind(&email)
+    .fetch_optional(&**pool)
+    .await?
+    .map(|row| (row.get("user_id"), row.get("email"), row.get("loses_vault")));
+
+    if let Some((user_id, email, loses_vault)) = user
+        && let Some((token, expires_at)) = issue_token(&pool, user_id).await?
+    {
+        send_in_background(mailer.into_inner(), reset_mail(&email, &token, expires_at, loses_vault));
+    }
+
+    Ok(HttpResponse::Accepted().body(FORGOT_PASSWORD_RESPONSE))
+}
+
+// ===================== RESET PASSWORD =====================
+#[post("/auth/reset-password")]
+pub async fn reset_password(
+    pool: web::Data<SqlitePool>,
+    sessions: web::Data<VaultSessions>,
+    req: HttpRequest,
+    body: web::Json<ResetPasswordDto>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate()?;
+    let invalid_token = || ApiError::new(ErrorCode::InvalidEmailToken, INVALID_RESET_TOKEN);
+    let now = Utc::now().naive_utc();
+    let row = sqlx::query(
+        "SELECT t.token_id, u.user_id, u.email, u.vault_key_wrapped, u.vault_key_escrow
+         FROM password_reset_tokens t
+         JOIN users u ON u.user_id = t.user_id
+         WHERE t.token_hash = ? AND t.used_at IS NULL AND t.expires_at > ? AND u.is_active = 1"
+    )
+    .bind(hash_token(&body.token))
+    .bind(now)
+    .fetch_optional(&**pool)
+    .await?
+    .ok_or_else(invalid_token)?;
+    let token_id: i64 = row.get("token_id");
+    let user_id: i64 = row.get("user_id");
+    let email: String = row.get("email");
+    let wrapped: Option<String> = row.get("vault_key_wrapped");
+    let escrow: Option<String> = row.get("vault_key_escrow");
+
+    check_master_password(&body.new_password, &email).map_err(weak_password)?;
+
+    // את מפתח הכספת עוטפים מחדש בסיסמה החדשה. בלי עותק במפתח השרת (שחזור כבוי) אין דרך
+    // לפתוח אותו: נוצרת כספת חדשה, והמפתח הישן נשמר עטוף בסיסמה הישנה יחד עם הסיסמאות שלו
+    let (rewrapped, new_vault) = match (wrapped, escrow) {
+        (_, Some(escrow)) => {
+            let key = recover_vault_key(user_id, &escrow).or_internal("Vault key error")?;
+            (Some(wrap_vault_key(user_id, &key, &body.new_password).or_internal("Vault key error")?), false)
+        }
+        (Some(_), None) => {
+            let key = VaultKey::generate();
+            (Some(wrap_vault_key(user_id, &key, &body.new_password).or_internal("Vault key error")?), true)
+        }
+        // משתמש ישן בלי כספת - המפתח ייווצר בהתחברות הבאה
+        (None, None) => (None, false),
+    };
+
+    let password_hash = hash_password(&body.new_password).or_internal("Hashing error")?;
+
+    // ה-token, הסיסמה והעברת המפתח הישן ל-previous_vault_keys - הכל או כלום
+    let locked = in_transaction(&pool, async |conn| -> Result<Option<i64>, ApiError> {
+        // בקשה מקבילה עם אותו token תיכשל כאן
+        let claimed = sqlx::query("UPDATE password_reset_tokens SET used_at = ? WHERE token_id = ? AND used_at IS NULL")
+            .bind(now)
+            .bind(token_id)
+            .execute(&mut *conn)
+            .await?;
+        if claimed.rows_affected() == 0 {
+            return Err(invalid_token());
+        }
+
+        if new_vault {
+            sqlx::query(
+                "INSERT INTO previous_vault_keys (user_id, vault_key_salt, vault_key_wrapped, replaced_at)
+                 SELECT user_id, vault_key_salt, vault_key_wrapped, ? FROM users
+                 WHERE user_id = ? AND vault_key_wrapped IS NOT NULL"
+            )
+            .bind(now)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        sqlx::query(
+            "UPDATE users SET
+                password_hash_to_login = ?,
+                vault_key_salt = COALESCE(?, vault_key_salt),
+                vault_key_wrapped = COALESCE(?, vault_key_wrapped),
+                updated_at = ?
+            WHERE user_id = ?"
+        )
+        .bind(&password_hash)
+        .bind(rewrapped.as_ref().map(|w| &w.salt))
+        .bind(rewrapped.as_ref().map(|w| &w.wrapped))
+        .bind(now)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+        if !new_vault {
+            return Ok(None);
+        }
+        // לא מוחקים כלום: הסיסמאות נשארות מוצפנות במפתח הישן
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM passwords WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        Ok(Some(count))
+    })
+    .await?;
+
+    // מי שהחזיק בסיסמה הישנה מתנתק, ונעילה על החשבון מתבטלת
+    invalidate_tokens(&pool, user_id).await?;
+    revoke_user_sessions(&pool, &sessions, user_id).await?;
+    lockout_controller::record_success(&pool, &email).await?;
+
+    let ip = client_ip(&req);
+    if let Err(e) = record(&pool, user_id, ACTION_PASSWORD_RESET, "users", user_id, ip.as_deref()).await {
+        log::warn!("⚠️ Failed to audit password reset of user {}: {}", user_id, e);
+    }
+
+    Ok(HttpResponse::Ok().body(match locked {
+        None => "Password reset, log in with the new password".to_string(),
+        Some(count) => format!(
+            "Password reset, log in with the new password. Vault recovery was off, so the old vault \
+             could not be opened: a new vault was created, and {} saved passwords stay locked until you \
+             unlock them with the old password (POST /auth/vault/restore)",
+            count
+        ),
+    }))
+}
@@ -1,33 +1,63 @@
-use chrono::{NaiveDateTime, Utc};
-use actix_web::{get, post, put, delete, web, HttpResponse, Responder};
+use actix_web::{get, post, put, delete, web, HttpRequest, HttpResponse};
 use sqlx::{SqlitePool, Row};
-use crate::models::passwords::{Password, CreatePasswordDto, UpdatePasswordDto};
-use crate::utils::encryption::{encrypt_password, decrypt_password};
-use crate::controllers::password_history_controller::create_password_history_internal; // נדרש בשביל היסטוריית סיסמאות
-
-// ===================== INIT DATABASE =====================
-pub async fn init_db() -> Result<SqlitePool, sqlx::Error> {
-    let database_url = "sqlite://src/passwords_management_system.db";
-    let pool = SqlitePool::connect(database_url).await?;
-
-    println!("Creating passwords table if not exists...");
-    sqlx::query(
-        r#"
-        CREATE TABLE passwords (
-        password_id INTEGER PRIMARY KEY AUTOINCREMENT,
-        user_id INTEGER NOT NULL,
-        domain TEXT NOT NULL,
-        password_encrypted TEXT NOT NULL,
-        created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-        updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-        FOREIGN KEY(user_id) REFERENCES users(user_id) ON DELETE CASCADE
-        );
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-    println!("passwords table ready");
-    Ok(pool)
+use crate::errors::{ApiError, ErrorCode};
+use crate::utils::validation::Validate;
+use crate::utils::pagination::{paged_response, ListQuery};
+use crate::models::passwords::{PublicPassword, CreatePasswordDto, UpdatePasswordDto, PasswordFilter};
+use crate::repositories::RepoError;
+use crate::repositories::passwords::{NewPassword, PasswordChanges, PasswordRepository};
+use crate::repositories::users::UserRepository;
+use crate::models::password_history::{FIELD_CUSTOM, FIELD_NOTES, FIELD_URLS, FIELD_USERNAME};
+use crate::utils::db::in_transaction;
+use crate::utils::encryption::{
+    decrypt_with_vault_key, encrypt_with_vault_key, entry_field_aad, is_bound_vault_value, open_with_vault_key, passwords_aad,
+    history_field_aad,
+};
+use crate::utils::vault::{unwrap_vault_key, UnlockedVault, VaultKey, WrappedVaultKey};
+use crate::middleware::auth::AuthUser;
+use crate::controllers::audit_controller::{record, ACTION_PASSWORD_REVEAL};
+use crate::controllers::email_verification_controller::EMAIL_NOT_VERIFIED;
+use crate::controllers::roles_controller::user_has_any_role;
+use crate::controllers::users_controller::client_ip;
+use crate::middleware::permissions::ROLE_ADMIN;
+
+const DUPLICATE_DOMAIN: &str = "Password for this domain already exists for this user";
+pub const PASSWORD_NOT_FOUND: &str = "Password not found";
+const IN_PREVIOUS_VAULT: &str =
+    "This password is still locked in your vault from before the password reset, unlock it with POST /auth/vault/restore";
+
+/// שגיאה של repository בכתיבת סיסמה
+fn write_error(e: RepoError) -> ApiError {
+    match e {
+        RepoError::NotFound => ApiError::not_found(PASSWORD_NOT_FOUND),
+        RepoError::Duplicate => ApiError::new(ErrorCode::AlreadyExists, DUPLICATE_DOMAIN),
+        e => e.into(),
+    }
+}
+
+/// ערך שלא נפתח במפתח הכספת, אצל משתמש שיש לו כספת מלפני איפוס סיסמה, כנראה שייך אליה
+async fn vault_error(pool: &SqlitePool, user_id: i64, e: RepoError) -> ApiError {
+    if let RepoError::Decryption(_) = e
+        && has_previous_vault(pool, user_id).await.unwrap_or(false)
+    {
+        return ApiError::new(ErrorCode::VaultLocked, IN_PREVIOUS_VAULT);
+    }
+    write_error(e)
+}
+
+/// סיומת הדומיין, שלפיה הסיסמה משויכת לקטגוריה (example.co.il => .co.il)
+fn domain_suffix(domain: &str) -> String {
+    let domain = domain.to_lowercase();
+    let parts: Vec<&str> = domain.split('.').collect();
+    if parts.len() >= 2 {
+        if parts.len() >= 3 && parts[parts.len() - 2].len() <= 3 {
+            format!(".{}.{}", parts[parts.len() - 2], parts[parts.len() - 1])
+        } else {
+            format!(".{}", parts.last().unwrap())
+        }
+    } else {
+        ".unknown".to_string()
+    }
 }
 
 // // ===================== CREATE PASSWORD =====================
@@ -117,223 +147,113 @@ pub async fn init_db() -> Result<SqlitePool, sqlx::Error> {
 // ===================== CREATE PASSWORD =====================
 #[post("/passwords")]
 pub async fn create_password(
-    pool: web::Data<SqlitePool>,
-    password: web::Json<CreatePasswordDto>
-) -> impl Responder {
-    let now = Utc::now().naive_utc();
-
-    // 🔹 בדיקה אם כבר קיימת סיסמה לאותו user ולדומיין
-    let existing: Option<(i64,)> = match sqlx::query_as(
-        "SELECT password_id FROM passwords WHERE user_id = ? AND domain = ?"
-    )
-    .bind(password.user_id)
-    .bind(&password.domain)
-    .fetch_optional(&**pool)
-    .await
-    {
-        Ok(res) => res,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("DB error: {}", e)),
-    };
-
-    if existing.is_some() {
-        return HttpResponse::BadRequest().body("Password for this domain already exists for this user");
+    users: web::Data<dyn UserRepository>,
+    passwords: web::Data<dyn PasswordRepository>,
+    password: web::Json<CreatePasswordDto>,
+    user: AuthUser,
+    vault: UnlockedVault,
+) -> Result<HttpResponse, ApiError> {
+    password.validate()?;
+    // חשבון שעוד לא אימת את המייל לא מוסיף רשומות לכספת
+    match users.get(user.user_id).await? {
+        Some(account) if account.email_verified_at.is_some() => {}
+        _ => return Err(ApiError::new(ErrorCode::EmailNotVerified, EMAIL_NOT_VERIFIED)),
     }
 
-    // 🔹 הצפנת הסיסמה
-    let encrypted = match encrypt_password(&password.password_encrypted) {
-        Ok(enc) => enc,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Encryption error: {}", e)),
+    // הסיסמה תמיד שייכת למשתמש המחובר, והקטגוריה לפי סיומת הדומיין
+    let suffix = domain_suffix(&password.domain);
+    let details = password.details();
+    let new_password = NewPassword {
+        user_id: user.user_id,
+        domain: &password.domain,
+        password: &password.password_encrypted,
+        details: &details,
+        category_name: &suffix,
     };
-
-    // 🔹 הכנסה לטבלת passwords
-    let insert_result = match sqlx::query(
-        "INSERT INTO passwords (user_id, domain, password_encrypted, created_at, updated_at)
-         VALUES (?, ?, ?, ?, ?)"
-    )
-    .bind(password.user_id)
-    .bind(&password.domain)
-    .bind(&encrypted)
-    .bind(now)
-    .bind(now)
-    .execute(&**pool)
-    .await
-    {
-        Ok(res) => res,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
-    };
-
-    let password_id = insert_result.last_insert_rowid();
-
-    // 🔹 הפקת סיומת לדומיין (ליצירת קטגוריה)
-    let domain = password.domain.to_lowercase();
-    let parts: Vec<&str> = domain.split('.').collect();
-    let suffix = if parts.len() >= 2 {
-        if parts.len() >= 3 && parts[parts.len() - 2].len() <= 3 {
-            format!(".{}.{}", parts[parts.len() - 2], parts[parts.len() - 1])
-        } else {
-            format!(".{}", parts.last().unwrap())
-        }
-    } else {
-        ".unknown".to_string()
-    };
-
-    // 🔹 בדיקה או יצירה של קטגוריה תואמת
-    let category_id: i64 = match sqlx::query("SELECT category_id FROM categories WHERE category_name = ?")
-        .bind(&suffix)
-        .fetch_optional(&**pool)
-        .await
-    {
-        Ok(Some(row)) => row.get("category_id"),
-        Ok(None) => {
-            match sqlx::query("INSERT INTO categories (category_name) VALUES (?)")
-                .bind(&suffix)
-                .execute(&**pool)
-                .await
-            {
-                Ok(result) => result.last_insert_rowid(),
-                Err(e) => return HttpResponse::InternalServerError().body(format!("Error creating category: {}", e)),
-            }
-        }
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error checking category: {}", e)),
-    };
-
-    // 🔹 קישור בין הסיסמה לקטגוריה
-    if let Err(e) = sqlx::query(
-        "INSERT OR IGNORE INTO password_category (password_id, category_id) VALUES (?, ?)"
-    )
-    .bind(password_id)
-    .bind(category_id)
-    .execute(&**pool)
-    .await
-    {
-        return HttpResponse::InternalServerError().body(format!("Error linking password to category: {}", e));
-    }
-
-    let new_password = Password {
-        password_id,
-        user_id: password.user_id,
-        domain: password.domain.clone(),
-        password_encrypted: password.password_encrypted.clone(),
-        created_at: now,
-        updated_at: now,
-    };
-
-    HttpResponse::Created().json(new_password)
+    let created = passwords.create(&vault, new_password).await.map_err(write_error)?;
+    Ok(HttpResponse::Created().json(PublicPassword::from(created)))
 }
 
 
 // ===================== READ ALL PASSWORDS =====================
 #[get("/passwords")]
-pub async fn get_passwords(pool: web::Data<SqlitePool>) -> impl Responder {
-    match sqlx::query("SELECT * FROM passwords ORDER BY password_id")
-        .fetch_all(&**pool)
-        .await
-    {
-        Ok(rows) => {
-            let passwords: Vec<Password> = rows.iter().map(|row| {
-                let encrypted: String = row.get("password_encrypted");
-                let decrypted = decrypt_password(&encrypted).unwrap_or("[decryption error]".to_string());
-                Password {
-                    password_id: row.get("password_id"),
-                    user_id: row.get("user_id"),
-                    domain: row.get("domain"),
-                    password_encrypted: decrypted,
-                    created_at: row.get::<NaiveDateTime,_>("created_at"),
-                    updated_at: row.get::<NaiveDateTime,_>("updated_at"),
-                }
-            }).collect();
-            HttpResponse::Ok().json(passwords)
-        }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
+pub async fn get_passwords(
+    pool: web::Data<SqlitePool>,
+    passwords: web::Data<dyn PasswordRepository>,
+    query: ListQuery<PasswordFilter>,
+    user: AuthUser,
+) -> Result<HttpResponse, ApiError> {
+    // רק admin רואה (בלי הסיסמאות עצמן) את הרשימה של משתמש אחר
+    let owner = query.filters.user_id.unwrap_or(user.user_id);
+    if owner != user.user_id && !user_has_any_role(&pool, user.user_id, &[ROLE_ADMIN]).await? {
+        return Err(ApiError::new(ErrorCode::Forbidden, "Only admins can list another user's passwords"));
     }
+
+    // ברשימה לא מפענחים כלום, הסיסמה עצמה רק דרך reveal
+    let page = passwords.list(owner, &query.filters, &query.page).await?;
+    Ok(paged_response(page, PublicPassword::from))
 }
 
 // ===================== READ ONE PASSWORD =====================
 #[get("/passwords/{id}")]
-pub async fn get_password(pool: web::Data<SqlitePool>, path: web::Path<i64>) -> impl Responder {
+pub async fn get_password(passwords: web::Data<dyn PasswordRepository>, path: web::Path<i64>, user: AuthUser) -> Result<HttpResponse, ApiError> {
+    // סיסמה של משתמש אחר מחזירה 404, כדי לא לחשוף שהיא קיימת
+    let password = passwords
+        .get(user.user_id, path.into_inner())
+        .await?
+        .ok_or_else(|| ApiError::not_found(PASSWORD_NOT_FOUND))?;
+    Ok(HttpResponse::Ok().json(PublicPassword::from(password)))
+}
+
+// ===================== REVEAL PASSWORD =====================
+/// מחזיר את הסיסמה בטקסט גלוי לבעלים בלבד, ורושם את החשיפה ביומן
+#[post("/passwords/{id}/reveal")]
+pub async fn reveal_password(
+    pool: web::Data<SqlitePool>,
+    passwords: web::Data<dyn PasswordRepository>,
+    path: web::Path<i64>,
+    req: HttpRequest,
+    user: AuthUser,
+    vault: UnlockedVault,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
 
-    match sqlx::query("SELECT * FROM passwords WHERE password_id = ?")
-        .bind(id)
-        .fetch_optional(&**pool)
-        .await
-    {
-        Ok(Some(row)) => {
-            let encrypted: String = row.get("password_encrypted");
-            let decrypted = decrypt_password(&encrypted).unwrap_or("[decryption error]".to_string());
-            let password = Password {
-                password_id: row.get("password_id"),
-                user_id: row.get("user_id"),
-                domain: row.get("domain"),
-                password_encrypted: decrypted,
-                created_at: row.get::<NaiveDateTime,_>("created_at"),
-                updated_at: row.get::<NaiveDateTime,_>("updated_at"),
-            };
-            HttpResponse::Ok().json(password)
-        }
-        Ok(None) => HttpResponse::NotFound().body("Password not found"),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
-    }
+    let revealed = match passwords.reveal(&vault, user.user_id, id).await {
+        Ok(revealed) => revealed.ok_or_else(|| ApiError::not_found(PASSWORD_NOT_FOUND))?,
+        Err(e) => return Err(vault_error(&pool, user.user_id, e).await),
+    };
+
+    // בלי רישום ביומן לא מחזירים את הסיסמה
+    let ip = client_ip(&req);
+    record(&pool, user.user_id, ACTION_PASSWORD_REVEAL, "passwords", id, ip.as_deref()).await?;
+
+    Ok(HttpResponse::Ok().json(revealed))
 }
 
 // ===================== UPDATE PASSWORD =====================
 #[put("/passwords/{id}")]
 pub async fn update_password(
     pool: web::Data<SqlitePool>,
+    passwords: web::Data<dyn PasswordRepository>,
     path: web::Path<i64>,
     updated: web::Json<UpdatePasswordDto>,
-) -> impl Responder {
-    let password_id = path.into_inner();
-    let now = Utc::now().naive_utc();
-
-    // נביא את הסיסמה הישנה ונשמור אותה בהיסטוריה
-    let old_row = match sqlx::query("SELECT password_encrypted FROM passwords WHERE password_id = ?")
-        .bind(password_id)
-        .fetch_optional(&**pool)
-        .await
-    {
-        Ok(Some(row)) => row,
-        Ok(None) => return HttpResponse::NotFound().body("Password not found"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
+    user: AuthUser,
+    vault: UnlockedVault,
+) -> Result<HttpResponse, ApiError> {
+    updated.validate()?;
+    // הערכים הקודמים נשמרים בהיסטוריה. אם זה נכשל, הרשומה לא משתנה
+    let changes = PasswordChanges {
+        domain: updated.domain.as_deref(),
+        password: updated.password_encrypted.as_deref(),
+        username: updated.username.as_deref(),
+        urls: updated.urls.as_deref(),
+        notes: updated.notes.as_deref(),
+        fields: updated.fields.as_deref(),
     };
-
-    let old_password: String = old_row.get("password_encrypted");
-
-    if let Err(e) = create_password_history_internal(&**pool, password_id, &old_password).await {
-        eprintln!("Failed to save password history: {}", e);
-    }
-
-    let encrypted = match &updated.password_encrypted {
-        Some(pwd) => match encrypt_password(pwd) {
-            Ok(enc) => Some(enc),
-            Err(e) => return HttpResponse::InternalServerError().body(format!("Encryption error: {}", e)),
-        },
-        None => None,
-    };
-
-    match sqlx::query(
-        "UPDATE passwords SET
-            domain = COALESCE(?, domain),
-            password_encrypted = COALESCE(?, password_encrypted),
-            updated_at = ?
-         WHERE password_id = ?"
-    )
-    .bind(&updated.domain)
-    .bind(&encrypted)
-    .bind(now)
-    .bind(password_id)
-    .execute(&**pool)
-    .await
-    {
-        Ok(result) => {
-            if result.rows_affected() > 0 {
-                HttpResponse::Ok().body("Password updated successfully and history recorded")
-            } else {
-                HttpResponse::NotFound().body("Password not found")
-            }
-        }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
+    if let Err(e) = passwords.update(&vault, user.user_id, path.into_inner(), &changes).await {
+        return Err(vault_error(&pool, user.user_id, e).await);
     }
+    Ok(HttpResponse::Ok().body("Password updated successfully and history recorded"))
 }
 
 // // ===================== DELETE PASSWORD =====================
@@ -375,7 +295,7 @@ pub async fn update_password(
 //     let old_password: String = old_row.get("password_encrypted");
 
 //     // נשמור בהיסטוריה
-//     if let Err(e) = create_password_history_internal(&**pool, password_id, &old_password).await {
+//     if let Err(e) = create_password_history_internal(&pool, password_id, &old_password).await {
 //         eprintln!("Failed to save password history before deletion: {}", e);
 //     }
 
@@ -398,95 +318,294 @@ pub async fn update_password(
 // ===================== DELETE PASSWORD =====================
 #[delete("/passwords/{id}")]
 pub async fn delete_password(
-    pool: web::Data<SqlitePool>,
-    path: web::Path<i64>
-) -> impl Responder {
-    let password_id = path.into_inner();
-
-    // 1️⃣ נביא את הסיסמה הקיימת לפני מחיקה
-    let old_row = match sqlx::query("SELECT password_encrypted FROM passwords WHERE password_id = ?")
-        .bind(password_id)
-        .fetch_optional(&**pool)
-        .await
-    {
-        Ok(Some(row)) => row,
-        Ok(None) => return HttpResponse::NotFound().body("Password not found"),
-        Err(e) => {
-            eprintln!("DB error fetching old password: {}", e);
-            return HttpResponse::InternalServerError().body(format!("Database error: {}", e));
-        }
-    };
+    passwords: web::Data<dyn PasswordRepository>,
+    path: web::Path<i64>,
+    user: AuthUser,
+    // רק מי שהכספת שלו פתוחה מוחק ממנה
+    _vault: UnlockedVault,
+) -> Result<HttpResponse, ApiError> {
+    // ההיסטוריה והקישורים לקטגוריות נמחקים יחד עם הסיסמה
+    if !passwords.delete(user.user_id, path.into_inner()).await? {
+        return Err(ApiError::not_found(PASSWORD_NOT_FOUND));
+    }
+    Ok(HttpResponse::Ok().body("Password deleted successfully"))
+}
+
+// ===================== USERS WITH 3+ PASSWORDS =====================
+#[get("/passwords/users_with_3_or_more")]
+pub async fn get_users_with_3_or_more_passwords(passwords: web::Data<dyn PasswordRepository>) -> Result<HttpResponse, ApiError> {
+    let result = passwords.users_with_at_least(3).await?;
+    Ok(HttpResponse::Ok().json(result))
+}
 
-    let old_password: String = old_row.get("password_encrypted");
-    println!("Old password fetched for history: {}", old_password);
+// ===================== MOVE ROWS TO VAULT KEY =====================
+/// העמודות המוצפנות של passwords, עם שם השדה שנכנס ל-AAD (None לסיסמה עצמה)
+const ENCRYPTED_COLUMNS: [(&str, Option<&str>); 5] = [
+    ("password_encrypted", None),
+    ("username_encrypted", Some(FIELD_USERNAME)),
+    ("urls_encrypted", Some(FIELD_URLS)),
+    ("notes_encrypted", Some(FIELD_NOTES)),
+    ("fields_encrypted", Some(FIELD_CUSTOM)),
+];
+
+/// כמה ערכים הוצפנו מחדש, וכמה לא נפתחו ונשארו כמו שהם
+#[derive(Debug, Default)]
+pub struct Reencrypted {
+    pub moved: u64,
+    pub failed: u64,
+}
 
-    // 2️⃣ שמירה בהיסטוריה
-    if let Err(e) = create_password_history_internal(&**pool, password_id, &old_password).await {
-        eprintln!("Failed to save password history: {}", e);
-        // אנחנו ממשיכים למחיקה גם אם ההיסטוריה לא נשמרת
-    } else {
-        println!("Password history saved successfully for password_id={}", password_id);
-    }
+/// מה עושים עם ערך מוצפן: None - משאירים (כבר במקום, או שייך לכספת אחרת),
+/// Some(Ok) - הטקסט הגלוי, להצפנה מחדש, Some(Err) - היה אמור להיפתח ולא נפתח
+type Opener<'a> = dyn Fn(&str, &[u8]) -> Option<Result<String, Box<dyn std::error::Error>>> + 'a;
+
+/// מצפין מחדש במפתח הכספת to את כל הערכים של המשתמש ש-open פותח. כל טבלה בטרנזקציה אחת.
+/// ערך שלא נפתח נספר ומדלגים עליו, כדי שלא יעצור את השאר. העדכון רק אם הערך לא השתנה בינתיים,
+/// כדי לא לדרוס עדכון מקביל (update_password) בערך ישן
+pub async fn reencrypt_rows(
+    pool: &SqlitePool,
+    user_id: i64,
+    open: &Opener<'_>,
+    to: &VaultKey,
+) -> Result<Reencrypted, sqlx::Error> {
+    let seal = |encrypted: &str, aad: &[u8]| {
+        open(encrypted, aad).map(|plain| plain.and_then(|plain| encrypt_with_vault_key(to.as_bytes(), &plain, aad)))
+    };
 
-    // 3️⃣ מחיקה מהטבלה passwords
-    match sqlx::query("DELETE FROM passwords WHERE password_id = ?")
-        .bind(password_id)
-        .execute(&**pool)
-        .await
-    {
-        Ok(result) => {
-            if result.rows_affected() > 0 {
-                HttpResponse::Ok().body("Password deleted and history recorded successfully")
-            } else {
-                HttpResponse::NotFound().body("Password not found")
+    let mut result = in_transaction(pool, async |conn| {
+        let mut result = Reencrypted::default();
+        let rows = sqlx::query("SELECT * FROM passwords WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_all(&mut *conn)
+            .await?;
+        for row in rows {
+            let password_id: i64 = row.get("password_id");
+            let domain: String = row.get("domain");
+            for (column, field) in ENCRYPTED_COLUMNS {
+                let Some(encrypted) = row.get::<Option<String>, _>(column) else {
+                    continue;
+                };
+                let aad = match field {
+                    None => passwords_aad(password_id, user_id, &domain),
+                    Some(field) => entry_field_aad(password_id, user_id, &domain, field),
+                };
+                match seal(&encrypted, &aad) {
+                    None => {}
+                    Some(Ok(sealed)) => {
+                        let updated = sqlx::query(&format!("UPDATE passwords SET {0} = ? WHERE password_id = ? AND {0} = ?", column))
+                            .bind(sealed)
+                            .bind(password_id)
+                            .bind(&encrypted)
+                            .execute(&mut *conn)
+                            .await?;
+                        result.moved += updated.rows_affected();
+                    }
+                    Some(Err(e)) => {
+                        log::warn!("⚠️ Could not re-encrypt {} of password {}: {}", column, password_id, e);
+                        result.failed += 1;
+                    }
+                }
             }
         }
-        Err(e) => {
-            eprintln!("DB error deleting password: {}", e);
-            HttpResponse::InternalServerError().body(format!("Database error: {}", e))
+        Ok::<_, sqlx::Error>(result)
+    })
+    .await?;
+
+    let history = in_transaction(pool, async |conn| {
+        let mut result = Reencrypted::default();
+        let rows = sqlx::query(
+            "SELECT ph.history_id, ph.password_id, ph.field, ph.old_password_encrypted
+             FROM password_history ph
+             JOIN passwords p ON p.password_id = ph.password_id
+             WHERE p.user_id = ?"
+        )
+        .bind(user_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        for row in rows {
+            let history_id: i64 = row.get("history_id");
+            let encrypted: String = row.get("old_password_encrypted");
+            let aad = history_field_aad(history_id, row.get("password_id"), row.get("field"));
+            match seal(&encrypted, &aad) {
+                None => {}
+                Some(Ok(sealed)) => {
+                    let updated = sqlx::query(
+                        "UPDATE password_history SET old_password_encrypted = ? WHERE history_id = ? AND old_password_encrypted = ?"
+                    )
+                    .bind(sealed)
+                    .bind(history_id)
+                    .bind(&encrypted)
+                    .execute(&mut *conn)
+                    .await?;
+                    result.moved += updated.rows_affected();
+                }
+                Some(Err(e)) => {
+                    log::warn!("⚠️ Could not re-encrypt history row {}: {}", history_id, e);
+                    result.failed += 1;
+                }
+            }
         }
-    }
+        Ok::<_, sqlx::Error>(result)
+    })
+    .await?;
+
+    result.moved += history.moved;
+    result.failed += history.failed;
+    Ok(result)
 }
 
-// ===================== USERS WITH 3+ PASSWORDS =====================
-#[derive(serde::Serialize)]
-pub struct PasswordSummary {
-    pub user_id: i64,
-    pub user_first_name: String,
-    pub user_last_name: String,
-    pub password_count: i64,
+/// מעביר למפתח הכספת, וקושר לשורה, את כל הערכים של המשתמש שעוד מוצפנים במפתח השרת או לא קשורים
+pub async fn move_rows_to_vault(pool: &SqlitePool, user_id: i64, key: &VaultKey) -> Result<Reencrypted, sqlx::Error> {
+    let open = |encrypted: &str, aad: &[u8]| {
+        (!is_bound_vault_value(encrypted)).then(|| decrypt_with_vault_key(key.as_bytes(), encrypted, aad))
+    };
+    reencrypt_rows(pool, user_id, &open, key).await
 }
 
-#[get("/passwords/users_with_3_or_more")]
-pub async fn get_users_with_3_or_more_passwords(pool: web::Data<SqlitePool>) -> impl Responder {
-    let query_result: Result<Vec<sqlx::sqlite::SqliteRow>, sqlx::Error> = sqlx::query(
-        r#"
-        SELECT 
-            u.user_id, 
-            u.user_first_name, 
-            u.user_last_name, 
-            COUNT(p.password_id) AS password_count
-        FROM users u
-        JOIN passwords p ON u.user_id = p.user_id
-        GROUP BY u.user_id
-        HAVING COUNT(p.password_id) >= 3
-        ORDER BY password_count DESC;
-        "#
+// ===================== PREVIOUS VAULTS =====================
+/// האם נשארו למשתמש מפתחות כספת מלפני איפוס סיסמה
+pub async fn has_previous_vault(pool: &SqlitePool, user_id: i64) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query("SELECT 1 FROM previous_vault_keys WHERE user_id = ? LIMIT 1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.is_some())
+}
+
+/// פותח בסיסמה הישנה את הכספות שהוחלפו באיפוס סיסמה, ומעביר את הערכים שלהן לכספת to.
+/// מפתח ישן נמחק רק אחרי שכל הערכים שלו עברו. None אם הסיסמה לא פותחת אף אחד מהם
+pub async fn restore_previous_vaults(
+    pool: &SqlitePool,
+    user_id: i64,
+    old_password: &str,
+    to: &VaultKey,
+) -> Result<Option<Reencrypted>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT previous_key_id, vault_key_salt, vault_key_wrapped FROM previous_vault_keys
+         WHERE user_id = ? ORDER BY previous_key_id"
     )
-    .fetch_all(&**pool)
-    .await;
-
-    match query_result {
-        Ok(rows) => {
-            let result: Vec<PasswordSummary> = rows.iter().map(|row| PasswordSummary {
-                user_id: row.get("user_id"),
-                user_first_name: row.get("user_first_name"),
-                user_last_name: row.get("user_last_name"),
-                password_count: row.get::<i64, _>("password_count"),
-            }).collect();
-
-            HttpResponse::Ok().json(result)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut restored: Option<Reencrypted> = None;
+    for row in rows {
+        let wrapped = WrappedVaultKey { salt: row.get("vault_key_salt"), wrapped: row.get("vault_key_wrapped") };
+        let Ok((old_key, _)) = unwrap_vault_key(user_id, &wrapped, old_password) else {
+            continue;
+        };
+        // ערכים של הכספת הנוכחית או של כספת ישנה אחרת לא נפתחים במפתח הזה ונשארים
+        let open = |encrypted: &str, aad: &[u8]| {
+            open_with_vault_key(old_key.as_bytes(), encrypted, aad).map(Ok::<_, Box<dyn std::error::Error>>)
+        };
+        let result = reencrypt_rows(pool, user_id, &open, to).await?;
+        if result.failed == 0 {
+            sqlx::query("DELETE FROM previous_vault_keys WHERE previous_key_id = ?")
+                .bind(row.get::<i64, _>("previous_key_id"))
+                .execute(pool)
+                .await?;
         }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
+
+        let total = restored.get_or_insert_with(Reencrypted::default);
+        total.moved += result.moved;
+        total.failed += result.failed;
+    }
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{self, Config};
+    use crate::models::password_history::FIELD_PASSWORD;
+    use crate::models::passwords::EntryDetails;
+    use crate::repositories::sqlite::SqliteStore;
+    use crate::utils::db::{new_user, test_pool};
+    use crate::utils::encryption::encrypt_password;
+    use crate::utils::keys::load_test_keys;
+    use crate::utils::vault::wrap_vault_key;
+
+    #[actix_web::test]
+    async fn values_that_do_not_open_are_counted_and_the_rest_still_move() {
+        let _ = config::init(Config::default());
+        load_test_keys();
+        let pool = test_pool("move-rows-to-vault").await;
+        let store = SqliteStore::new(pool.clone());
+        let user = UserRepository::create(&store, new_user("a@x.io")).await.unwrap();
+        let vault = UnlockedVault { user_id: user.user_id, key: VaultKey::generate() };
+        let details = EntryDetails { notes: Some("n1".to_string()), ..Default::default() };
+        let entry = NewPassword { user_id: user.user_id, domain: "a.com", password: "p1", details: &details, category_name: "web" };
+        let password_id = PasswordRepository::create(&store, &vault, entry).await.unwrap().password_id;
+
+        // סיסמה שעוד מוצפנת במפתח השרת, והערה שלא נפתחת בשום מפתח
+        let server = encrypt_password("p1", &passwords_aad(password_id, user.user_id, "a.com")).unwrap();
+        let garbage = "bm90IGEgY2lwaGVydGV4dA==";
+        sqlx::query("UPDATE passwords SET password_encrypted = ?, notes_encrypted = ? WHERE password_id = ?")
+            .bind(&server)
+            .bind(garbage)
+            .bind(password_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let result = move_rows_to_vault(&pool, user.user_id, &vault.key).await.unwrap();
+        assert_eq!((result.moved, result.failed), (1, 1));
+
+        let row = sqlx::query("SELECT password_encrypted, notes_encrypted FROM passwords WHERE password_id = ?")
+            .bind(password_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let moved: String = row.get("password_encrypted");
+        assert!(is_bound_vault_value(&moved));
+        assert_eq!(row.get::<String, _>("notes_encrypted"), garbage);
+        let aad = passwords_aad(password_id, user.user_id, "a.com");
+        assert_eq!(decrypt_with_vault_key(vault.key.as_bytes(), &moved, &aad).unwrap(), "p1");
+    }
+
+    #[actix_web::test]
+    async fn restore_moves_the_previous_vault_and_forgets_its_key() {
+        let _ = config::init(Config::default());
+        load_test_keys();
+        let pool = test_pool("restore-previous-vault").await;
+        let store = SqliteStore::new(pool.clone());
+        let user = UserRepository::create(&store, new_user("a@x.io")).await.unwrap();
+
+        // הכספת מלפני האיפוס: רשומה עם הערה והיסטוריה, והמפתח עטוף בסיסמה הישנה
+        let old = UnlockedVault { user_id: user.user_id, key: VaultKey::generate() };
+        let details = EntryDetails { notes: Some("n1".to_string()), ..Default::default() };
+        let entry = NewPassword { user_id: user.user_id, domain: "a.com", password: "p1", details: &details, category_name: "web" };
+        let password_id = PasswordRepository::create(&store, &old, entry).await.unwrap().password_id;
+        let changes = PasswordChanges { password: Some("p2"), ..Default::default() };
+        PasswordRepository::update(&store, &old, user.user_id, password_id, &changes).await.unwrap();
+        let wrapped = wrap_vault_key(user.user_id, &old.key, "old password").unwrap();
+        sqlx::query("INSERT INTO previous_vault_keys (user_id, vault_key_salt, vault_key_wrapped, replaced_at) VALUES (?, ?, ?, ?)")
+            .bind(user.user_id)
+            .bind(&wrapped.salt)
+            .bind(&wrapped.wrapped)
+            .bind(chrono::Utc::now().naive_utc())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let current = UnlockedVault { user_id: user.user_id, key: VaultKey::generate() };
+        assert!(store.reveal(&current, user.user_id, password_id).await.is_err());
+        assert!(restore_previous_vaults(&pool, user.user_id, "wrong password", &current.key).await.unwrap().is_none());
+        assert!(has_previous_vault(&pool, user.user_id).await.unwrap());
+
+        let result = restore_previous_vaults(&pool, user.user_id, "old password", &current.key).await.unwrap().unwrap();
+        // הסיסמה, ההערה והסיסמה הקודמת בהיסטוריה
+        assert_eq!((result.moved, result.failed), (3, 0));
+        assert!(!has_previous_vault(&pool, user.user_id).await.unwrap());
+        let revealed = store.reveal(&current, user.user_id, password_id).await.unwrap().unwrap();
+        assert_eq!((revealed.password.as_str(), revealed.details.notes.as_deref()), ("p2", Some("n1")));
+        let (history_id, history): (i64, String) =
+            sqlx::query_as("SELECT history_id, old_password_encrypted FROM password_history WHERE password_id = ?")
+                .bind(password_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let aad = history_field_aad(history_id, password_id, FIELD_PASSWORD);
+        assert_eq!(current.decrypt(&history, &aad).unwrap(), "p1");
     }
 }
@@ -0,0 +1,181 @@
+use actix_web::{get, post, delete, web, HttpResponse};
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{SqlitePool, Row};
+use crate::config;
+use crate::errors::{ApiError, ErrorCode};
+use crate::utils::validation::Validate;
+use crate::middleware::permissions::ROLE_ADMIN;
+use crate::models::roles::{AssignRoleDto, Role, UserRole};
+
+/// נותן admin למשתמש שמוגדר ב-admin_email (אם הוגדר, קיים ואימת את המייל), כדי שיהיה admin ראשון.
+/// נקרא בהפעלה ושוב כשמשתמש מאמת מייל, כך שמי שנרשם עם המייל הזה מקבל admin רק אחרי האימות
+pub async fn bootstrap_admin(pool: &SqlitePool) -> Result<Option<i64>, sqlx::Error> {
+    let Some(email) = &config::get().admin_email else {
+        return Ok(None);
+    };
+
+    let row = sqlx::query("SELECT user_id, email_verified_at IS NOT NULL AS verified FROM users WHERE lower(email) = lower(?)")
+        .bind(email)
+        .fetch_optional(pool)
+        .await?;
+
+    match row {
+        Some(row) if row.get::<bool, _>("verified") => {
+            let user_id = row.get("user_id");
+            assign_role(pool, user_id, ROLE_ADMIN).await?;
+            Ok(Some(user_id))
+        }
+        Some(_) => {
+            log::warn!("⚠️ admin_email {} is not verified yet; admin is granted once it is", email);
+            Ok(None)
+        }
+        None => {
+            log::warn!("⚠️ admin_email is set but no user has the email {}", email);
+            Ok(None)
+        }
+    }
+}
+
+// ===================== HELPERS =====================
+/// מוסיף תפקיד למשתמש. false אם אין תפקיד כזה
+pub async fn assign_role(pool: &SqlitePool, user_id: i64, role_name: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT OR IGNORE INTO user_roles (user_id, role_id, assigned_at)
+         SELECT ?, role_id, ? FROM roles WHERE role_name = ?"
+    )
+    .bind(user_id)
+    .bind(Utc::now().naive_utc())
+    .bind(role_name)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() > 0 {
+        return Ok(true);
+    }
+    // INSERT OR IGNORE לא מוסיף גם כשהתפקיד כבר קיים
+    role_exists(pool, role_name).await
+}
+
+async fn role_exists(pool: &SqlitePool, role_name: &str) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query("SELECT role_id FROM roles WHERE role_name = ?")
+        .bind(role_name)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.is_some())
+}
+
+/// האם למשתמש יש לפחות אחד מהתפקידים (נקרא מה-middleware)
+pub async fn user_has_any_role(pool: &SqlitePool, user_id: i64, roles: &[&str]) -> Result<bool, sqlx::Error> {
+    if roles.is_empty() {
+        return Ok(false);
+    }
+
+    let placeholders = vec!["?"; roles.len()].join(", ");
+    let sql = format!(
+        "SELECT 1 FROM user_roles ur
+         JOIN roles r ON r.role_id = ur.role_id
+         WHERE ur.user_id = ? AND r.role_name IN ({})
+         LIMIT 1",
+        placeholders
+    );
+
+    let mut query = sqlx::query(&sql).bind(user_id);
+    for role in roles {
+        query = query.bind(*role);
+    }
+    Ok(query.fetch_optional(pool).await?.is_some())
+}
+
+// ===================== READ ALL ROLES =====================
+#[get("/roles")]
+pub async fn get_roles(pool: web::Data<SqlitePool>) -> Result<HttpResponse, ApiError> {
+    let rows = sqlx::query("SELECT role_id, role_name FROM roles ORDER BY role_id")
+        .fetch_all(&**pool)
+        .await?;
+    let roles: Vec<Role> = rows.iter().map(|row| Role {
+        role_id: row.get("role_id"),
+        role_name: row.get("role_name"),
+    }).collect();
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+// ===================== READ USER ROLES =====================
+#[get("/users/{id}/roles")]
+pub async fn get_user_roles(pool: web::Data<SqlitePool>, path: web::Path<i64>) -> Result<HttpResponse, ApiError> {
+    let user_id = path.into_inner();
+
+    let rows = sqlx::query(
+        "SELECT ur.user_id, r.role_name, ur.assigned_at
+         FROM user_roles ur
+         JOIN roles r ON r.role_id = ur.role_id
+         WHERE ur.user_id = ?
+         ORDER BY r.role_id"
+    )
+    .bind(user_id)
+    .fetch_all(&**pool)
+    .await?;
+    let roles: Vec<UserRole> = rows.iter().map(|row| UserRole {
+        user_id: row.get("user_id"),
+        role_name: row.get("role_name"),
+        assigned_at: row.get::<NaiveDateTime, _>("assigned_at"),
+    }).collect();
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+// ===================== ASSIGN ROLE =====================
+#[post("/users/{id}/roles")]
+pub async fn add_user_role(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<i64>,
+    role: web::Json<AssignRoleDto>,
+) -> Result<HttpResponse, ApiError> {
+    role.validate()?;
+    let user_id = path.into_inner();
+
+    sqlx::query("SELECT user_id FROM users WHERE user_id = ?")
+        .bind(user_id)
+        .fetch_optional(&**pool)
+        .await?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+
+    if !assign_role(&pool, user_id, &role.role_name).await? {
+        return Err(ApiError::new(ErrorCode::BadRequest, "Unknown role"));
+    }
+    Ok(HttpResponse::Created().body(format!("Role '{}' assigned", role.role_name)))
+}
+
+// ===================== REMOVE ROLE =====================
+#[delete("/users/{id}/roles/{role_name}")]
+pub async fn remove_user_role(pool: web::Data<SqlitePool>, path: web::Path<(i64, String)>) -> Result<HttpResponse, ApiError> {
+    let (user_id, role_name) = path.into_inner();
+
+    // לא משאירים את המערכת בלי admin
+    if role_name == ROLE_ADMIN {
+        let admins: i64 = sqlx::query(
+            "SELECT COUNT(*) AS admins FROM user_roles ur
+             JOIN roles r ON r.role_id = ur.role_id
+             WHERE r.role_name = ? AND ur.user_id != ?"
+        )
+        .bind(ROLE_ADMIN)
+        .bind(user_id)
+        .fetch_one(&**pool)
+        .await?
+        .get("admins");
+        if admins == 0 {
+            return Err(ApiError::new(ErrorCode::Conflict, "Cannot remove the last admin"));
+        }
+    }
+
+    let result = sqlx::query(
+        "DELETE FROM user_roles
+         WHERE user_id = ? AND role_id = (SELECT role_id FROM roles WHERE role_name = ?)"
+    )
+    .bind(user_id)
+    .bind(&role_name)
+    .execute(&**pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::not_found("Role not assigned to this user"));
+    }
+    Ok(HttpResponse::Ok().body(format!("Role '{}' removed", role_name)))
+}
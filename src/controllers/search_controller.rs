@@ -0,0 +1,420 @@
+use std::collections::BTreeSet;
+
+use actix_web::{get, web, HttpResponse};
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+use crate::errors::ApiError;
+use crate::utils::db::in_transaction;
+use crate::utils::encryption::entry_field_aad;
+use crate::utils::search::{blind_token, replace_username_tokens, words};
+use crate::utils::validation::Validate;
+use crate::utils::vault::{UnlockedVault, VaultKey};
+use crate::models::password_history::FIELD_USERNAME;
+use crate::models::search::{SearchQuery, SearchResult, DEFAULT_SEARCH_LIMIT};
+use crate::middleware::auth::AuthUser;
+
+// החיפוש עצמו באינדקס password_search (מיגרציה 0003), שה-triggers מעדכנים,
+// ובמזהים העיוורים של ה-username (מיגרציה 0010), שהשרת כותב עם הרשומה
+const MAX_TERMS: usize = 8;
+const MAX_TYPO_ALTERNATIVES: usize = 5;
+// כמה מוסיפה לציון מילה שנמצאה ב-username. bm25 של התאמה בדומיין הוא בערך באותו סדר גודל
+const USERNAME_SCORE: f64 = 5.0;
+// סימון ההתאמות מ-SQLite, לפני ה-escape. תווים שלא יכולים להופיע בדומיין או בשם קטגוריה
+const MARK_START: char = '\u{1}';
+const MARK_END: char = '\u{2}';
+
+// ===================== QUERY =====================
+fn terms(q: &str) -> Vec<String> {
+    words(q).take(MAX_TERMS).collect()
+}
+
+/// כמה טעויות הקלדה מותרות במילה. במילים קצרות אף אחת, אחרת כל מילה מתאימה
+fn allowed_typos(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// מרחק Levenshtein
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// המילים בדומיינים ובשמות הקטגוריות של הרשומות של המשתמש בלבד. מילים של משתמשים אחרים
+/// לא מוצעות כחלופות, כדי שהחיפוש לא יחשוף אותן. UNIQUE(user_id, domain) הוא האינדקס כאן
+async fn user_terms(pool: &SqlitePool, user_id: i64) -> Result<BTreeSet<String>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT p.domain, coalesce(group_concat(c.category_name, ' '), '') AS categories
+         FROM passwords p
+         LEFT JOIN password_category pc ON pc.password_id = p.password_id
+         LEFT JOIN categories c ON c.category_id = pc.category_id
+         WHERE p.user_id = ?
+         GROUP BY p.password_id"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .flat_map(|row| {
+            let text = format!("{} {}", row.get::<String, _>("domain"), row.get::<String, _>("categories"));
+            words(&text).collect::<Vec<_>>()
+        })
+        .collect())
+}
+
+/// מילים מתוך known שקרובות ל-term
+fn typo_alternatives(known: &BTreeSet<String>, term: &str) -> Vec<String> {
+    let typos = allowed_typos(term);
+    if typos == 0 {
+        return Vec::new();
+    }
+    let length = term.chars().count();
+    let mut close: Vec<(usize, &String)> = known
+        .iter()
+        .filter(|candidate| candidate.chars().count().abs_diff(length) <= typos && !candidate.starts_with(term))
+        .map(|candidate| (edit_distance(term, candidate), candidate))
+        .filter(|(distance, _)| *distance <= typos)
+        .collect();
+    close.sort();
+    close.into_iter().take(MAX_TYPO_ALTERNATIVES).map(|(_, candidate)| candidate.clone()).collect()
+}
+
+/// ביטוי MATCH לכל מילה: המילה כתחילית או אחת החלופות שלה.
+/// המילים הן רק אותיות וספרות, כך שהמרכאות לא נשברות
+async fn match_expressions(pool: &SqlitePool, user_id: i64, terms: &[String]) -> Result<Vec<String>, sqlx::Error> {
+    let known = if terms.iter().any(|term| allowed_typos(term) > 0) {
+        user_terms(pool, user_id).await?
+    } else {
+        BTreeSet::new()
+    };
+    Ok(terms
+        .iter()
+        .map(|term| {
+            let mut options = vec![format!("\"{}\"*", term)];
+            options.extend(typo_alternatives(&known, term).into_iter().map(|alt| format!("\"{}\"", alt)));
+            format!("({})", options.join(" OR "))
+        })
+        .collect())
+}
+
+/// escape של HTML, ואז הסימונים של SQLite הופכים ל-<mark>
+fn marked(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            MARK_START => out.push_str("<mark>"),
+            MARK_END => out.push_str("</mark>"),
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// ===================== SEARCH =====================
+/// חיפוש ברשומות של המשתמש המחובר לפי דומיין, שמות קטגוריות ו-username, מהמתאימה ביותר.
+/// כל מילה צריכה להתאים באחד מהם. ה-username מוצפן, לכן הוא נבדק רק כמילה שלמה ורק כשהכספת פתוחה;
+/// notes ושאר הפרטים לא באינדקס
+#[get("/search")]
+pub async fn search(
+    pool: web::Data<SqlitePool>,
+    query: web::Query<SearchQuery>,
+    user: AuthUser,
+    vault: Option<UnlockedVault>,
+) -> Result<HttpResponse, ApiError> {
+    query.validate()?;
+    let key = vault.as_ref().map(|vault| &vault.key);
+    let results = find(&pool, user.user_id, key, &query.q, query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT)).await?;
+    Ok(HttpResponse::Ok().json(results))
+}
+
+/// vault_key: None כשהכספת נעולה, ואז ה-username לא נבדק
+async fn find(pool: &SqlitePool, user_id: i64, vault_key: Option<&VaultKey>, q: &str, limit: usize) -> Result<Vec<SearchResult>, sqlx::Error> {
+    let terms = terms(q);
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let expressions = match_expressions(pool, user_id, &terms).await?;
+    let tokens: Vec<String> = match vault_key {
+        Some(key) => terms.iter().map(|term| blind_token(key, term)).collect(),
+        None => Vec::new(),
+    };
+
+    // hits: ההדגשות והדירוג לפי כל מילה שנמצאה בדומיין או בקטגוריות.
+    // bm25: נמוך = מתאים יותר. התאמה בדומיין שווה יותר מהתאמה בקטגוריה
+    let mut sql: QueryBuilder<'_, Sqlite> = QueryBuilder::new(
+        "WITH hits AS MATERIALIZED (
+            SELECT rowid AS password_id,
+                   highlight(password_search, 1, char(1), char(2)) AS domain_highlight,
+                   snippet(password_search, 2, char(1), char(2), '…', 10) AS categories_highlight,
+                   bm25(password_search, 0.0, 10.0, 3.0) AS rank
+            FROM password_search
+            WHERE password_search MATCH "
+    );
+    sql.push_bind(expressions.join(" OR "));
+    sql.push(" AND user_id = ");
+    sql.push_bind(user_id);
+    sql.push(
+        ")
+         SELECT p.password_id, p.domain, h.domain_highlight, h.categories_highlight,
+                coalesce(h.rank, 0.0) AS text_rank,
+                (SELECT COUNT(*) FROM password_search_tokens t WHERE t.password_id = p.password_id AND t.token IN ("
+    );
+    // IN () ריק מותר ב-SQLite: כשהכספת נעולה אין מזהים
+    let mut list = sql.separated(", ");
+    for token in &tokens {
+        list.push_bind(token);
+    }
+    sql.push(
+        ")) AS username_hits
+         FROM passwords p
+         LEFT JOIN hits h ON h.password_id = p.password_id
+         WHERE p.user_id = "
+    );
+    sql.push_bind(user_id);
+    for (i, expression) in expressions.iter().enumerate() {
+        sql.push(" AND (p.password_id IN (SELECT rowid FROM password_search WHERE password_search MATCH ");
+        sql.push_bind(expression);
+        sql.push(")");
+        if let Some(token) = tokens.get(i) {
+            sql.push(" OR p.password_id IN (SELECT password_id FROM password_search_tokens WHERE token = ");
+            sql.push_bind(token);
+            sql.push(")");
+        }
+        sql.push(")");
+    }
+    sql.push(" ORDER BY username_hits * ");
+    sql.push_bind(USERNAME_SCORE);
+    // h.rank ולא text_rank: בתוך ביטוי SQLite מעדיף את העמודה של hits על פני הכינוי
+    sql.push(" - coalesce(h.rank, 0.0) DESC, p.password_id LIMIT ");
+    sql.push_bind(limit as i64);
+
+    let rows = sql.build().fetch_all(pool).await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let domain: String = row.get("domain");
+            let domain_highlight: Option<String> = row.get("domain_highlight");
+            let categories: Option<String> = row.get("categories_highlight");
+            let username_hits: i64 = row.get("username_hits");
+            SearchResult {
+                password_id: row.get("password_id"),
+                domain_highlight: marked(domain_highlight.as_deref().unwrap_or(&domain)),
+                domain,
+                categories_highlight: categories.filter(|c| !c.is_empty()).map(|c| marked(&c)),
+                username_match: username_hits > 0,
+                score: username_hits as f64 * USERNAME_SCORE - row.get::<f64, _>("text_rank"),
+            }
+        })
+        .collect())
+}
+
+// ===================== USERNAME TOKENS =====================
+/// כותב את המזהים העיוורים של ה-username לרשומות של המשתמש שנפתחות במפתח הזה, ומחזיר לכמה.
+/// only_missing: רק לרשומות שעוד אין להן מזהים (מלפני 0010, או שעברו עכשיו ממפתח השרת).
+/// false אחרי שחזור כספת קודמת, כשהמזהים הקיימים נכתבו במפתח שלה
+pub async fn index_usernames(pool: &SqlitePool, user_id: i64, key: &VaultKey, only_missing: bool) -> Result<usize, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT password_id, domain, username_encrypted FROM passwords
+         WHERE user_id = ? AND username_encrypted IS NOT NULL
+           AND (? = 0 OR password_id NOT IN (SELECT password_id FROM password_search_tokens))"
+    )
+    .bind(user_id)
+    .bind(only_missing)
+    .fetch_all(pool)
+    .await?;
+
+    // ערך שעוד בכספת קודמת לא נפתח כאן, והמזהים שלו נשארים כמו שהם עד השחזור
+    let vault = UnlockedVault { user_id, key: key.clone() };
+    let usernames: Vec<(i64, String)> = rows
+        .iter()
+        .filter_map(|row| {
+            let password_id: i64 = row.get("password_id");
+            let aad = entry_field_aad(password_id, user_id, row.get("domain"), FIELD_USERNAME);
+            let username = vault.decrypt(row.get("username_encrypted"), &aad).ok()?;
+            Some((password_id, username))
+        })
+        .collect();
+
+    in_transaction(pool, async |conn| {
+        for (password_id, username) in &usernames {
+            replace_username_tokens(conn, key, *password_id, Some(username)).await?;
+        }
+        Ok::<_, sqlx::Error>(usernames.len())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{self, Config};
+    use crate::models::passwords::EntryDetails;
+    use crate::repositories::passwords::{NewPassword, PasswordChanges, PasswordRepository};
+    use crate::repositories::sqlite::SqliteStore;
+    use crate::repositories::users::UserRepository;
+    use crate::utils::db::{new_user, test_pool};
+    use crate::utils::keys::load_test_keys;
+
+    fn known(words: &[&str]) -> BTreeSet<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn edit_distance_counts_inserts_deletes_and_substitutions() {
+        let cases = [
+            ("", "", 0),
+            ("abc", "", 3),
+            ("", "abc", 3),
+            ("github", "github", 0),
+            ("githib", "github", 1),
+            ("gthub", "github", 1),
+            ("githubb", "github", 1),
+            ("kitten", "sitting", 3),
+            ("flaw", "lawn", 2),
+            // החלפת שני תווים סמוכים היא שתי פעולות
+            ("ab", "ba", 2),
+            // תווים, לא בייטים
+            ("שלום", "שלוס", 1),
+        ];
+        for (a, b, distance) in cases {
+            assert_eq!(edit_distance(a, b), distance, "{} -> {}", a, b);
+            assert_eq!(edit_distance(b, a), distance, "{} -> {}", b, a);
+        }
+    }
+
+    #[test]
+    fn typo_alternatives_come_from_known_words_within_the_allowed_distance() {
+        let known = known(&["github", "gitlab", "bank", "banks", "mail", "gmail", "paypal", "microsoft"]);
+        let cases: [(&str, &[&str]); 8] = [
+            ("githib", &["github"]),
+            ("banc", &["bank"]),
+            // קצרות מדי לטעות
+            ("gti", &[]),
+            ("mial", &[]),
+            // מילים שמתחילות במילה כבר נמצאות כתחילית
+            ("bank", &[]),
+            // שתי טעויות במילה של 6 אותיות
+            ("gihtub", &[]),
+            // עד שתי טעויות במילה של 8 אותיות ומעלה
+            ("microsfot", &["microsoft"]),
+            ("paypall", &["paypal"]),
+        ];
+        for (term, expected) in cases {
+            assert_eq!(typo_alternatives(&known, term), expected, "{}", term);
+        }
+    }
+
+    #[test]
+    fn typo_alternatives_are_the_closest_few() {
+        let known = known(&["abcdx", "abcxe", "abxde", "axcde", "xbcde", "abcdef", "abcdz", "zbcde"]);
+        let alternatives = typo_alternatives(&known, "abcde");
+        assert_eq!(alternatives.len(), MAX_TYPO_ALTERNATIVES);
+        assert_eq!(alternatives, ["abcdx", "abcdz", "abcxe", "abxde", "axcde"]);
+    }
+
+    #[test]
+    fn marked_escapes_html_and_turns_only_the_sqlite_markers_into_tags() {
+        let cases = [
+            ("github.com", "github.com"),
+            ("\u{1}git\u{2}hub.com", "<mark>git</mark>hub.com"),
+            ("<mark>x</mark>", "&lt;mark&gt;x&lt;/mark&gt;"),
+            ("\u{1}<b>\u{2} & \"a\" 'b'", "<mark>&lt;b&gt;</mark> &amp; &quot;a&quot; &#39;b&#39;"),
+            ("&lt;", "&amp;lt;"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(marked(text), expected);
+        }
+    }
+
+    #[test]
+    fn terms_are_lowercase_words_up_to_the_limit() {
+        assert_eq!(terms("GitHub.com  my-Bank!"), ["github", "com", "my", "bank"]);
+        assert_eq!(terms("Ünïcode"), ["ünïcode"]);
+        assert!(terms(" .-_ ").is_empty());
+        assert_eq!(terms("a b c d e f g h i j").len(), MAX_TERMS);
+    }
+
+    #[actix_web::test]
+    async fn username_words_are_found_only_with_the_vault_key() {
+        let _ = config::init(Config::default());
+        load_test_keys();
+        let pool = test_pool("search-username").await;
+        let store = SqliteStore::new(pool.clone());
+        let user = UserRepository::create(&store, new_user("a@x.io")).await.unwrap();
+        let vault = UnlockedVault { user_id: user.user_id, key: VaultKey::generate() };
+        let add = async |domain: &str, username: Option<&str>| {
+            let details = EntryDetails { username: username.map(str::to_string), ..Default::default() };
+            let entry = NewPassword { user_id: user.user_id, domain, password: "p1", details: &details, category_name: "web" };
+            PasswordRepository::create(&store, &vault, entry).await.unwrap().password_id
+        };
+        let github = add("github.com", Some("Octo.Cat@example.com")).await;
+        let octopus = add("octopus.io", None).await;
+
+        let found = |results: &[SearchResult]| results.iter().map(|r| (r.password_id, r.username_match)).collect::<Vec<_>>();
+        let key = Some(&vault.key);
+        assert_eq!(found(&find(&pool, user.user_id, key, "cat", 20).await.unwrap()), [(github, true)]);
+        // מילה שלמה בלבד, בלי תחיליות
+        assert!(find(&pool, user.user_id, key, "ca", 20).await.unwrap().is_empty());
+        // כל מילה צריכה להתאים במקום כלשהו: אחת בדומיין ואחת ב-username
+        let both = find(&pool, user.user_id, key, "git example", 20).await.unwrap();
+        assert_eq!(found(&both), [(github, true)]);
+        assert_eq!(both[0].domain_highlight, "<mark>github</mark>.com");
+        // התאמה ב-username מדורגת מעל תחילית בדומיין בלבד
+        assert_eq!(found(&find(&pool, user.user_id, key, "octo", 20).await.unwrap()), [(github, true), (octopus, false)]);
+
+        // בלי מפתח הכספת, ובמפתח של כספת אחרת, ה-username לא נבדק
+        assert!(find(&pool, user.user_id, None, "cat", 20).await.unwrap().is_empty());
+        assert!(find(&pool, user.user_id, Some(&VaultKey::generate()), "cat", 20).await.unwrap().is_empty());
+        // ולא אצל משתמש אחר
+        let other = UserRepository::create(&store, new_user("b@x.io")).await.unwrap();
+        assert!(find(&pool, other.user_id, key, "cat", 20).await.unwrap().is_empty());
+
+        // שינוי ה-username מחליף את המזהים
+        let changes = PasswordChanges { username: Some("dog"), ..Default::default() };
+        PasswordRepository::update(&store, &vault, user.user_id, github, &changes).await.unwrap();
+        assert!(find(&pool, user.user_id, key, "cat", 20).await.unwrap().is_empty());
+        assert_eq!(found(&find(&pool, user.user_id, key, "dog", 20).await.unwrap()), [(github, true)]);
+    }
+
+    #[actix_web::test]
+    async fn entries_without_tokens_are_indexed_when_the_vault_opens() {
+        let _ = config::init(Config::default());
+        load_test_keys();
+        let pool = test_pool("search-index-usernames").await;
+        let store = SqliteStore::new(pool.clone());
+        let user = UserRepository::create(&store, new_user("a@x.io")).await.unwrap();
+        let vault = UnlockedVault { user_id: user.user_id, key: VaultKey::generate() };
+        let details = EntryDetails { username: Some("octocat".to_string()), ..Default::default() };
+        let entry = NewPassword { user_id: user.user_id, domain: "github.com", password: "p1", details: &details, category_name: "web" };
+        PasswordRepository::create(&store, &vault, entry).await.unwrap();
+
+        // כמו רשומה מלפני 0010
+        sqlx::query("DELETE FROM password_search_tokens").execute(&pool).await.unwrap();
+        assert!(find(&pool, user.user_id, Some(&vault.key), "octocat", 20).await.unwrap().is_empty());
+
+        assert_eq!(index_usernames(&pool, user.user_id, &vault.key, true).await.unwrap(), 1);
+        assert_eq!(find(&pool, user.user_id, Some(&vault.key), "octocat", 20).await.unwrap().len(), 1);
+        assert_eq!(index_usernames(&pool, user.user_id, &vault.key, true).await.unwrap(), 0);
+        // מפתח שלא פותח את הרשומה לא נוגע במזהים שלה
+        assert_eq!(index_usernames(&pool, user.user_id, &VaultKey::generate(), false).await.unwrap(), 0);
+        assert_eq!(find(&pool, user.user_id, Some(&vault.key), "octocat", 20).await.unwrap().len(), 1);
+    }
+}
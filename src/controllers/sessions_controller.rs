@@ -0,0 +1,186 @@
+use actix_web::{post, web, HttpResponse};
+use chrono::{Duration, NaiveDateTime, Utc};
+use sqlx::{SqlitePool, Row};
+use crate::config;
+use crate::errors::{ApiError, ErrorCode};
+use crate::utils::validation::Validate;
+use crate::middleware::auth::AuthUser;
+use crate::models::sessions::{RefreshRequest, TokenResponse};
+use crate::utils::tokens::{generate_token, hash_token};
+use crate::utils::vault::{VaultKey, VaultSessions};
+
+fn token_lifetimes() -> (Duration, Duration) {
+    let tokens = &config::get().tokens;
+    (Duration::minutes(tokens.access_minutes), Duration::days(tokens.refresh_days))
+}
+
+struct IssuedTokens {
+    access_token: String,
+    refresh_token: String,
+    access_expires_at: NaiveDateTime,
+    refresh_expires_at: NaiveDateTime,
+}
+
+fn issue_tokens() -> IssuedTokens {
+    let now = Utc::now().naive_utc();
+    let (access_lifetime, refresh_lifetime) = token_lifetimes();
+    IssuedTokens {
+        access_token: generate_token(),
+        refresh_token: generate_token(),
+        access_expires_at: now + access_lifetime,
+        refresh_expires_at: now + refresh_lifetime,
+    }
+}
+
+fn token_response(user_id: i64, tokens: IssuedTokens) -> TokenResponse {
+    TokenResponse {
+        user_id,
+        token_type: "Bearer".to_string(),
+        access_token: tokens.access_token,
+        access_expires_at: tokens.access_expires_at,
+        refresh_token: tokens.refresh_token,
+        refresh_expires_at: tokens.refresh_expires_at,
+    }
+}
+
+// ===================== CREATE SESSION =====================
+/// נקרא מ-login אחרי שהסיסמה אומתה: יוצר session ופותח את הכספת שלו
+pub async fn create_session(
+    pool: &SqlitePool,
+    vaults: &VaultSessions,
+    user_id: i64,
+    vault_key: VaultKey,
+) -> Result<TokenResponse, sqlx::Error> {
+    let tokens = issue_tokens();
+
+    let result = sqlx::query(
+        "INSERT INTO sessions (user_id, access_token_hash, refresh_token_hash, access_expires_at, refresh_expires_at, created_at)
+         VALUES (?, ?, ?, ?, ?, ?)"
+    )
+    .bind(user_id)
+    .bind(hash_token(&tokens.access_token))
+    .bind(hash_token(&tokens.refresh_token))
+    .bind(tokens.access_expires_at)
+    .bind(tokens.refresh_expires_at)
+    .bind(Utc::now().naive_utc())
+    .execute(pool)
+    .await?;
+
+    vaults.open(result.last_insert_rowid(), user_id, vault_key, tokens.refresh_expires_at);
+    Ok(token_response(user_id, tokens))
+}
+
+/// מחפש session פעיל לפי access token (נקרא מה-middleware)
+pub async fn find_session_by_access_token(pool: &SqlitePool, token: &str) -> Result<Option<AuthUser>, sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT s.session_id, s.user_id FROM sessions s
+         JOIN users u ON u.user_id = s.user_id
+         WHERE s.access_token_hash = ? AND s.revoked_at IS NULL AND s.access_expires_at > ? AND u.is_active = 1"
+    )
+    .bind(hash_token(token))
+    .bind(Utc::now().naive_utc())
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| AuthUser {
+        user_id: row.get("user_id"),
+        session_id: row.get("session_id"),
+    }))
+}
+
+/// מבטל את כל ה-sessions של משתמש (מחיקה, שינוי סיסמה)
+pub async fn revoke_user_sessions(pool: &SqlitePool, vaults: &VaultSessions, user_id: i64) -> Result<u64, sqlx::Error> {
+    vaults.close_user(user_id);
+    let result = sqlx::query("UPDATE sessions SET revoked_at = ? WHERE user_id = ? AND revoked_at IS NULL")
+        .bind(Utc::now().naive_utc())
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// מבטל את כל ה-sessions של משתמש חוץ מהנוכחי (שינוי סיסמה)
+pub async fn revoke_other_sessions(
+    pool: &SqlitePool,
+    vaults: &VaultSessions,
+    user_id: i64,
+    keep_session_id: i64,
+) -> Result<u64, sqlx::Error> {
+    vaults.close_user_except(user_id, keep_session_id);
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = ? WHERE user_id = ? AND session_id != ? AND revoked_at IS NULL"
+    )
+    .bind(Utc::now().naive_utc())
+    .bind(user_id)
+    .bind(keep_session_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// ===================== REFRESH =====================
+/// מחליף את שני ה-tokens. ה-refresh token הישן לא תקף יותר
+#[post("/auth/refresh")]
+pub async fn refresh(
+    pool: web::Data<SqlitePool>,
+    vaults: web::Data<VaultSessions>,
+    body: web::Json<RefreshRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate()?;
+    let invalid_token = || ApiError::new(ErrorCode::InvalidToken, "Invalid or expired refresh token");
+    let now = Utc::now().naive_utc();
+
+    let row = sqlx::query(
+        "SELECT s.session_id, s.user_id FROM sessions s
+         JOIN users u ON u.user_id = s.user_id
+         WHERE s.refresh_token_hash = ? AND s.revoked_at IS NULL AND s.refresh_expires_at > ? AND u.is_active = 1"
+    )
+    .bind(hash_token(&body.refresh_token))
+    .bind(now)
+    .fetch_optional(&**pool)
+    .await?
+    .ok_or_else(invalid_token)?;
+
+    let session_id: i64 = row.get("session_id");
+    let user_id: i64 = row.get("user_id");
+    let tokens = issue_tokens();
+
+    let result = sqlx::query(
+        "UPDATE sessions SET
+            access_token_hash = ?, refresh_token_hash = ?,
+            access_expires_at = ?, refresh_expires_at = ?
+         WHERE session_id = ? AND refresh_token_hash = ?"
+    )
+    .bind(hash_token(&tokens.access_token))
+    .bind(hash_token(&tokens.refresh_token))
+    .bind(tokens.access_expires_at)
+    .bind(tokens.refresh_expires_at)
+    .bind(session_id)
+    .bind(hash_token(&body.refresh_token))
+    .execute(&**pool)
+    .await?;
+    // בקשת refresh מקבילה כבר החליפה את ה-token
+    if result.rows_affected() == 0 {
+        return Err(invalid_token());
+    }
+
+    vaults.extend(session_id, tokens.refresh_expires_at);
+    Ok(HttpResponse::Ok().json(token_response(user_id, tokens)))
+}
+
+// ===================== LOGOUT =====================
+#[post("/logout")]
+pub async fn logout(
+    pool: web::Data<SqlitePool>,
+    vaults: web::Data<VaultSessions>,
+    user: AuthUser,
+) -> Result<HttpResponse, ApiError> {
+    vaults.close(user.session_id);
+
+    sqlx::query("UPDATE sessions SET revoked_at = ? WHERE session_id = ?")
+        .bind(Utc::now().naive_utc())
+        .bind(user.session_id)
+        .execute(&**pool)
+        .await?;
+    Ok(HttpResponse::Ok().body("Logged out"))
+}
@@ -0,0 +1,277 @@
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use sqlx::{Executor, Row, Sqlite, SqliteConnection, SqlitePool};
+use crate::errors::{ApiError, ErrorCode, OrInternal};
+use crate::utils::validation::Validate;
+use crate::controllers::lockout_controller;
+use crate::controllers::login_history_controller::{record_login, LOGIN_LOCKED, LOGIN_MFA_FAILED};
+use crate::controllers::users_controller::{client_ip, complete_login, too_many_attempts};
+use crate::middleware::auth::AuthUser;
+use crate::repositories::users::UserRepository;
+use crate::models::totp::{LoginTotpRequest, RecoveryCodesResponse, SecondFactorDto, TotpConfirmDto, TotpEnrollResponse};
+use crate::utils::challenges::LoginChallenges;
+use crate::utils::db::in_transaction;
+use crate::utils::encryption::{decrypt_password, encrypt_password, totp_aad};
+use crate::utils::hash::{hash_password, verify_password};
+use crate::utils::totp::{generate_recovery_codes, generate_secret, normalize_recovery_code, otpauth_uri, verify_code};
+use crate::utils::vault::VaultSessions;
+
+// ===================== HELPERS =====================
+const INVALID_CHALLENGE: &str = "Invalid or expired login challenge";
+
+fn already_enabled() -> ApiError {
+    ApiError::new(ErrorCode::Conflict, "Two-factor authentication is already enabled")
+}
+
+fn invalid_code() -> ApiError {
+    ApiError::new(ErrorCode::InvalidCode, "Invalid code")
+}
+
+struct TotpState {
+    secret: Option<String>,
+    enabled: bool,
+    last_step: Option<i64>,
+}
+
+async fn load_state(pool: &SqlitePool, user_id: i64) -> Result<Option<TotpState>, Box<dyn std::error::Error>> {
+    let row = sqlx::query("SELECT totp_secret_encrypted, totp_enabled, totp_last_step FROM users WHERE user_id = ?")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let encrypted: Option<String> = row.get("totp_secret_encrypted");
+    let secret = match encrypted {
+        Some(enc) => Some(decrypt_password(&enc, &totp_aad(user_id))?),
+        None => None,
+    };
+    Ok(Some(TotpState {
+        secret,
+        enabled: row.get::<i64, _>("totp_enabled") != 0,
+        last_step: row.get("totp_last_step"),
+    }))
+}
+
+/// האם צריך גורם שני בהתחברות (נקרא מ-login)
+pub async fn is_enabled(pool: &SqlitePool, user_id: i64) -> Result<bool, sqlx::Error> {
+    let enabled: Option<i64> = sqlx::query("SELECT totp_enabled FROM users WHERE user_id = ?")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .map(|row| row.get("totp_enabled"));
+    Ok(enabled.unwrap_or(0) != 0)
+}
+
+/// בודק קוד TOTP ושומר את ה-step שלו, כך שאותו קוד לא יעבוד פעמיים
+async fn use_totp_code(pool: &SqlitePool, user_id: i64, secret: &str, code: &str, last_step: Option<i64>) -> Result<bool, sqlx::Error> {
+    let Some(step) = verify_code(secret, code, Utc::now().timestamp(), last_step) else {
+        return Ok(false);
+    };
+    claim_step(pool, user_id, step).await
+}
+
+/// שומר את ה-step של קוד שעבר. false אם קוד מאותו step או מאוחר יותר כבר שומש
+async fn claim_step<'c>(executor: impl Executor<'c, Database = Sqlite>, user_id: i64, step: i64) -> Result<bool, sqlx::Error> {
+    // בקשה מקבילה עם אותו קוד תיכשל כאן
+    let result = sqlx::query(
+        "UPDATE users SET totp_last_step = ?
+         WHERE user_id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)"
+    )
+    .bind(step)
+    .bind(user_id)
+    .bind(step)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// מסמן קוד שחזור כמשומש. כל קוד עובד פעם אחת בלבד
+async fn use_recovery_code(pool: &SqlitePool, user_id: i64, code: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let code = normalize_recovery_code(code);
+    let rows = sqlx::query("SELECT code_id, code_hash FROM recovery_codes WHERE user_id = ? AND used_at IS NULL")
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+    for row in rows {
+        let code_hash: String = row.get("code_hash");
+        if !verify_password(&code, &code_hash).map_err(|e| e.to_string())? {
+            continue;
+        }
+        let result = sqlx::query("UPDATE recovery_codes SET used_at = ? WHERE code_id = ? AND used_at IS NULL")
+            .bind(Utc::now().naive_utc())
+            .bind(row.get::<i64, _>("code_id"))
+            .execute(pool)
+            .await?;
+        return Ok(result.rows_affected() > 0);
+    }
+    Ok(false)
+}
+
+/// בודק את הגורם השני: קוד מהאפליקציה או קוד שחזור
+pub async fn verify_second_factor(pool: &SqlitePool, user_id: i64, factor: &SecondFactorDto) -> Result<bool, Box<dyn std::error::Error>> {
+    let Some(state) = load_state(pool, user_id).await? else {
+        return Ok(false);
+    };
+    if !state.enabled {
+        return Ok(false);
+    }
+
+    if let (Some(code), Some(secret)) = (&factor.code, &state.secret) {
+        return Ok(use_totp_code(pool, user_id, secret, code, state.last_step).await?);
+    }
+    if let Some(code) = &factor.recovery_code {
+        return use_recovery_code(pool, user_id, code).await;
+    }
+    Ok(false)
+}
+
+/// מחליף את כל קודי השחזור של המשתמש ב-hash של הקודים החדשים. ה-hash מחושב לפני
+/// הטרנזקציה, כדי לא להחזיק את נעילת הכתיבה בזמן Argon2
+async fn replace_recovery_codes(conn: &mut SqliteConnection, user_id: i64, code_hashes: &[String]) -> Result<(), sqlx::Error> {
+    let now = Utc::now().naive_utc();
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    for code_hash in code_hashes {
+        sqlx::query("INSERT INTO recovery_codes (user_id, code_hash, created_at) VALUES (?, ?, ?)")
+            .bind(user_id)
+            .bind(code_hash)
+            .bind(now)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+// ===================== ENROLL =====================
+/// יוצר סוד חדש (עדיין לא פעיל עד confirm)
+#[post("/auth/totp/enroll")]
+pub async fn enroll_totp(pool: web::Data<SqlitePool>, user: AuthUser) -> Result<HttpResponse, ApiError> {
+    let email: String = match sqlx::query("SELECT email, totp_enabled FROM users WHERE user_id = ?")
+        .bind(user.user_id)
+        .fetch_optional(&**pool)
+        .await?
+    {
+        Some(row) if row.get::<i64, _>("totp_enabled") != 0 => return Err(already_enabled()),
+        Some(row) => row.get("email"),
+        None => return Err(ApiError::not_found("User not found")),
+    };
+
+    let secret = generate_secret();
+    let encrypted = encrypt_password(&secret, &totp_aad(user.user_id)).or_internal("Encryption error")?;
+
+    sqlx::query(
+        "UPDATE users SET totp_secret_encrypted = ?, totp_enabled = 0, totp_last_step = NULL WHERE user_id = ?"
+    )
+    .bind(&encrypted)
+    .bind(user.user_id)
+    .execute(&**pool)
+    .await?;
+    Ok(HttpResponse::Ok().json(TotpEnrollResponse {
+        otpauth_uri: otpauth_uri(&email, &secret),
+        secret,
+    }))
+}
+
+// ===================== CONFIRM =====================
+/// מפעיל את ה-TOTP אחרי קוד ראשון תקין, ומחזיר קודי שחזור
+#[post("/auth/totp/confirm")]
+pub async fn confirm_totp(pool: web::Data<SqlitePool>, user: AuthUser, body: web::Json<TotpConfirmDto>) -> Result<HttpResponse, ApiError> {
+    body.validate()?;
+    let state = load_state(&pool, user.user_id)
+        .await
+        .or_internal("Two-factor state error")?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+    if state.enabled {
+        return Err(already_enabled());
+    }
+    let Some(secret) = state.secret else {
+        return Err(ApiError::new(ErrorCode::BadRequest, "Call /auth/totp/enroll first"));
+    };
+
+    let Some(step) = verify_code(&secret, &body.code, Utc::now().timestamp(), state.last_step) else {
+        return Err(invalid_code());
+    };
+    let codes = generate_recovery_codes();
+    let code_hashes = codes
+        .iter()
+        .map(|code| hash_password(&normalize_recovery_code(code)))
+        .collect::<Result<Vec<_>, _>>()
+        .or_internal("Recovery codes error")?;
+
+    // הקוד, קודי השחזור וההפעלה - הכל או כלום. בלי זה קוד שנכשל באמצע כבר נחשב משומש
+    in_transaction(&pool, async |conn| -> Result<(), ApiError> {
+        if !claim_step(&mut *conn, user.user_id, step).await? {
+            return Err(invalid_code());
+        }
+        replace_recovery_codes(conn, user.user_id, &code_hashes).await?;
+        sqlx::query("UPDATE users SET totp_enabled = 1 WHERE user_id = ?")
+            .bind(user.user_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes: codes }))
+}
+
+// ===================== DISABLE =====================
+#[post("/auth/totp/disable")]
+pub async fn disable_totp(pool: web::Data<SqlitePool>, user: AuthUser, body: web::Json<SecondFactorDto>) -> Result<HttpResponse, ApiError> {
+    body.validate()?;
+    if !verify_second_factor(&pool, user.user_id, &body).await.or_internal("Second factor error")? {
+        return Err(invalid_code());
+    }
+
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+        .bind(user.user_id)
+        .execute(&**pool)
+        .await?;
+
+    sqlx::query(
+        "UPDATE users SET totp_secret_encrypted = NULL, totp_enabled = 0, totp_last_step = NULL WHERE user_id = ?"
+    )
+    .bind(user.user_id)
+    .execute(&**pool)
+    .await?;
+    Ok(HttpResponse::Ok().body("Two-factor authentication disabled"))
+}
+
+// ===================== LOGIN - SECOND STEP =====================
+/// השלב השני של login: token מהשלב הראשון + קוד
+#[post("/login/totp")]
+pub async fn login_totp(
+    pool: web::Data<SqlitePool>,
+    users: web::Data<dyn UserRepository>,
+    sessions: web::Data<VaultSessions>,
+    challenges: web::Data<LoginChallenges>,
+    req: HttpRequest,
+    body: web::Json<LoginTotpRequest>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate()?;
+    let invalid_challenge = || ApiError::new(ErrorCode::InvalidToken, INVALID_CHALLENGE);
+    let user_id = challenges.user_id(&body.challenge_token).ok_or_else(invalid_challenge)?;
+
+    // קודים שגויים נספרים לנעילת החשבון כמו סיסמאות שגויות
+    // (None: המשתמש נמחק בין השלבים)
+    let email = users.get(user_id).await?.ok_or_else(invalid_challenge)?.email;
+    let ip = client_ip(&req);
+    if let Some(retry_after) = lockout_controller::locked_for(&pool, &email, ip.as_deref()).await? {
+        record_login(&pool, &req, Some(user_id), &email, LOGIN_LOCKED).await;
+        return Err(too_many_attempts(retry_after));
+    }
+
+    if !verify_second_factor(&pool, user_id, &body.factor).await.or_internal("Second factor error")? {
+        challenges.fail(&body.challenge_token);
+        record_login(&pool, &req, Some(user_id), &email, LOGIN_MFA_FAILED).await;
+        lockout_controller::record_failure(&pool, &email, ip.as_deref()).await?;
+        return Err(invalid_code());
+    }
+
+    let (user_id, vault_key) = challenges.complete(&body.challenge_token).ok_or_else(invalid_challenge)?;
+    complete_login(&pool, &**users, &sessions, &req, user_id, &email, vault_key).await
+}
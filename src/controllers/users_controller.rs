@@ -1,205 +1,322 @@
-use chrono::{NaiveDateTime, Utc};
-use actix_web::{get, post, put, delete, web, HttpResponse, Responder};
-use sqlx::{SqlitePool, Row};
-use crate::models::users::{User, CreateUserDto, UpdateUserDto};
-use crate::utils::hash::{verify_password, hash_password};
-
-// ===================== INIT DATABASE =====================
-pub async fn init_db() -> Result<SqlitePool, sqlx::Error> {
-    let database_url = "sqlite://src/passwords_management_system.db";
-    let pool = SqlitePool::connect(database_url).await?;
-
-    println!("Creating users table if not exists...");
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS users (
-            user_id INTEGER PRIMARY KEY AUTOINCREMENT,
-            user_first_name TEXT NOT NULL,
-            user_last_name TEXT NOT NULL,
-            email TEXT UNIQUE NOT NULL,
-            phone TEXT,
-            password_hash_to_login TEXT NOT NULL,
-            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-            last_login TIMESTAMP,
-            is_active BOOLEAN DEFAULT 1
-        );
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-    println!("users table ready");
-    Ok(pool)
+use std::sync::OnceLock;
+use actix_web::{get, post, put, delete, web, HttpRequest, HttpResponse};
+use sqlx::SqlitePool;
+use crate::errors::{ApiError, ErrorCode, OrInternal};
+use crate::utils::validation::{Validate, Validator, MAX_LOGIN_PASSWORD_LEN};
+use crate::utils::pagination::{paged_response, ListQuery};
+use crate::models::users::{
+    PublicUser, CreateUserDto, UpdateUserDto, ChangePasswordDto, RestoreVaultDto, UserFilter, VaultRecoveryStatus, VaultRestoreResult,
+};
+use crate::repositories::RepoError;
+use crate::repositories::users::{NewUser, UserAccount, UserChanges, UserRepository};
+use crate::utils::hash::{verify_password, hash_password, needs_rehash};
+use crate::utils::vault::{PendingVaultKey, UnlockedVault, VaultKey, VaultSessions, escrow_vault_key, wrap_vault_key, unwrap_vault_key};
+use crate::controllers::passwords_controller::{has_previous_vault, move_rows_to_vault, restore_previous_vaults};
+use crate::controllers::sessions_controller::{create_session, revoke_other_sessions, revoke_user_sessions};
+use crate::controllers::roles_controller::user_has_any_role;
+use crate::controllers::totp_controller;
+use crate::controllers::search_controller::index_usernames;
+use crate::controllers::email_verification_controller::{new_verification_token, send_verification, verification_mail};
+use crate::controllers::password_reset_controller::invalidate_tokens as invalidate_reset_tokens;
+use crate::controllers::audit_controller::{
+    record, ACTION_PASSWORD_CHANGE, ACTION_VAULT_RECOVERY_DISABLE, ACTION_VAULT_RECOVERY_ENABLE, ACTION_VAULT_RESTORE,
+};
+use crate::controllers::lockout_controller;
+use crate::controllers::login_history_controller::{
+    record_login, LOGIN_INACTIVE, LOGIN_INVALID_CREDENTIALS, LOGIN_LOCKED, LOGIN_MFA_REQUIRED, LOGIN_SUCCESS,
+};
+use crate::models::totp::MfaRequiredResponse;
+use crate::utils::challenges::LoginChallenges;
+use crate::utils::email::{normalize_email, MAX_EMAIL_LEN};
+use crate::utils::password_policy::check_master_password;
+use crate::utils::mailer::{send_in_background, MailTransport};
+use crate::middleware::auth::AuthUser;
+use crate::middleware::permissions::{ROLE_ADMIN, ROLE_USER};
+
+const EMAIL_TAKEN: &str = "Email address is already registered";
+
+fn email_taken() -> ApiError {
+    ApiError::new(ErrorCode::AlreadyExists, EMAIL_TAKEN)
+}
+
+/// Duplicate בכתיבה של משתמש הוא תמיד המייל
+fn email_error(e: RepoError) -> ApiError {
+    match e {
+        RepoError::Duplicate => email_taken(),
+        e => e.into(),
+    }
+}
+
+fn user_not_found() -> ApiError {
+    ApiError::not_found("User not found")
+}
+
+/// הבעיות של מדיניות הסיסמאות, בהודעה אחת
+pub fn weak_password(problems: Vec<String>) -> ApiError {
+    ApiError::new(ErrorCode::WeakPassword, problems.join("; "))
 }
 
 // ===================== CREATE USER =====================
 #[post("/users")]
-pub async fn create_user(pool: web::Data<SqlitePool>, user: web::Json<CreateUserDto>) -> impl Responder {
-    let now: NaiveDateTime = Utc::now().naive_utc();
+pub async fn create_user(
+    users: web::Data<dyn UserRepository>,
+    mailer: web::Data<dyn MailTransport>,
+    user: web::Json<CreateUserDto>,
+) -> Result<HttpResponse, ApiError> {
+    user.validate()?;
+    // החשבון נוצר ממתין לאימות המייל
+    let email = normalize_email(&user.email).map_err(|msg| ApiError::new(ErrorCode::ValidationFailed, msg))?;
+    if users.email_in_use(&email, None).await? {
+        return Err(email_taken());
+    }
+    check_master_password(&user.password_hash_to_login, &email).map_err(weak_password)?;
 
     // הוספת האשינג של הסיסמה
-    let password_hash = match hash_password(&user.password_hash_to_login) {
-        Ok(hash) => hash,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Hashing error: {}", e)),
+    let password_hash = hash_password(&user.password_hash_to_login).or_internal("Hashing error")?;
+
+    // מפתח כספת חדש. נעטף בסיסמה של המשתמש כשה-user_id ידוע
+    let vault_key = PendingVaultKey::new(VaultKey::generate(), &user.password_hash_to_login).or_internal("Vault key error")?;
+
+    let (token, verification) = new_verification_token();
+    let expires_at = verification.expires_at;
+    let new_user = NewUser {
+        user_first_name: user.user_first_name.clone(),
+        user_last_name: user.user_last_name.clone(),
+        email: email.clone(),
+        phone: user.phone.clone().unwrap_or_default(),
+        password_hash,
+        vault_key,
+        role: ROLE_USER,
+        verification,
     };
+    // Duplicate: רישום מקביל עם אותה כתובת
+    let created = users.create(new_user).await.map_err(email_error)?;
 
-    match sqlx::query(
-        "INSERT INTO users (user_first_name, user_last_name, email, phone, password_hash_to_login, created_at, updated_at, last_login, is_active)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1)"
-    )
-    .bind(&user.user_first_name)
-    .bind(&user.user_last_name)
-    .bind(&user.email)
-    .bind(&user.phone)
-    .bind(&password_hash) // כאן השתמשנו בהאש
-    .bind(now)
-    .bind(now)
-    .bind(now)
-    .execute(&**pool)
-    .await
-    {
-        Ok(result) => {
-            let new_user = User {
-                user_id: result.last_insert_rowid(),
-                user_first_name: user.user_first_name.clone(),
-                user_last_name: user.user_last_name.clone(),
-                email: user.email.clone(),
-                phone: user.phone.clone().unwrap_or_default(),
-                password_hash_to_login: password_hash,
-                created_at: now,
-                updated_at: now,
-                last_login: Some(now),
-                is_active: true,
-            };
-            HttpResponse::Created().json(new_user)
-        }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
-    }
+    // המייל נשלח רק אחרי ה-commit, כדי שלא יישלח token שלא נשמר
+    send_in_background(mailer.into_inner(), verification_mail(&email, &token, expires_at));
+    Ok(HttpResponse::Created().json(PublicUser::from(created)))
 }
 
 // ===================== READ ALL USERS =====================
 #[get("/users")]
-pub async fn get_users(pool: web::Data<SqlitePool>) -> impl Responder {
-    match sqlx::query("SELECT * FROM users ORDER BY user_id")
-        .fetch_all(&**pool)
-        .await
-    {
-        Ok(rows) => {
-            let users: Vec<User> = rows
-                .iter()
-                .map(|row| User {
-                    user_id: row.get("user_id"),
-                    user_first_name: row.get("user_first_name"),
-                    user_last_name: row.get("user_last_name"),
-                    email: row.get("email"),
-                    phone: row.get("phone"),
-                    password_hash_to_login: row.get("password_hash_to_login"),
-                    created_at: row.get::<NaiveDateTime, _>("created_at"),
-                    updated_at: row.get::<NaiveDateTime, _>("updated_at"),
-                    last_login: row.get::<Option<NaiveDateTime>, _>("last_login"),
-                    is_active: row.get("is_active"),
-                })
-                .collect();
-            HttpResponse::Ok().json(users)
-        }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
-    }
+pub async fn get_users(users: web::Data<dyn UserRepository>, query: ListQuery<UserFilter>) -> Result<HttpResponse, ApiError> {
+    let page = users.list(&query.filters, &query.page).await?;
+    Ok(paged_response(page, PublicUser::from))
 }
 
 // ===================== READ ONE USER =====================
 #[get("/users/{id}")]
-pub async fn get_user(pool: web::Data<SqlitePool>, path: web::Path<i64>) -> impl Responder {
-    let id = path.into_inner();
-
-    match sqlx::query("SELECT * FROM users WHERE user_id = ?")
-        .bind(id)
-        .fetch_optional(&**pool)
-        .await
-    {
-        Ok(Some(row)) => {
-            let user = User {
-                user_id: row.get("user_id"),
-                user_first_name: row.get("user_first_name"),
-                user_last_name: row.get("user_last_name"),
-                email: row.get("email"),
-                phone: row.get("phone"),
-                password_hash_to_login: row.get("password_hash_to_login"),
-                created_at: row.get::<NaiveDateTime, _>("created_at"),
-                updated_at: row.get::<NaiveDateTime, _>("updated_at"),
-                last_login: row.get::<Option<NaiveDateTime>, _>("last_login"),
-                is_active: row.get("is_active"),
-            };
-            HttpResponse::Ok().json(user)
-        }
-        Ok(None) => HttpResponse::NotFound().body("User not found"),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
-    }
+pub async fn get_user(users: web::Data<dyn UserRepository>, path: web::Path<i64>) -> Result<HttpResponse, ApiError> {
+    let user = users.get(path.into_inner()).await?.ok_or_else(user_not_found)?;
+    Ok(HttpResponse::Ok().json(PublicUser::from(user)))
 }
 
 // ===================== UPDATE USER =====================
 #[put("/users/{id}")]
 pub async fn update_user(
     pool: web::Data<SqlitePool>,
+    users: web::Data<dyn UserRepository>,
     path: web::Path<i64>,
     updated: web::Json<UpdateUserDto>,
-) -> impl Responder {
+    user: AuthUser,
+    sessions: web::Data<VaultSessions>,
+    mailer: web::Data<dyn MailTransport>,
+) -> Result<HttpResponse, ApiError> {
+    updated.validate()?;
     let id = path.into_inner();
-    let now: NaiveDateTime = Utc::now().naive_utc();
-
-    match sqlx::query(
-        "UPDATE users SET
-            user_first_name = COALESCE(?, user_first_name),
-            user_last_name = COALESCE(?, user_last_name),
-            email = COALESCE(?, email),
-            phone = COALESCE(?, phone),
-            password_hash_to_login = COALESCE(?, password_hash_to_login),
-            is_active = COALESCE(?, is_active),
-            last_login = COALESCE(?, last_login),
-            updated_at = ?
-        WHERE user_id = ?"
-    )
-    .bind(&updated.user_first_name)
-    .bind(&updated.user_last_name)
-    .bind(&updated.email)
-    .bind(&updated.phone)
-    .bind(&updated.password_hash_to_login)
-    .bind(&updated.is_active)
-    .bind(&updated.last_login)
-    .bind(now)
-    .bind(&id)
-    .execute(&**pool)
-    .await
-    {
-        Ok(result) => {
-            if result.rows_affected() > 0 {
-                HttpResponse::Ok().body("User updated successfully")
-            } else {
-                HttpResponse::NotFound().body("User not found")
-            }
+
+    // כתובת חדשה חוזרת למצב ממתין לאימות
+    let new_email = match &updated.email {
+        Some(raw) => Some(normalize_email(raw).map_err(|msg| ApiError::new(ErrorCode::ValidationFailed, msg))?),
+        None => None,
+    };
+    let mut email_changed = false;
+    if let Some(email) = &new_email {
+        let current = users.get(id).await?.ok_or_else(user_not_found)?;
+        email_changed = current.email != *email;
+        if email_changed && users.email_in_use(email, Some(id)).await? {
+            return Err(email_taken());
         }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
     }
+
+    // רק admin מפעיל או משבית חשבונות
+    if updated.is_active.is_some() && !user_has_any_role(&pool, user.user_id, &[ROLE_ADMIN]).await? {
+        return Err(ApiError::new(ErrorCode::Forbidden, "Only admins can change is_active"));
+    }
+
+    let changes = UserChanges {
+        user_first_name: updated.user_first_name.clone(),
+        user_last_name: updated.user_last_name.clone(),
+        email: new_email.clone(),
+        phone: updated.phone.clone(),
+        is_active: updated.is_active,
+        email_changed,
+    };
+    if !users.update(id, &changes).await.map_err(email_error)? {
+        return Err(user_not_found());
+    }
+
+    // חשבון שהושבת מתנתק מיד
+    if updated.is_active == Some(false) {
+        revoke_user_sessions(&pool, &sessions, id).await?;
+    }
+    if let Some(email) = new_email.as_deref().filter(|_| email_changed) {
+        send_verification(&pool, mailer.into_inner(), id, email).await?;
+    }
+    Ok(HttpResponse::Ok().body("User updated successfully"))
+}
+
+// ===================== CHANGE PASSWORD =====================
+/// מחליף את סיסמת ההתחברות של המשתמש המחובר. דורש את הסיסמה הנוכחית,
+/// עוטף מחדש את מפתח הכספת ומנתק את שאר ה-sessions
+#[post("/auth/change-password")]
+pub async fn change_password(
+    pool: web::Data<SqlitePool>,
+    users: web::Data<dyn UserRepository>,
+    sessions: web::Data<VaultSessions>,
+    req: HttpRequest,
+    user: AuthUser,
+    body: web::Json<ChangePasswordDto>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate()?;
+    let account = users.account(user.user_id).await?.ok_or_else(user_not_found)?;
+    let email = account.user.email.clone();
+
+    // ניחוש הסיסמה הנוכחית מתוך session גנוב נחסם כמו ניחוש ב-login
+    let ip = client_ip(&req);
+    if let Some(retry_after) = lockout_controller::locked_for(&pool, &email, ip.as_deref()).await? {
+        return Err(too_many_attempts(retry_after));
+    }
+    if !verify_password(&body.current_password, &account.user.password_hash_to_login).or_internal("Hashing error")? {
+        lockout_controller::record_failure(&pool, &email, ip.as_deref()).await?;
+        return Err(ApiError::new(ErrorCode::InvalidCredentials, "Current password is incorrect"));
+    }
+
+    if body.new_password == body.current_password {
+        return Err(ApiError::new(ErrorCode::ValidationFailed, "New password must differ from the current password"));
+    }
+    check_master_password(&body.new_password, &email).map_err(weak_password)?;
+
+    // אותו מפתח כספת, עטוף בסיסמה החדשה. הסיסמאות השמורות לא מוצפנות מחדש
+    let key = unlock_vault(&**users, &account, &body.current_password).await.or_internal("Vault key error")?;
+    let rewrapped = wrap_vault_key(user.user_id, &key, &body.new_password).or_internal("Vault key error")?;
+    let password_hash = hash_password(&body.new_password).or_internal("Hashing error")?;
+
+    users.set_password(user.user_id, &password_hash, &rewrapped).await?;
+
+    // מי שהחזיק בסיסמה הישנה (או בקישור איפוס) מאבד גישה; ה-session הנוכחי נשאר
+    revoke_other_sessions(&pool, &sessions, user.user_id, user.session_id).await?;
+    invalidate_reset_tokens(&pool, user.user_id).await?;
+    lockout_controller::record_success(&pool, &email).await?;
+    if let Err(e) = record(&pool, user.user_id, ACTION_PASSWORD_CHANGE, "users", user.user_id, ip.as_deref()).await {
+        log::warn!("⚠️ Failed to audit password change of user {}: {}", user.user_id, e);
+    }
+
+    Ok(HttpResponse::Ok().body("Password changed, other sessions were logged out"))
+}
+
+// ===================== VAULT RECOVERY =====================
+#[get("/auth/vault-recovery")]
+pub async fn get_vault_recovery(users: web::Data<dyn UserRepository>, user: AuthUser) -> Result<HttpResponse, ApiError> {
+    let account = users.account(user.user_id).await?.ok_or_else(user_not_found)?;
+    Ok(HttpResponse::Ok().json(VaultRecoveryStatus { enabled: account.vault_key_escrow.is_some() }))
+}
+
+/// שומר עותק של מפתח הכספת מוצפן במפתח השרת, כדי שאיפוס סיסמה ישמור את הסיסמאות השמורות.
+/// כבוי כברירת מחדל: מי שמחזיק במפתח השרת יכול לפתוח עם העותק את הכספת
+#[post("/auth/vault-recovery/enable")]
+pub async fn enable_vault_recovery(
+    pool: web::Data<SqlitePool>,
+    users: web::Data<dyn UserRepository>,
+    req: HttpRequest,
+    user: AuthUser,
+    vault: UnlockedVault,
+) -> Result<HttpResponse, ApiError> {
+    let escrow = escrow_vault_key(user.user_id, &vault.key).or_internal("Vault key error")?;
+    users.set_vault_escrow(user.user_id, Some(&escrow)).await?;
+
+    let ip = client_ip(&req);
+    if let Err(e) = record(&pool, user.user_id, ACTION_VAULT_RECOVERY_ENABLE, "users", user.user_id, ip.as_deref()).await {
+        log::warn!("⚠️ Failed to audit vault recovery of user {}: {}", user.user_id, e);
+    }
+    Ok(HttpResponse::Ok().body("Vault recovery enabled, a password reset keeps your saved passwords"))
+}
+
+/// מוחק את העותק. מכאן איפוס סיסמה פותח כספת חדשה וריקה
+#[post("/auth/vault-recovery/disable")]
+pub async fn disable_vault_recovery(
+    pool: web::Data<SqlitePool>,
+    users: web::Data<dyn UserRepository>,
+    req: HttpRequest,
+    user: AuthUser,
+) -> Result<HttpResponse, ApiError> {
+    users.set_vault_escrow(user.user_id, None).await?;
+
+    let ip = client_ip(&req);
+    if let Err(e) = record(&pool, user.user_id, ACTION_VAULT_RECOVERY_DISABLE, "users", user.user_id, ip.as_deref()).await {
+        log::warn!("⚠️ Failed to audit vault recovery of user {}: {}", user.user_id, e);
+    }
+    Ok(HttpResponse::Ok().body("Vault recovery disabled, after a password reset your saved passwords stay locked until you restore them with the old password"))
+}
+
+// ===================== RESTORE PREVIOUS VAULT =====================
+/// מחזיר לכספת הפתוחה את הסיסמאות שנשארו בכספת מלפני איפוס סיסמה (כשהשחזור היה כבוי).
+/// הסיסמה הישנה נבדקת עם אותה נעילה כמו ב-login
+#[post("/auth/vault/restore")]
+pub async fn restore_vault(
+    pool: web::Data<SqlitePool>,
+    users: web::Data<dyn UserRepository>,
+    req: HttpRequest,
+    user: AuthUser,
+    vault: UnlockedVault,
+    body: web::Json<RestoreVaultDto>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate()?;
+    if !has_previous_vault(&pool, user.user_id).await? {
+        return Err(ApiError::new(ErrorCode::Conflict, "There is no previous vault to restore"));
+    }
+    let account = users.account(user.user_id).await?.ok_or_else(user_not_found)?;
+    let email = account.user.email;
+
+    let ip = client_ip(&req);
+    if let Some(retry_after) = lockout_controller::locked_for(&pool, &email, ip.as_deref()).await? {
+        return Err(too_many_attempts(retry_after));
+    }
+    let Some(result) = restore_previous_vaults(&pool, user.user_id, &body.old_password, &vault.key).await? else {
+        lockout_controller::record_failure(&pool, &email, ip.as_deref()).await?;
+        return Err(ApiError::new(ErrorCode::InvalidCredentials, "The old password does not open a previous vault"));
+    };
+
+    lockout_controller::record_success(&pool, &email).await?;
+    // המזהים של הרשומות המשוחזרות נכתבו במפתח הקודם
+    if let Err(e) = index_usernames(&pool, user.user_id, &vault.key, false).await {
+        log::warn!("⚠️ Failed to index the usernames of user {} for search: {}", user.user_id, e);
+    }
+    if result.failed > 0 {
+        log::warn!("⚠️ {} values of user {} could not be restored and stay in the previous vault", result.failed, user.user_id);
+    }
+    if let Err(e) = record(&pool, user.user_id, ACTION_VAULT_RESTORE, "users", user.user_id, ip.as_deref()).await {
+        log::warn!("⚠️ Failed to audit vault restore of user {}: {}", user.user_id, e);
+    }
+    Ok(HttpResponse::Ok().json(VaultRestoreResult { restored: result.moved, failed: result.failed }))
 }
 
 // ===================== DELETE USER =====================
 #[delete("/users/{id}")]
-pub async fn delete_user(pool: web::Data<SqlitePool>, path: web::Path<i64>) -> impl Responder {
+pub async fn delete_user(
+    pool: web::Data<SqlitePool>,
+    users: web::Data<dyn UserRepository>,
+    sessions: web::Data<VaultSessions>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, ApiError> {
     let id = path.into_inner();
+    revoke_user_sessions(&pool, &sessions, id).await?;
 
-    match sqlx::query("DELETE FROM users WHERE user_id = ?")
-        .bind(id)
-        .execute(&**pool)
-        .await
-    {
-        Ok(result) => {
-            if result.rows_affected() > 0 {
-                HttpResponse::Ok().body("User deleted successfully")
-            } else {
-                HttpResponse::NotFound().body("User not found")
-            }
-        }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
+    // התפקידים, הסיסמאות וההיסטוריה נמחקים איתו (ON DELETE CASCADE)
+    if !users.delete(id).await? {
+        return Err(user_not_found());
     }
+    Ok(HttpResponse::Ok().body("User deleted successfully"))
 }
 
 // ===================== LOGIN =====================
@@ -209,58 +326,183 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+// רק אורך: פורמט שגוי מקבל את אותה תשובה כמו מייל שלא קיים
+impl Validate for LoginRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .secret("email", &self.email, MAX_EMAIL_LEN)
+            .secret("password", &self.password, MAX_LOGIN_PASSWORD_LEN)
+            .finish()
+    }
+}
+
+// אותה תשובה למייל שלא קיים ולסיסמה שגויה, כדי לא לחשוף אילו חשבונות קיימים
+pub const INVALID_LOGIN: &str = "Invalid email or password";
+pub const ACCOUNT_DISABLED: &str = "Account is disabled";
+
+fn account_disabled() -> ApiError {
+    ApiError::new(ErrorCode::AccountDisabled, ACCOUNT_DISABLED)
+}
+
+/// hash קבוע לבדיקה כשהמייל לא קיים, כך שהתשובה לוקחת אותו זמן
+fn dummy_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| hash_password("dummy password").expect("hashing a constant password"))
+}
+
+/// IP של החיבור עצמו. לא לפי X-Forwarded-For, שהלקוח יכול לזייף כדי לעקוף את הנעילה
+pub fn client_ip(req: &HttpRequest) -> Option<String> {
+    req.peer_addr().map(|addr| addr.ip().to_string())
+}
+
+pub fn too_many_attempts(retry_after: i64) -> ApiError {
+    ApiError::new(ErrorCode::RateLimited, "Too many failed login attempts, try again later").retry_after(retry_after)
+}
+
 #[post("/login")]
-pub async fn login(pool: web::Data<SqlitePool>, creds: web::Json<LoginRequest>) -> impl Responder {
-    let row = match sqlx::query("SELECT * FROM users WHERE email = ?")
-        .bind(&creds.email)
-        .fetch_optional(&**pool)
-        .await
-    {
-        Ok(Some(row)) => row,
-        Ok(None) => return HttpResponse::Unauthorized().body("User not found"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("DB error: {}", e)),
+pub async fn login(
+    pool: web::Data<SqlitePool>,
+    users: web::Data<dyn UserRepository>,
+    sessions: web::Data<VaultSessions>,
+    challenges: web::Data<LoginChallenges>,
+    req: HttpRequest,
+    creds: web::Json<LoginRequest>,
+) -> Result<HttpResponse, ApiError> {
+    creds.validate()?;
+    let account = users.find_account_by_email(&lockout_controller::account_key(&creds.email)).await?;
+    let known_user_id: Option<i64> = account.as_ref().map(|a| a.user.user_id);
+
+    let ip = client_ip(&req);
+    if let Some(retry_after) = lockout_controller::locked_for(&pool, &creds.email, ip.as_deref()).await? {
+        record_login(&pool, &req, known_user_id, &creds.email, LOGIN_LOCKED).await;
+        return Err(too_many_attempts(retry_after));
+    }
+
+    // השוואת הסיסמה שהוזנה להאש (גם כשהמשתמש לא קיים)
+    let stored_hash: &str = match &account {
+        Some(account) => &account.user.password_hash_to_login,
+        None => dummy_hash(),
+    };
+    let verified = verify_password(&creds.password, stored_hash).or_internal("Hashing error")?;
+    let account = match account {
+        Some(account) if verified => account,
+        _ => {
+            record_login(&pool, &req, known_user_id, &creds.email, LOGIN_INVALID_CREDENTIALS).await;
+            lockout_controller::record_failure(&pool, &creds.email, ip.as_deref()).await?;
+            return Err(ApiError::new(ErrorCode::InvalidCredentials, INVALID_LOGIN));
+        }
     };
 
-    let stored_hash: String = row.get("password_hash_to_login");
+    let user_id = account.user.user_id;
+    // חשבון מושבת לא נכנס, גם עם סיסמה נכונה
+    if !account.user.is_active {
+        record_login(&pool, &req, Some(user_id), &creds.email, LOGIN_INACTIVE).await;
+        return Err(account_disabled());
+    }
+
+    // hash שנוצר עם פרמטרים ישנים (או בלי pepper) מחושב מחדש עכשיו, כשהסיסמה ידועה
+    if needs_rehash(&account.user.password_hash_to_login) {
+        upgrade_password_hash(&**users, user_id, &creds.password, &account.user.password_hash_to_login).await;
+    }
+
+    let vault_key = unlock_vault(&**users, &account, &creds.password).await.or_internal("Vault key error")?;
 
-    // השוואת הסיסמה שהוזנה להאש
-    match verify_password(&creds.password, &stored_hash) {
-        Ok(true) => HttpResponse::Ok().body("Login successful"),
-        Ok(false) => HttpResponse::Unauthorized().body("Invalid credentials"),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Hashing error: {}", e)),
+    // עם אימות דו-שלבי ה-session נוצר רק אחרי הקוד (POST /login/totp)
+    if totp_controller::is_enabled(&pool, user_id).await? {
+        record_login(&pool, &req, Some(user_id), &creds.email, LOGIN_MFA_REQUIRED).await;
+        let (challenge_token, expires_at) = challenges.create(user_id, vault_key);
+        return Ok(HttpResponse::Ok().json(MfaRequiredResponse {
+            mfa_required: true,
+            challenge_token,
+            expires_at,
+        }));
     }
+
+    complete_login(&pool, &**users, &sessions, &req, user_id, &creds.email, vault_key).await
 }
-#[get("/users/created_in_range")]
-pub async fn get_users_created_in_range(pool: web::Data<SqlitePool>) -> impl Responder {
-    // מוסיפים טיפוס מפורש כדי למנוע שגיאת rows
-    let query_result: Result<Vec<sqlx::sqlite::SqliteRow>, sqlx::Error> = sqlx::query(
-        r#"
-        SELECT *
-        FROM users
-        WHERE strftime('%Y', created_at) BETWEEN '2022' AND '2024'
-        ORDER BY created_at ASC;
-        "#
-    )
-    .fetch_all(&**pool)
-    .await;
-
-    match query_result {
-        Ok(rows) => {
-            let users: Vec<User> = rows.iter().map(|row| User {
-                user_id: row.get("user_id"),
-                user_first_name: row.get("user_first_name"),
-                user_last_name: row.get("user_last_name"),
-                email: row.get("email"),
-                phone: row.get("phone"),
-                password_hash_to_login: row.get("password_hash_to_login"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                last_login: row.get("last_login"),
-                is_active: row.get("is_active"),
-            }).collect();
-
-            HttpResponse::Ok().json(users)
+
+/// סוף ההתחברות, אחרי הסיסמה (והגורם השני אם הוא מופעל)
+pub async fn complete_login(
+    pool: &SqlitePool,
+    users: &dyn UserRepository,
+    sessions: &VaultSessions,
+    req: &HttpRequest,
+    user_id: i64,
+    email: &str,
+    vault_key: VaultKey,
+) -> Result<HttpResponse, ApiError> {
+    // עם TOTP החשבון מתאפס רק אחרי הקוד, אחרת אפשר היה לנחש קודים בלי סוף
+    lockout_controller::record_success(pool, email).await?;
+
+    // last_login מתעדכן רק אם החשבון עדיין פעיל (אולי הושבת בין השלבים)
+    if !users.touch_last_login(user_id).await? {
+        record_login(pool, req, Some(user_id), email, LOGIN_INACTIVE).await;
+        return Err(account_disabled());
+    }
+
+    // שורות ישנות שעוד מוצפנות במפתח השרת עוברות למפתח הכספת
+    match move_rows_to_vault(pool, user_id, &vault_key).await {
+        Ok(moved) => {
+            if moved.moved > 0 {
+                log::info!("🔑 Moved {} values of user {} to the vault key", moved.moved, user_id);
+            }
+            if moved.failed > 0 {
+                log::warn!("⚠️ {} values of user {} could not be decrypted and were left as they are", moved.failed, user_id);
+            }
         }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Database error: {}", e)),
+        Err(e) => log::warn!("⚠️ Failed to move rows of user {} to the vault key: {}", user_id, e),
     }
+    // רשומות שה-username שלהן עוד לא בחיפוש (מלפני המזהים העיוורים, או שעברו עכשיו)
+    if let Err(e) = index_usernames(pool, user_id, &vault_key, true).await {
+        log::warn!("⚠️ Failed to index the usernames of user {} for search: {}", user_id, e);
+    }
+
+    let tokens = create_session(pool, sessions, user_id, vault_key).await?;
+    record_login(pool, req, Some(user_id), email, LOGIN_SUCCESS).await;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+/// מחליף hash ישן בחדש. כישלון לא מפיל את ההתחברות - ננסה שוב בפעם הבאה
+async fn upgrade_password_hash(users: &dyn UserRepository, user_id: i64, password: &str, old_hash: &str) {
+    let new_hash = match hash_password(password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            log::warn!("⚠️ Failed to rehash password of user {}: {}", user_id, e);
+            return;
+        }
+    };
+
+    // רק אם הסיסמה לא השתנתה בינתיים
+    match users.replace_password_hash(user_id, old_hash, &new_hash).await {
+        Ok(true) => log::info!("🔒 Upgraded password hash of user {}", user_id),
+        Ok(false) => {}
+        Err(e) => log::warn!("⚠️ Failed to store new password hash of user {}: {}", user_id, e),
+    }
+}
+
+/// פותח את מפתח הכספת. למשתמשים ישנים בלי מפתח - יוצר אחד עכשיו
+async fn unlock_vault(
+    users: &dyn UserRepository,
+    account: &UserAccount,
+    password: &str,
+) -> Result<VaultKey, Box<dyn std::error::Error>> {
+    let user_id = account.user.user_id;
+
+    if let Some(wrapped) = account.vault_key() {
+        let (key, unbound) = unwrap_vault_key(user_id, &wrapped, password)?;
+        // נעטף לפני שהיה קשור ל-user_id: עוטפים מחדש, עכשיו כשהסיסמה ידועה
+        if unbound {
+            users.set_vault_key(user_id, &wrap_vault_key(user_id, &key, password)?).await?;
+        }
+        return Ok(key);
+    }
+
+    let key = VaultKey::generate();
+    users.set_vault_key(user_id, &wrap_vault_key(user_id, &key, password)?).await?;
+    Ok(key)
+}
+#[get("/users/created_in_range")]
+pub async fn get_users_created_in_range(users: web::Data<dyn UserRepository>) -> Result<HttpResponse, ApiError> {
+    let found = users.created_between_years(2022, 2024).await?;
+    Ok(HttpResponse::Ok().json(found.into_iter().map(PublicUser::from).collect::<Vec<_>>()))
 }
@@ -0,0 +1,242 @@
+use std::fmt;
+
+use actix_web::error::{JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use serde::Serialize;
+
+use crate::middleware::request_id;
+use crate::repositories::RepoError;
+
+// ===================== CODES =====================
+/// קודי השגיאה שהלקוח מקבל. הקוד קובע גם את סטטוס ה-HTTP, ולא משתנה בין גרסאות
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// JSON, path או query שלא ניתן לפענח
+    MalformedRequest,
+    ValidationFailed,
+    WeakPassword,
+    /// קישור אימות מייל או איפוס סיסמה שלא תקף
+    InvalidEmailToken,
+    BadRequest,
+    Unauthenticated,
+    InvalidCredentials,
+    /// access, refresh או challenge token שלא תקף
+    InvalidToken,
+    InvalidCode,
+    Forbidden,
+    AccountDisabled,
+    EmailNotVerified,
+    VaultLocked,
+    NotFound,
+    AlreadyExists,
+    /// הפעולה לא מתאימה למצב הנוכחי (למשל 2FA שכבר מופעל)
+    Conflict,
+    /// הפניה לשורה שלא קיימת (foreign key)
+    InvalidReference,
+    /// גוף בקשה גדול מ-server.max_json_bytes
+    PayloadTooLarge,
+    RateLimited,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::MalformedRequest => "MALFORMED_REQUEST",
+            ErrorCode::ValidationFailed => "VALIDATION_FAILED",
+            ErrorCode::WeakPassword => "WEAK_PASSWORD",
+            ErrorCode::InvalidEmailToken => "INVALID_EMAIL_TOKEN",
+            ErrorCode::BadRequest => "BAD_REQUEST",
+            ErrorCode::Unauthenticated => "UNAUTHENTICATED",
+            ErrorCode::InvalidCredentials => "INVALID_CREDENTIALS",
+            ErrorCode::InvalidToken => "INVALID_TOKEN",
+            ErrorCode::InvalidCode => "INVALID_CODE",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::AccountDisabled => "ACCOUNT_DISABLED",
+            ErrorCode::EmailNotVerified => "EMAIL_NOT_VERIFIED",
+            ErrorCode::VaultLocked => "VAULT_LOCKED",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::AlreadyExists => "ALREADY_EXISTS",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::InvalidReference => "INVALID_REFERENCE",
+            ErrorCode::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            ErrorCode::RateLimited => "RATE_LIMITED",
+            ErrorCode::Internal => "INTERNAL_ERROR",
+        }
+    }
+
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::MalformedRequest
+            | ErrorCode::ValidationFailed
+            | ErrorCode::WeakPassword
+            | ErrorCode::InvalidEmailToken
+            | ErrorCode::BadRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthenticated | ErrorCode::InvalidCredentials | ErrorCode::InvalidToken | ErrorCode::InvalidCode => {
+                StatusCode::UNAUTHORIZED
+            }
+            ErrorCode::Forbidden | ErrorCode::AccountDisabled | ErrorCode::EmailNotVerified | ErrorCode::VaultLocked => {
+                StatusCode::FORBIDDEN
+            }
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::AlreadyExists | ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::InvalidReference => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+// ===================== API ERROR =====================
+/// שגיאה שחוזרת מ-handler. הלקוח מקבל רק קוד, הודעה ו-request id;
+/// הפרטים הפנימיים (SQL, הצפנה, hash) נכתבים ללוג בלבד
+#[derive(Debug)]
+pub struct ApiError {
+    code: ErrorCode,
+    message: String,
+    internal: Option<String>,
+    retry_after: Option<i64>,
+    fields: Vec<FieldError>,
+}
+
+/// שדה אחד שלא עבר בדיקה (VALIDATION_FAILED)
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'static str,
+    message: &'a str,
+    request_id: String,
+    #[serde(skip_serializing_if = "<[FieldError]>::is_empty")]
+    fields: &'a [FieldError],
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError { code, message: message.into(), internal: None, retry_after: None, fields: Vec::new() }
+    }
+
+    /// כל השדות שלא עברו בדיקה, בתשובה אחת
+    pub fn validation(fields: Vec<FieldError>) -> Self {
+        let summary: Vec<String> = fields.iter().map(|f| format!("{}: {}", f.field, f.message)).collect();
+        ApiError { fields, ..Self::new(ErrorCode::ValidationFailed, summary.join("; ")) }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    /// 500 עם הודעה כללית; detail הולך ללוג
+    pub fn internal(detail: impl fmt::Display) -> Self {
+        ApiError {
+            internal: Some(detail.to_string()),
+            ..Self::new(ErrorCode::Internal, "Internal server error")
+        }
+    }
+
+    /// מוסיף כותרת Retry-After (בשניות)
+    pub fn retry_after(mut self, seconds: i64) -> Self {
+        self.retry_after = Some(seconds);
+        self
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code.as_str(), self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.code.status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let request_id = request_id::current();
+        if let Some(detail) = &self.internal {
+            log::error!("❌ [{}] {}", request_id, detail);
+        }
+
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(seconds) = self.retry_after {
+            response.insert_header(("Retry-After", seconds.to_string()));
+        }
+        response.json(ErrorBody {
+            code: self.code.as_str(),
+            message: &self.message,
+            request_id,
+            fields: &self.fields,
+        })
+    }
+}
+
+// ===================== CONVERSIONS =====================
+/// הפרות של אילוצי ה-DB הופכות לשגיאות של הלקוח; כל השאר 500
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                ApiError::new(ErrorCode::AlreadyExists, "Resource already exists")
+            }
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                ApiError::new(ErrorCode::InvalidReference, "Referenced resource does not exist")
+            }
+            sqlx::Error::RowNotFound => ApiError::not_found("Not found"),
+            _ => ApiError::internal(format!("Database error: {}", e)),
+        }
+    }
+}
+
+impl From<RepoError> for ApiError {
+    fn from(e: RepoError) -> Self {
+        match e {
+            RepoError::NotFound => ApiError::not_found("Not found"),
+            RepoError::Duplicate => ApiError::new(ErrorCode::AlreadyExists, "Resource already exists"),
+            RepoError::InvalidReference => ApiError::new(ErrorCode::InvalidReference, "Referenced resource does not exist"),
+            RepoError::Database(e) => e.into(),
+            e => ApiError::internal(e),
+        }
+    }
+}
+
+/// לשגיאות שאין להן משמעות ללקוח: `hash_password(pw).or_internal("Hashing error")?`
+pub trait OrInternal<T> {
+    fn or_internal(self, context: &str) -> Result<T, ApiError>;
+}
+
+impl<T, E: fmt::Display> OrInternal<T> for Result<T, E> {
+    fn or_internal(self, context: &str) -> Result<T, ApiError> {
+        self.map_err(|e| ApiError::internal(format!("{}: {}", context, e)))
+    }
+}
+
+// ===================== EXTRACTORS =====================
+/// גוף, path או query שלא מתפענחים חוזרים באותו מבנה כמו שגיאות של handlers
+pub fn json_error(err: JsonPayloadError, _: &HttpRequest) -> actix_web::Error {
+    match err {
+        JsonPayloadError::Overflow { limit } | JsonPayloadError::OverflowKnownLength { limit, .. } => {
+            ApiError::new(ErrorCode::PayloadTooLarge, format!("Request body is larger than {} bytes", limit)).into()
+        }
+        err => ApiError::new(ErrorCode::MalformedRequest, err.to_string()).into(),
+    }
+}
+
+pub fn path_error(err: PathError, _: &HttpRequest) -> actix_web::Error {
+    ApiError::new(ErrorCode::MalformedRequest, err.to_string()).into()
+}
+
+pub fn query_error(err: QueryPayloadError, _: &HttpRequest) -> actix_web::Error {
+    ApiError::new(ErrorCode::MalformedRequest, err.to_string()).into()
+}
+
+/// נתיב שלא קיים
+pub async fn route_not_found() -> Result<HttpResponse, ApiError> {
+    Err(ApiError::not_found("Route not found"))
+}
@@ -1,38 +1,197 @@
-use actix_web::{App, HttpServer, web};
-use sqlx::sqlite::SqlitePoolOptions;
+use std::sync::Arc;
 
+use actix_cors::Cors;
+use actix_web::{App, HttpServer, http::header, middleware::{from_fn, Logger}, web};
+
+mod cli;
+mod config;
+mod errors;
 mod models;
 mod controllers;
 mod utils;
 mod routes;
+mod middleware;
+mod repositories;
 
 use routes::user_routes;
+use utils::{db, hash, keys, migrations, pagination};
+use repositories::Repositories;
+use utils::vault::VaultSessions;
+use utils::challenges::LoginChallenges;
+use utils::mailer::{self, MailTransport};
+use middleware::request_id;
+
+// CORS לפי ההגדרות: בלי origins מוגדרים דפדפן מ-origin אחר לא מקבל גישה
+fn cors(settings: &config::CorsSettings) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
+        .allowed_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE, header::ACCEPT])
+        .expose_headers(vec![request_id::REQUEST_ID_HEADER, pagination::TOTAL_COUNT_HEADER, pagination::NEXT_CURSOR_HEADER])
+        .max_age(3600);
+    for origin in &settings.allowed_origins {
+        cors = if origin == "*" { cors.allow_any_origin() } else { cors.allowed_origin(origin) };
+    }
+    cors
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // הגדרת מסלול לקובץ SQLite ישירות בקוד
-    let database_url = "sqlite://src/passwords_management_system.db";
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
+    let args = match cli::parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return Err(std::io::Error::other(e));
+        }
+    };
+
+    // ברירות מחדל < קובץ TOML < .env < משתני סביבה
+    let loaded = match config::load(args.config_file.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("❌ Refusing to start: {}", e);
+            return Err(std::io::Error::other(e.to_string()));
+        }
+    };
+    if args.print_config {
+        // מדפיס גם הגדרות לא תקינות, ורק אחר כך את הבעיות
+        print!("{}", loaded.redacted_toml());
+        return match loaded.validate() {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                Err(std::io::Error::other(e.to_string()))
+            }
+        };
+    }
+    let settings = match loaded.validate().and_then(|_| config::init(loaded)) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("❌ Refusing to start: {}", e);
+            return Err(std::io::Error::other(e.to_string()));
+        }
+    };
+    env_logger::Builder::new().parse_filters(&settings.log.level).init();
+
+    if let Some(command) = args.command {
+        return cli::run(command, settings).await.map_err(|e| {
+            log::error!("❌ {}", e);
+            std::io::Error::other(e.to_string())
+        });
+    }
+
+    // בלי מפתח ראשי השרת לא עולה
+    let key_loaded = keys::provider_from_config(&settings.keys)
+        .and_then(|provider| keys::init_key_ring(provider.as_ref(), &settings.keys));
+    if let Err(e) = key_loaded {
+        log::error!("❌ Refusing to start: {}", e);
+        return Err(std::io::Error::other(e.to_string()));
+    }
+
+    if let Err(e) = hash::config_from_settings(&settings.hashing).and_then(hash::init_hasher) {
+        log::error!("❌ Refusing to start: {}", e);
+        return Err(std::io::Error::other(e.to_string()));
+    }
+    // כמה זמן לוקח hash של סיסמה על המכונה הזאת, והצעה לפרמטרים
+    hash::benchmark_on_startup(settings.hashing.target_ms);
+
+    let mail_transport: Arc<dyn MailTransport> = match mailer::transport_from_config(&settings.mail) {
+        Ok(transport) => Arc::from(transport),
+        Err(e) => {
+            log::error!("❌ Refusing to start: {}", e);
+            return Err(std::io::Error::other(e.to_string()));
+        }
+    };
+    log::info!("✉️ Sending mail via {}", mail_transport.name());
+
+    // הסכמה נבנית ומתעדכנת לפני שהשרת מתחיל לקבל בקשות
+    if let Err(e) = migrations::run_pending(&settings.database).await {
+        log::error!("❌ Failed to migrate database: {}", e);
+        return Err(std::io::Error::other(e.to_string()));
+    }
+
+    // foreign_keys, journal_mode, synchronous ו-busy_timeout מוגדרים לכל חיבור ב-pool
+    let pool = db::connect_pool(&settings.database)
         .await
         .expect("❌ Failed to connect to database");
 
-    println!("✅ Connected to database");
-    println!("🚀 Server running at http://127.0.0.1:8080");
+    log::info!(
+        "✅ Connected to database (journal_mode={}, synchronous={}, foreign keys on)",
+        settings.database.journal_mode, settings.database.synchronous
+    );
+
+    match controllers::roles_controller::bootstrap_admin(&pool).await {
+        Ok(Some(user_id)) => log::info!("🔑 User #{} has the admin role", user_id),
+        Ok(None) => {}
+        Err(e) => log::warn!("⚠️ Admin role not assigned: {}", e),
+    }
+    // אם כל הערכים כבר קשורים לשורות שלהם, ערך לא קשור הוא כנראה ערך שהועתק
+    match controllers::key_rotation_controller::enforce_bound_if_migrated(&pool).await {
+        Ok(Some(0)) => log::info!("🔐 Every encrypted value is bound to its row; unbound values are refused"),
+        Ok(Some(unbound)) => log::warn!(
+            "⚠️ {} encrypted values are not bound to their rows yet; they are accepted until the key rotation job or their owner's next login binds them",
+            unbound
+        ),
+        Ok(None) => {}
+        Err(e) => log::warn!("⚠️ Could not check for unbound values: {}", e),
+    }
+    // שורות שלא מוצפנות עם המפתח הפעיל מוצפנות מחדש ברקע
+    match controllers::key_rotation_controller::resume_on_startup(&pool).await {
+        Ok(Some(job_id)) => log::info!("🔄 Key rotation job #{} started in background", job_id),
+        Ok(None) => {}
+        Err(e) => log::warn!("⚠️ Key rotation not started: {}", e),
+    }
+
+    // כספות פתוחות משותפות לכל ה-workers
+    let vault_sessions = web::Data::new(VaultSessions::default());
+    let login_challenges = web::Data::new(LoginChallenges::default());
+    let permissions = web::Data::new(routes::permission_table());
+    let mail_transport: web::Data<dyn MailTransport> = web::Data::from(mail_transport);
+    // users, passwords, history ו-categories עוברים דרך ה-repositories ולא דרך SQL ב-handlers
+    let repositories = Repositories::sqlite(pool.clone());
 
     // מריץ את השרת
-    HttpServer::new(move || {
+    let mut server = HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(vault_sessions.clone())
+            .app_data(login_challenges.clone())
+            .app_data(permissions.clone())
+            .app_data(mail_transport.clone())
+            .configure(|cfg| repositories.register(cfg))
+            // שגיאות פענוח של הבקשה חוזרות כ-JSON כמו כל שגיאה אחרת
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(settings.server.max_json_bytes)
+                    .error_handler(errors::json_error),
+            )
+            .app_data(web::PathConfig::default().error_handler(errors::path_error))
+            .app_data(web::QueryConfig::default().error_handler(errors::query_error))
+            // כל הנתיבים (חוץ מ-login/הרשמה/refresh) דורשים access token והרשאה לפי routes
+            .wrap(from_fn(middleware::auth::require_auth))
+            .wrap(cors(&config::get().cors))
+            // ה-request id עוטף הכל חוץ מה-Logger, שכותב אותו בכל שורה
+            .wrap(from_fn(request_id::assign))
+            .wrap(Logger::new(r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{x-request-id}o"#))
             .configure(user_routes::config)
             .configure(routes::password_routes::config)
             .configure(routes::category_routes::config)
-        .configure(routes::password_category_routes::config)
-        .configure(routes::password_history_routes::config)
-
-    })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
+            .configure(routes::password_category_routes::config)
+            .configure(routes::password_history_routes::config)
+            .configure(routes::key_rotation_routes::config)
+            .configure(routes::session_routes::config)
+            .configure(routes::role_routes::config)
+            .configure(routes::audit_routes::config)
+            .configure(routes::totp_routes::config)
+            .configure(routes::lockout_routes::config)
+            .configure(routes::login_history_routes::config)
+            .configure(routes::password_reset_routes::config)
+            .configure(routes::email_verification_routes::config)
+            .configure(routes::search_routes::config)
+            .default_service(web::to(errors::route_not_found))
+    });
+    for address in &settings.server.bind {
+        server = server.bind(address.as_str())?;
+        log::info!("🚀 Server running at http://{}", address);
+    }
+    server.run().await
 }
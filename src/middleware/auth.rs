@@ -0,0 +1,85 @@
+use std::future::{ready, Ready};
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{http::header, web, Error, FromRequest, HttpMessage, HttpRequest};
+use sqlx::SqlitePool;
+use crate::errors::{ApiError, ErrorCode};
+use crate::controllers::roles_controller::user_has_any_role;
+use crate::controllers::sessions_controller::find_session_by_access_token;
+use crate::middleware::permissions::{Access, PermissionTable};
+
+/// המשתמש המחובר של הבקשה, לפי ה-access token
+#[derive(Debug, Clone, Copy)]
+pub struct AuthUser {
+    pub user_id: i64,
+    pub session_id: i64,
+}
+
+impl FromRequest for AuthUser {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user = req.extensions().get::<AuthUser>().copied();
+        ready(user.ok_or_else(|| ApiError::new(ErrorCode::Unauthenticated, "Not authenticated")))
+    }
+}
+
+fn bearer_token(req: &ServiceRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
+
+/// בודק את ה-access token בכל בקשה, שם את המשתמש ב-extensions
+/// ובודק את ההרשאה של הנתיב לפי טבלאות ה-routes
+pub async fn require_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    // דחייה מוחזרת כתשובה ולא כשגיאה, כדי שה-Logger וה-CORS שעוטפים את השרת יראו אותה
+    if let Err(e) = authorize(&req).await {
+        return Ok(req.error_response(e).map_into_right_body());
+    }
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
+
+async fn authorize(req: &ServiceRequest) -> Result<(), ApiError> {
+    let (access, target_user_id) = req
+        .app_data::<web::Data<PermissionTable>>()
+        .ok_or_else(|| ApiError::internal("Permission table not configured"))?
+        .lookup(req);
+
+    if access != Access::Public {
+        let token = bearer_token(req).ok_or_else(|| ApiError::new(ErrorCode::Unauthenticated, "Missing bearer token"))?;
+        let pool = req
+            .app_data::<web::Data<SqlitePool>>()
+            .cloned()
+            .ok_or_else(|| ApiError::internal("Database pool not configured"))?;
+
+        let user = find_session_by_access_token(&pool, &token)
+            .await?
+            .ok_or_else(|| ApiError::new(ErrorCode::InvalidToken, "Invalid or expired access token"))?;
+
+        let roles = match access {
+            Access::Roles(roles) => Some(roles),
+            Access::SelfOr(_) if target_user_id == Some(user.user_id) => None,
+            Access::SelfOr(roles) => Some(roles),
+            _ => None,
+        };
+        if let Some(roles) = roles {
+            let allowed = user_has_any_role(&pool, user.user_id, roles).await?;
+            if !allowed {
+                return Err(ApiError::new(ErrorCode::Forbidden, "Insufficient permissions"));
+            }
+        }
+
+        req.extensions_mut().insert(user);
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use actix_web::dev::{Path, ResourceDef, ServiceRequest};
+
+pub const ROLE_ADMIN: &str = "admin";
+pub const ROLE_AUDITOR: &str = "auditor";
+pub const ROLE_USER: &str = "user";
+
+// ===================== ACCESS RULES =====================
+/// מי רשאי לקרוא לנתיב
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    /// בלי התחברות (login, הרשמה)
+    Public,
+    /// כל משתמש מחובר
+    Authenticated,
+    /// רק משתמש עם אחד מהתפקידים
+    Roles(&'static [&'static str]),
+    /// המשתמש שה-{id} בנתיב הוא שלו, או משתמש עם אחד מהתפקידים
+    SelfOr(&'static [&'static str]),
+}
+
+/// שורה בטבלת ההרשאות של מודול routes
+pub struct RoutePermission {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub access: Access,
+}
+
+impl RoutePermission {
+    pub const fn new(method: &'static str, path: &'static str, access: Access) -> Self {
+        RoutePermission { method, path, access }
+    }
+}
+
+// ===================== PERMISSION TABLE =====================
+struct CompiledRule {
+    method: &'static str,
+    resource: ResourceDef,
+    access: Access,
+}
+
+/// כל טבלאות ההרשאות של ה-routes יחד. החוק הראשון שמתאים קובע,
+/// ונתיב שלא מופיע באף טבלה דורש רק התחברות
+pub struct PermissionTable {
+    rules: Vec<CompiledRule>,
+}
+
+impl PermissionTable {
+    pub fn new<'a>(tables: impl IntoIterator<Item = &'a [RoutePermission]>) -> Self {
+        let rules = tables
+            .into_iter()
+            .flatten()
+            .map(|rule| CompiledRule {
+                method: rule.method,
+                resource: ResourceDef::new(rule.path),
+                access: rule.access,
+            })
+            .collect();
+        PermissionTable { rules }
+    }
+
+    /// מחזיר את הכלל של הבקשה ואת ה-{id} מהנתיב (אם יש)
+    pub fn lookup(&self, req: &ServiceRequest) -> (Access, Option<i64>) {
+        for rule in &self.rules {
+            if rule.method != req.method().as_str() {
+                continue;
+            }
+            let mut path = Path::new(req.path());
+            if rule.resource.capture_match_info(&mut path) {
+                let id = path.get("id").and_then(|id| id.parse().ok());
+                return (rule.access, id);
+            }
+        }
+        (Access::Authenticated, None)
+    }
+}
@@ -0,0 +1,66 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::error::InternalError;
+use actix_web::Error;
+use rand_core::{OsRng, RngCore};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// ה-request id של הבקשה שמטופלת עכשיו (גם בתוך ApiError::error_response)
+pub fn current() -> String {
+    in_request().unwrap_or_else(generate)
+}
+
+/// ה-request id, רק אם הקוד רץ בתוך בקשה (לא בעבודת רקע או בפקודת CLI)
+pub fn in_request() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+fn generate() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// id שהגיע מ-proxy נשמר, אם הוא קצר ובלי תווים מוזרים
+fn from_header(req: &ServiceRequest) -> Option<String> {
+    let id = req.headers().get(&REQUEST_ID_HEADER)?.to_str().ok()?;
+    let valid = !id.is_empty()
+        && id.len() <= 64
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| id.to_string())
+}
+
+/// נותן לכל בקשה id, שחוזר בכותרת X-Request-Id ובגוף של כל שגיאה
+pub async fn assign(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let id = from_header(&req).unwrap_or_else(generate);
+    let header = HeaderValue::from_str(&id).ok();
+
+    let result = REQUEST_ID
+        .scope(id, async {
+            next.call(req).await.map_err(|e| {
+                // שגיאה שלא הפכה לתשובה נבנית כבר כאן, בתוך ה-scope, כדי שתכיל את ה-id
+                let mut response = e.error_response();
+                if let Some(value) = header.clone() {
+                    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+                }
+                Error::from(InternalError::from_response(e, response))
+            })
+        })
+        .await;
+
+    let mut res = result?;
+    if let Some(value) = header {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(res)
+}
@@ -0,0 +1,13 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    pub audit_id: i64,
+    pub user_id: i64,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: i64,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+}
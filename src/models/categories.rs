@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::errors::ApiError;
+use crate::utils::pagination::{CursorValue, ListFilters, Sortable};
+use crate::utils::validation::{Validate, Validator, MAX_CATEGORY_NAME_LEN};
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct Category {
     pub category_id: i64,
     pub category_name: String,
@@ -15,3 +19,40 @@ pub struct CreateCategoryDto {
 pub struct UpdateCategoryDto {
     pub category_name: Option<String>,
 }
+
+// ===================== VALIDATION =====================
+impl Validate for CreateCategoryDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new().text("category_name", &self.category_name, MAX_CATEGORY_NAME_LEN).finish()
+    }
+}
+
+impl Validate for UpdateCategoryDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .optional_text("category_name", self.category_name.as_deref(), MAX_CATEGORY_NAME_LEN)
+            .finish()
+    }
+}
+
+// ===================== LIST =====================
+/// GET /categories: השם מכיל (בלי הבדל רישיות)
+#[derive(Debug, Default, Deserialize)]
+pub struct CategoryFilter {
+    pub name: Option<String>,
+}
+
+impl ListFilters for CategoryFilter {
+    const SORT_KEYS: &'static [&'static str] = &["category_id", "category_name"];
+    const DEFAULT_SORT: &'static str = "category_id";
+}
+
+impl Sortable for Category {
+    fn position(&self, key: &str) -> (CursorValue, i64) {
+        let value = match key {
+            "category_name" => CursorValue::Text(self.category_name.clone()),
+            _ => CursorValue::Int(self.category_id),
+        };
+        (value, self.category_id)
+    }
+}
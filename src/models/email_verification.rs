@@ -0,0 +1,15 @@
+use serde::Deserialize;
+
+use crate::errors::ApiError;
+use crate::utils::validation::{Validate, Validator, MAX_TOKEN_LEN};
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailDto {
+    pub token: String,
+}
+
+impl Validate for VerifyEmailDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new().secret("token", &self.token, MAX_TOKEN_LEN).finish()
+    }
+}
@@ -0,0 +1,19 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KeyRotationJob {
+    pub job_id: i64,
+    pub target_key_id: i64,
+    pub status: String,
+    pub phase: String,
+    pub last_id: i64,
+    pub total_rows: i64,
+    pub processed_rows: i64,
+    pub rewrapped_rows: i64,
+    pub failed_rows: i64,
+    pub error: Option<String>,
+    pub started_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
@@ -0,0 +1,11 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct LoginThrottle {
+    pub scope: String,
+    pub key: String,
+    pub failed_count: i64,
+    pub last_failed_at: NaiveDateTime,
+    pub locked_until: Option<NaiveDateTime>,
+}
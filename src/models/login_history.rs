@@ -0,0 +1,13 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct LoginHistoryEntry {
+    pub login_id: i64,
+    pub user_id: Option<i64>,
+    pub email: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub result: String,
+    pub created_at: NaiveDateTime,
+}
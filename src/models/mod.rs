@@ -2,4 +2,15 @@ pub mod users;
 pub mod passwords;
 pub mod categories;
 pub mod password_category;
-pub mod password_history;
\ No newline at end of file
+pub mod password_history;
+pub mod key_rotation;
+pub mod sessions;
+
+pub mod roles;
+pub mod audit;
+pub mod totp;
+pub mod lockout;
+pub mod login_history;
+pub mod password_reset;
+pub mod email_verification;
+pub mod search;
\ No newline at end of file
@@ -1,13 +1,57 @@
 use serde::{Deserialize, Serialize};
 
+use crate::errors::ApiError;
+use crate::utils::pagination::{CursorValue, ListFilters, Sortable};
+use crate::utils::validation::{Validate, Validator};
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreatePasswordCategoryDto {
     pub password_id: i64,
     pub category_id: i64,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow)]
 pub struct PasswordCategory {
     pub password_id: i64,
     pub category_id: i64,
 }
+
+/// GET /password_category/users_with_com: משתמש שיש לו סיסמה בקטגוריה
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct UserShoppingInfo {
+    pub user_id: i64,
+    pub user_first_name: String,
+    pub user_last_name: String,
+    pub category_name: String,
+}
+
+impl Validate for CreatePasswordCategoryDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .id("password_id", self.password_id)
+            .id("category_id", self.category_id)
+            .finish()
+    }
+}
+
+// ===================== LIST =====================
+#[derive(Debug, Default, Deserialize)]
+pub struct PasswordCategoryFilter {
+    pub password_id: Option<i64>,
+    pub category_id: Option<i64>,
+}
+
+impl ListFilters for PasswordCategoryFilter {
+    const SORT_KEYS: &'static [&'static str] = &["password_id", "category_id"];
+    const DEFAULT_SORT: &'static str = "password_id";
+}
+
+// לקישור אין מזהה משלו: הזוג ייחודי, לכן העמודה השנייה מכריעה
+impl Sortable for PasswordCategory {
+    fn position(&self, key: &str) -> (CursorValue, i64) {
+        match key {
+            "category_id" => (CursorValue::Int(self.category_id), self.password_id),
+            _ => (CursorValue::Int(self.password_id), self.category_id),
+        }
+    }
+}
@@ -1,16 +1,47 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::utils::pagination::{CursorValue, ListFilters, Sortable};
+
+// השדה של הרשומה שהערך הישן שלו נשמר (עמודת field)
+pub const FIELD_PASSWORD: &str = "password";
+pub const FIELD_DOMAIN: &str = "domain";
+pub const FIELD_USERNAME: &str = "username";
+pub const FIELD_URLS: &str = "urls";
+pub const FIELD_NOTES: &str = "notes";
+pub const FIELD_CUSTOM: &str = "fields";
+
+/// ערך קודם של שדה ברשומה. old_password_encrypted מחזיק את הערך הישן של field, לא רק של הסיסמה
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct PasswordHistory {
     pub history_id: i64,
     pub password_id: i64,
+    pub field: String,
     pub old_password_encrypted: String,
     pub changed_at: NaiveDateTime,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreatePasswordHistoryDto {
-    pub password_id: i64,
-    pub old_password_encrypted: String,
+// ===================== LIST =====================
+/// GET /password_history: סיסמה אחת, שדה אחד, וטווח של changed_at (from כולל, to לא כולל)
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryFilter {
+    pub password_id: Option<i64>,
+    pub field: Option<String>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+impl ListFilters for HistoryFilter {
+    const SORT_KEYS: &'static [&'static str] = &["changed_at", "history_id"];
+    const DEFAULT_SORT: &'static str = "-changed_at";
+}
+
+impl Sortable for PasswordHistory {
+    fn position(&self, key: &str) -> (CursorValue, i64) {
+        let value = match key {
+            "changed_at" => CursorValue::Time(self.changed_at),
+            _ => CursorValue::Int(self.history_id),
+        };
+        (value, self.history_id)
+    }
 }
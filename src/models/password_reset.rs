@@ -0,0 +1,31 @@
+use serde::Deserialize;
+
+use crate::errors::ApiError;
+use crate::utils::validation::{Validate, Validator, MAX_LOGIN_PASSWORD_LEN, MAX_TOKEN_LEN};
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordDto {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordDto {
+    pub token: String,
+    pub new_password: String,
+}
+
+// ===================== VALIDATION =====================
+impl Validate for ForgotPasswordDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new().email("email", &self.email).finish()
+    }
+}
+
+impl Validate for ResetPasswordDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .secret("token", &self.token, MAX_TOKEN_LEN)
+            .secret("new_password", &self.new_password, MAX_LOGIN_PASSWORD_LEN)
+            .finish()
+    }
+}
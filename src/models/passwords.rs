@@ -1,7 +1,15 @@
-use chrono::NaiveDateTime;
+use std::collections::HashSet;
+
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::errors::ApiError;
+use crate::utils::pagination::{CursorValue, ListFilters, Sortable};
+use crate::utils::validation::{
+    Validate, Validator, MAX_CUSTOM_FIELDS, MAX_FIELD_NAME_LEN, MAX_NOTES_LEN, MAX_SECRET_LEN, MAX_URLS, MAX_USERNAME_LEN,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct Password {
     pub password_id: i64,
     pub user_id: i64,
@@ -9,17 +17,202 @@ pub struct Password {
     pub password_encrypted: String,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    // הפרטים הנוספים, כל אחד מוצפן בנפרד. None = אין ערך
+    pub username_encrypted: Option<String>,
+    pub urls_encrypted: Option<String>,
+    pub notes_encrypted: Option<String>,
+    pub fields_encrypted: Option<String>,
+}
+
+// ===================== ENTRY DETAILS =====================
+/// ערך של שדה מותאם. hidden נשמר כמו text, והלקוח מסתיר אותו בתצוגה
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum FieldValue {
+    Text(String),
+    Hidden(String),
+    Boolean(bool),
+    Date(NaiveDate),
+}
+
+/// {"name": "PIN", "type": "hidden", "value": "1234"}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomField {
+    pub name: String,
+    #[serde(flatten)]
+    pub value: FieldValue,
+}
+
+/// מה שנשמר ברשומה מעבר לדומיין ולסיסמה, בטקסט גלוי
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EntryDetails {
+    pub username: Option<String>,
+    pub urls: Vec<String>,
+    pub notes: Option<String>,
+    pub fields: Vec<CustomField>,
+}
+
+// במקום הסיסמה עצמה. תמיד באותו אורך כדי לא לחשוף את האורך האמיתי
+pub const MASKED_SECRET: &str = "********";
+
+/// מה שמוחזר ברשימות: בלי הסיסמה (גם לא המוצפנת)
+#[derive(Debug, Serialize)]
+pub struct PublicPassword {
+    pub password_id: i64,
+    pub user_id: i64,
+    pub domain: String,
+    pub password_masked: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl From<Password> for PublicPassword {
+    fn from(password: Password) -> Self {
+        PublicPassword {
+            password_id: password.password_id,
+            user_id: password.user_id,
+            domain: password.domain,
+            password_masked: MASKED_SECRET.to_string(),
+            created_at: password.created_at,
+            updated_at: password.updated_at,
+        }
+    }
+}
+
+/// תשובת reveal - הסיסמה בטקסט גלוי, רק לבעלים
+#[derive(Debug, Serialize)]
+pub struct RevealedPassword {
+    pub password_id: i64,
+    pub domain: String,
+    pub password: String,
+    #[serde(flatten)]
+    pub details: EntryDetails,
+}
+
+/// משתמש וכמה סיסמאות שמורות לו
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct PasswordSummary {
+    pub user_id: i64,
+    pub user_first_name: String,
+    pub user_last_name: String,
+    pub password_count: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreatePasswordDto {
-    pub user_id: i64,
     pub domain: String,
     pub password_encrypted: String,
+    pub username: Option<String>,
+    #[serde(default)]
+    pub urls: Vec<String>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<CustomField>,
+}
+
+impl CreatePasswordDto {
+    pub fn details(&self) -> EntryDetails {
+        EntryDetails {
+            username: self.username.clone(),
+            urls: self.urls.clone(),
+            notes: self.notes.clone(),
+            fields: self.fields.clone(),
+        }
+    }
 }
 
+/// שדה שלא נשלח לא משתנה. "" מוחק את username או notes, [] מוחק את urls או fields
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdatePasswordDto {
     pub domain: Option<String>,
     pub password_encrypted: Option<String>,
+    pub username: Option<String>,
+    pub urls: Option<Vec<String>>,
+    pub notes: Option<String>,
+    pub fields: Option<Vec<CustomField>>,
+}
+
+// ===================== VALIDATION =====================
+fn validate_urls(v: &mut Validator, urls: &[String]) {
+    if urls.len() > MAX_URLS {
+        v.error("urls", format!("At most {} URLs", MAX_URLS));
+    }
+    for (i, url) in urls.iter().enumerate() {
+        v.url(&format!("urls[{}]", i), url);
+    }
+}
+
+/// שמות לא ריקים ושונים זה מזה (בלי הבדל רישיות). ערכי טקסט כמו סיסמה: כל תו מותר
+fn validate_fields(v: &mut Validator, fields: &[CustomField]) {
+    if fields.len() > MAX_CUSTOM_FIELDS {
+        v.error("fields", format!("At most {} custom fields", MAX_CUSTOM_FIELDS));
+    }
+    let mut names = HashSet::new();
+    for (i, field) in fields.iter().enumerate() {
+        v.text(&format!("fields[{}].name", i), &field.name, MAX_FIELD_NAME_LEN);
+        if !names.insert(field.name.trim().to_lowercase()) {
+            v.error(&format!("fields[{}].name", i), "Must be unique within the entry");
+        }
+        if let FieldValue::Text(value) | FieldValue::Hidden(value) = &field.value {
+            v.secret(&format!("fields[{}].value", i), value, MAX_SECRET_LEN);
+        }
+    }
+}
+
+impl Validate for CreatePasswordDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.domain("domain", &self.domain)
+            .secret("password_encrypted", &self.password_encrypted, MAX_SECRET_LEN)
+            .optional_text("username", self.username.as_deref(), MAX_USERNAME_LEN)
+            .optional_secret("notes", self.notes.as_deref(), MAX_NOTES_LEN);
+        validate_urls(&mut v, &self.urls);
+        validate_fields(&mut v, &self.fields);
+        v.finish()
+    }
+}
+
+impl Validate for UpdatePasswordDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        // "" מוחק, לכן נבדק רק ערך לא ריק
+        let username = self.username.as_deref().filter(|username| !username.is_empty());
+        let notes = self.notes.as_deref().filter(|notes| !notes.is_empty());
+        let mut v = Validator::new();
+        v.optional_domain("domain", self.domain.as_deref())
+            .optional_secret("password_encrypted", self.password_encrypted.as_deref(), MAX_SECRET_LEN)
+            .optional_text("username", username, MAX_USERNAME_LEN)
+            .optional_secret("notes", notes, MAX_NOTES_LEN);
+        validate_urls(&mut v, self.urls.as_deref().unwrap_or_default());
+        validate_fields(&mut v, self.fields.as_deref().unwrap_or_default());
+        v.finish()
+    }
+}
+
+// ===================== LIST =====================
+/// GET /passwords: דומיין מכיל, קטגוריה, וזמן העדכון האחרון.
+/// user_id של משתמש אחר רק ל-admin
+#[derive(Debug, Default, Deserialize)]
+pub struct PasswordFilter {
+    pub user_id: Option<i64>,
+    pub domain: Option<String>,
+    pub category_id: Option<i64>,
+    pub updated_after: Option<NaiveDateTime>,
+    pub updated_before: Option<NaiveDateTime>,
+}
+
+impl ListFilters for PasswordFilter {
+    const SORT_KEYS: &'static [&'static str] = &["password_id", "domain", "created_at", "updated_at"];
+    const DEFAULT_SORT: &'static str = "password_id";
+}
+
+impl Sortable for Password {
+    fn position(&self, key: &str) -> (CursorValue, i64) {
+        let value = match key {
+            "domain" => CursorValue::Text(self.domain.clone()),
+            "created_at" => CursorValue::Time(self.created_at),
+            "updated_at" => CursorValue::Time(self.updated_at),
+            _ => CursorValue::Int(self.password_id),
+        };
+        (value, self.password_id)
+    }
 }
@@ -0,0 +1,29 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ApiError;
+use crate::utils::validation::{Validate, Validator, MAX_ROLE_NAME_LEN};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Role {
+    pub role_id: i64,
+    pub role_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserRole {
+    pub user_id: i64,
+    pub role_name: String,
+    pub assigned_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssignRoleDto {
+    pub role_name: String,
+}
+
+impl Validate for AssignRoleDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new().text("role_name", &self.role_name, MAX_ROLE_NAME_LEN).finish()
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ApiError;
+use crate::utils::validation::{Validate, Validator};
+
+pub const MAX_QUERY_LEN: usize = 200;
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+pub const MAX_SEARCH_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: Option<usize>,
+}
+
+impl Validate for SearchQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.text("q", &self.q, MAX_QUERY_LEN);
+        if self.limit.is_some_and(|limit| !(1..=MAX_SEARCH_LIMIT).contains(&limit)) {
+            v.error("limit", format!("Must be between 1 and {}", MAX_SEARCH_LIMIT));
+        }
+        v.finish()
+    }
+}
+
+/// תוצאה אחת, מהמתאימה ביותר. ב-*_highlight הטקסט עבר escape של HTML וההתאמות בתוך <mark>
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub password_id: i64,
+    pub domain: String,
+    pub domain_highlight: String,
+    pub categories_highlight: Option<String>,
+    // אחת המילים נמצאה ב-username. ה-username עצמו מוצפן ולא מוחזר
+    pub username_match: bool,
+    pub score: f64,
+}
@@ -0,0 +1,26 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ApiError;
+use crate::utils::validation::{Validate, Validator, MAX_TOKEN_LEN};
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub user_id: i64,
+    pub token_type: String,
+    pub access_token: String,
+    pub access_expires_at: NaiveDateTime,
+    pub refresh_token: String,
+    pub refresh_expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+impl Validate for RefreshRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new().secret("refresh_token", &self.refresh_token, MAX_TOKEN_LEN).finish()
+    }
+}
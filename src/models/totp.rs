@@ -0,0 +1,74 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ApiError;
+use crate::utils::validation::{Validate, Validator, MAX_CODE_LEN, MAX_TOKEN_LEN};
+
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+/// קוד מהאפליקציה או קוד שחזור (אחד מהשניים)
+#[derive(Debug, Deserialize)]
+pub struct SecondFactorDto {
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+/// תשובת login כשלמשתמש מופעל אימות דו-שלבי
+#[derive(Debug, Serialize)]
+pub struct MfaRequiredResponse {
+    pub mfa_required: bool,
+    pub challenge_token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginTotpRequest {
+    pub challenge_token: String,
+    #[serde(flatten)]
+    pub factor: SecondFactorDto,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TotpConfirmDto {
+    pub code: String,
+}
+
+// ===================== VALIDATION =====================
+impl SecondFactorDto {
+    fn check(&self, v: &mut Validator) {
+        v.optional_secret("code", self.code.as_deref(), MAX_CODE_LEN)
+            .optional_secret("recovery_code", self.recovery_code.as_deref(), MAX_CODE_LEN);
+    }
+}
+
+impl Validate for SecondFactorDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        self.check(&mut v);
+        v.finish()
+    }
+}
+
+impl Validate for LoginTotpRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        let mut v = Validator::new();
+        v.secret("challenge_token", &self.challenge_token, MAX_TOKEN_LEN);
+        self.factor.check(&mut v);
+        v.finish()
+    }
+}
+
+impl Validate for TotpConfirmDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new().secret("code", &self.code, MAX_CODE_LEN).finish()
+    }
+}
@@ -1,7 +1,11 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+use crate::errors::ApiError;
+use crate::utils::pagination::{CursorValue, ListFilters, Sortable};
+use crate::utils::validation::{Validate, Validator, MAX_LOGIN_PASSWORD_LEN, MAX_NAME_LEN};
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct User {
     pub user_id: i64,
     pub user_first_name: String,
@@ -13,6 +17,40 @@ pub struct User {
     pub updated_at: NaiveDateTime,
     pub last_login: Option<NaiveDateTime>,
     pub is_active: bool,
+    pub email_verified_at: Option<NaiveDateTime>,
+}
+
+/// מה שמוחזר ללקוח: בלי ה-hash של סיסמת ההתחברות
+#[derive(Debug, Serialize)]
+pub struct PublicUser {
+    pub user_id: i64,
+    pub user_first_name: String,
+    pub user_last_name: String,
+    pub email: String,
+    pub phone: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub last_login: Option<NaiveDateTime>,
+    pub is_active: bool,
+    /// ריק כל עוד המייל ממתין לאימות
+    pub email_verified_at: Option<NaiveDateTime>,
+}
+
+impl From<User> for PublicUser {
+    fn from(user: User) -> Self {
+        PublicUser {
+            user_id: user.user_id,
+            user_first_name: user.user_first_name,
+            user_last_name: user.user_last_name,
+            email: user.email,
+            phone: user.phone,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+            last_login: user.last_login,
+            is_active: user.is_active,
+            email_verified_at: user.email_verified_at,
+        }
+    }
 }
 
 //create
@@ -26,13 +64,108 @@ pub struct CreateUserDto {
 }
 
 //update
+// הסיסמה משתנה רק דרך POST /auth/change-password, ושדה לא מוכר (למשל last_login) נדחה
 #[derive(Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct UpdateUserDto {
     pub user_first_name: Option<String>,
     pub user_last_name: Option<String>,
     pub email: Option<String>,
     pub phone: Option<String>,
-    pub password_hash_to_login: Option<String>,
     pub is_active: Option<bool>,
-    pub last_login: Option<NaiveDateTime>,
+}
+
+//change password
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordDto {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// GET /auth/vault-recovery: האם יש עותק של מפתח הכספת לאיפוס סיסמה
+#[derive(Debug, Serialize)]
+pub struct VaultRecoveryStatus {
+    pub enabled: bool,
+}
+
+/// POST /auth/vault/restore: הסיסמה מלפני האיפוס, שפותחת את הכספת הקודמת
+#[derive(Debug, Deserialize)]
+pub struct RestoreVaultDto {
+    pub old_password: String,
+}
+
+/// כמה ערכים (סיסמאות, שדות והיסטוריה) עברו לכספת הנוכחית, וכמה נשארו בכספת הקודמת
+#[derive(Debug, Serialize)]
+pub struct VaultRestoreResult {
+    pub restored: u64,
+    pub failed: u64,
+}
+
+// ===================== VALIDATION =====================
+// הסיסמה החדשה נבדקת אחר כך מול password_policy (WEAK_PASSWORD)
+impl Validate for CreateUserDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .text("user_first_name", &self.user_first_name, MAX_NAME_LEN)
+            .text("user_last_name", &self.user_last_name, MAX_NAME_LEN)
+            .email("email", &self.email)
+            .optional_phone("phone", self.phone.as_deref())
+            .secret("password_hash_to_login", &self.password_hash_to_login, MAX_LOGIN_PASSWORD_LEN)
+            .finish()
+    }
+}
+
+impl Validate for UpdateUserDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .optional_text("user_first_name", self.user_first_name.as_deref(), MAX_NAME_LEN)
+            .optional_text("user_last_name", self.user_last_name.as_deref(), MAX_NAME_LEN)
+            .optional_email("email", self.email.as_deref())
+            .optional_phone("phone", self.phone.as_deref())
+            .finish()
+    }
+}
+
+impl Validate for ChangePasswordDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .secret("current_password", &self.current_password, MAX_LOGIN_PASSWORD_LEN)
+            .secret("new_password", &self.new_password, MAX_LOGIN_PASSWORD_LEN)
+            .finish()
+    }
+}
+
+impl Validate for RestoreVaultDto {
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .secret("old_password", &self.old_password, MAX_LOGIN_PASSWORD_LEN)
+            .finish()
+    }
+}
+
+// ===================== LIST =====================
+/// GET /users: email מכיל (בלי הבדל רישיות), פעיל או לא, ותאריך יצירה
+#[derive(Debug, Default, Deserialize)]
+pub struct UserFilter {
+    pub email: Option<String>,
+    pub is_active: Option<bool>,
+    pub created_after: Option<NaiveDateTime>,
+    pub created_before: Option<NaiveDateTime>,
+}
+
+impl ListFilters for UserFilter {
+    const SORT_KEYS: &'static [&'static str] = &["user_id", "email", "user_last_name", "created_at"];
+    const DEFAULT_SORT: &'static str = "user_id";
+}
+
+impl Sortable for User {
+    fn position(&self, key: &str) -> (CursorValue, i64) {
+        let value = match key {
+            "email" => CursorValue::Text(self.email.clone()),
+            "user_last_name" => CursorValue::Text(self.user_last_name.clone()),
+            "created_at" => CursorValue::Time(self.created_at),
+            _ => CursorValue::Int(self.user_id),
+        };
+        (value, self.user_id)
+    }
 }
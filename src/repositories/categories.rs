@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+use crate::models::categories::{Category, CategoryFilter};
+use crate::models::password_category::UserShoppingInfo;
+use crate::repositories::RepoError;
+use crate::utils::pagination::{Page, Paged};
+
+#[async_trait]
+pub trait CategoryRepository: Send + Sync {
+    /// Duplicate אם כבר יש קטגוריה בשם הזה
+    async fn create(&self, name: &str) -> Result<Category, RepoError>;
+    async fn list(&self, filter: &CategoryFilter, page: &Page) -> Result<Paged<Category>, RepoError>;
+    async fn get(&self, category_id: i64) -> Result<Option<Category>, RepoError>;
+    /// false אם הקטגוריה לא קיימת. name None לא משנה כלום
+    async fn rename(&self, category_id: i64, name: Option<&str>) -> Result<bool, RepoError>;
+    /// הקישורים של סיסמאות לקטגוריה נמחקים איתה
+    async fn delete(&self, category_id: i64) -> Result<bool, RepoError>;
+    /// קטגוריות שהשם שלהן מכיל את keyword
+    async fn search(&self, keyword: &str) -> Result<Vec<Category>, RepoError>;
+    /// כל משתמש שיש לו לפחות סיסמה אחת בקטגוריה name, פעם אחת
+    async fn users_in(&self, name: &str) -> Result<Vec<UserShoppingInfo>, RepoError>;
+}
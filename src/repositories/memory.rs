@@ -0,0 +1,593 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use chrono::{Datelike, Utc};
+use crate::models::categories::{Category, CategoryFilter};
+use crate::models::password_category::{PasswordCategory, PasswordCategoryFilter, UserShoppingInfo};
+use crate::models::password_history::{HistoryFilter, PasswordHistory, FIELD_DOMAIN, FIELD_PASSWORD};
+use crate::models::passwords::{Password, PasswordFilter, PasswordSummary, RevealedPassword};
+use crate::models::users::{User, UserFilter};
+use crate::repositories::RepoError;
+use crate::repositories::categories::CategoryRepository;
+use crate::repositories::password_history::PasswordHistoryRepository;
+use crate::repositories::passwords::{open_details, replaced_values, seal_details, NewPassword, PasswordChanges, PasswordRepository};
+use crate::repositories::users::{NewUser, UserAccount, UserChanges, UserRepository};
+use crate::utils::encryption::{history_field_aad, passwords_aad};
+use crate::utils::pagination::{Page, Paged};
+use crate::utils::vault::{UnlockedVault, WrappedVaultKey};
+
+#[derive(Default)]
+struct Tables {
+    users: BTreeMap<i64, UserAccount>,
+    passwords: BTreeMap<i64, Password>,
+    history: BTreeMap<i64, PasswordHistory>,
+    categories: BTreeMap<i64, Category>,
+    /// (password_id, category_id)
+    password_category: BTreeSet<(i64, i64)>,
+}
+
+fn next_id<V>(rows: &BTreeMap<i64, V>) -> i64 {
+    rows.keys().next_back().map_or(1, |id| id + 1)
+}
+
+/// כמו instr(lower(..), lower(..)) ב-SQLite
+fn contains(value: &str, needle: &Option<String>) -> bool {
+    needle.as_ref().is_none_or(|needle| value.to_lowercase().contains(&needle.to_lowercase()))
+}
+
+impl Tables {
+    fn email_taken(&self, email: &str, except_user_id: Option<i64>) -> bool {
+        let email = email.to_lowercase();
+        self.users
+            .values()
+            .any(|a| a.user.email.to_lowercase() == email && Some(a.user.user_id) != except_user_id)
+    }
+
+    fn user_mut(&mut self, user_id: i64) -> Result<&mut UserAccount, RepoError> {
+        self.users.get_mut(&user_id).ok_or(RepoError::NotFound)
+    }
+
+    fn owned_password(&self, user_id: i64, password_id: i64) -> Option<&Password> {
+        self.passwords.get(&password_id).filter(|p| p.user_id == user_id)
+    }
+
+    fn domain_taken(&self, user_id: i64, domain: &str, except_password_id: Option<i64>) -> bool {
+        self.passwords
+            .values()
+            .any(|p| p.user_id == user_id && p.domain == domain && Some(p.password_id) != except_password_id)
+    }
+
+    /// שורת היסטוריה מוצפנת (הערך הקודם של field), עוד לא בטבלה.
+    /// offset: כמה שורות כבר הוכנו באותה פעולה ועוד לא נכנסו
+    fn new_history(
+        &self,
+        vault: &UnlockedVault,
+        password_id: i64,
+        field: &str,
+        old_password: &str,
+        offset: i64,
+    ) -> Result<PasswordHistory, RepoError> {
+        let history_id = next_id(&self.history) + offset;
+        let old_password_encrypted = vault
+            .encrypt(old_password, &history_field_aad(history_id, password_id, field))
+            .map_err(|e| RepoError::Encryption(e.to_string()))?;
+        Ok(PasswordHistory {
+            history_id,
+            password_id,
+            field: field.to_string(),
+            old_password_encrypted,
+            changed_at: Utc::now().naive_utc(),
+        })
+    }
+
+    /// כמו ON DELETE CASCADE: ההיסטוריה והקישורים לקטגוריות נמחקים עם הסיסמה
+    fn delete_password(&mut self, password_id: i64) -> bool {
+        if self.passwords.remove(&password_id).is_none() {
+            return false;
+        }
+        self.history.retain(|_, h| h.password_id != password_id);
+        self.password_category.retain(|(p, _)| *p != password_id);
+        true
+    }
+}
+
+/// אותם חוקים כמו ב-SQLite (ייחודיות, cascade, הצפנה קשורה לשורה), בזיכרון.
+/// כל פעולה מחזיקה את הנעילה עד הסוף ומשנה את הטבלאות רק אחרי שכל מה שיכול להיכשל הצליח
+#[derive(Default)]
+pub struct MemoryStore {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryStore {
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap()
+    }
+}
+
+// ===================== USERS =====================
+#[async_trait]
+impl UserRepository for MemoryStore {
+    /// התפקיד וה-token לא נשמרים כאן: תפקידים ואימות מייל עובדים ישירות מול ה-pool
+    async fn create(&self, user: NewUser) -> Result<User, RepoError> {
+        let mut tables = self.tables();
+        if tables.email_taken(&user.email, None) {
+            return Err(RepoError::Duplicate);
+        }
+        let now = Utc::now().naive_utc();
+        let user_id = next_id(&tables.users);
+        let vault_key = user.vault_key.wrap(user_id).map_err(|e| RepoError::Encryption(e.to_string()))?;
+        let created = User {
+            user_id,
+            user_first_name: user.user_first_name,
+            user_last_name: user.user_last_name,
+            email: user.email,
+            phone: user.phone,
+            password_hash_to_login: user.password_hash,
+            created_at: now,
+            updated_at: now,
+            last_login: None,
+            is_active: true,
+            email_verified_at: None,
+        };
+        tables.users.insert(created.user_id, UserAccount {
+            user: created.clone(),
+            vault_key_salt: Some(vault_key.salt),
+            vault_key_wrapped: Some(vault_key.wrapped),
+            vault_key_escrow: None,
+        });
+        Ok(created)
+    }
+
+    async fn list(&self, filter: &UserFilter, page: &Page) -> Result<Paged<User>, RepoError> {
+        let rows = self
+            .tables()
+            .users
+            .values()
+            .map(|a| &a.user)
+            .filter(|u| contains(&u.email, &filter.email))
+            .filter(|u| filter.is_active.is_none_or(|active| u.is_active == active))
+            .filter(|u| filter.created_after.is_none_or(|after| u.created_at >= after))
+            .filter(|u| filter.created_before.is_none_or(|before| u.created_at < before))
+            .cloned()
+            .collect();
+        Ok(page.apply(rows))
+    }
+
+    async fn get(&self, user_id: i64) -> Result<Option<User>, RepoError> {
+        Ok(self.tables().users.get(&user_id).map(|a| a.user.clone()))
+    }
+
+    async fn account(&self, user_id: i64) -> Result<Option<UserAccount>, RepoError> {
+        Ok(self.tables().users.get(&user_id).cloned())
+    }
+
+    async fn find_account_by_email(&self, email: &str) -> Result<Option<UserAccount>, RepoError> {
+        Ok(self.tables().users.values().find(|a| a.user.email.to_lowercase() == email.to_lowercase()).cloned())
+    }
+
+    async fn email_in_use(&self, email: &str, except_user_id: Option<i64>) -> Result<bool, RepoError> {
+        Ok(self.tables().email_taken(email, except_user_id))
+    }
+
+    async fn update(&self, user_id: i64, changes: &UserChanges) -> Result<bool, RepoError> {
+        let mut tables = self.tables();
+        if let Some(email) = &changes.email
+            && tables.email_taken(email, Some(user_id))
+        {
+            return Err(RepoError::Duplicate);
+        }
+        let Some(account) = tables.users.get_mut(&user_id) else {
+            return Ok(false);
+        };
+        let user = &mut account.user;
+        if let Some(first) = &changes.user_first_name {
+            user.user_first_name = first.clone();
+        }
+        if let Some(last) = &changes.user_last_name {
+            user.user_last_name = last.clone();
+        }
+        if let Some(email) = &changes.email {
+            user.email = email.clone();
+        }
+        if let Some(phone) = &changes.phone {
+            user.phone = phone.clone();
+        }
+        if let Some(is_active) = changes.is_active {
+            user.is_active = is_active;
+        }
+        if changes.email_changed {
+            user.email_verified_at = None;
+        }
+        user.updated_at = Utc::now().naive_utc();
+        Ok(true)
+    }
+
+    async fn set_password(&self, user_id: i64, password_hash: &str, vault_key: &WrappedVaultKey) -> Result<(), RepoError> {
+        let mut tables = self.tables();
+        let account = tables.user_mut(user_id)?;
+        account.user.password_hash_to_login = password_hash.to_string();
+        account.user.updated_at = Utc::now().naive_utc();
+        account.vault_key_salt = Some(vault_key.salt.clone());
+        account.vault_key_wrapped = Some(vault_key.wrapped.clone());
+        Ok(())
+    }
+
+    async fn replace_password_hash(&self, user_id: i64, expected: &str, new_hash: &str) -> Result<bool, RepoError> {
+        let mut tables = self.tables();
+        match tables.users.get_mut(&user_id) {
+            Some(account) if account.user.password_hash_to_login == expected => {
+                account.user.password_hash_to_login = new_hash.to_string();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn set_vault_key(&self, user_id: i64, vault_key: &WrappedVaultKey) -> Result<(), RepoError> {
+        let mut tables = self.tables();
+        let account = tables.user_mut(user_id)?;
+        account.vault_key_salt = Some(vault_key.salt.clone());
+        account.vault_key_wrapped = Some(vault_key.wrapped.clone());
+        Ok(())
+    }
+
+    async fn set_vault_escrow(&self, user_id: i64, escrow: Option<&str>) -> Result<(), RepoError> {
+        self.tables().user_mut(user_id)?.vault_key_escrow = escrow.map(str::to_string);
+        Ok(())
+    }
+
+    async fn touch_last_login(&self, user_id: i64) -> Result<bool, RepoError> {
+        let mut tables = self.tables();
+        match tables.users.get_mut(&user_id) {
+            Some(account) if account.user.is_active => {
+                account.user.last_login = Some(Utc::now().naive_utc());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete(&self, user_id: i64) -> Result<bool, RepoError> {
+        let mut tables = self.tables();
+        if tables.users.remove(&user_id).is_none() {
+            return Ok(false);
+        }
+        let owned: Vec<i64> = tables
+            .passwords
+            .values()
+            .filter(|p| p.user_id == user_id)
+            .map(|p| p.password_id)
+            .collect();
+        for password_id in owned {
+            tables.delete_password(password_id);
+        }
+        Ok(true)
+    }
+
+    async fn created_between_years(&self, from: i32, to: i32) -> Result<Vec<User>, RepoError> {
+        let mut users: Vec<User> = self
+            .tables()
+            .users
+            .values()
+            .map(|a| a.user.clone())
+            .filter(|u| (from..=to).contains(&u.created_at.year()))
+            .collect();
+        users.sort_by_key(|u| u.created_at);
+        Ok(users)
+    }
+}
+
+// ===================== PASSWORDS =====================
+#[async_trait]
+impl PasswordRepository for MemoryStore {
+    async fn create(&self, vault: &UnlockedVault, new: NewPassword<'_>) -> Result<Password, RepoError> {
+        let mut tables = self.tables();
+        if tables.domain_taken(new.user_id, new.domain, None) {
+            return Err(RepoError::Duplicate);
+        }
+
+        let password_id = next_id(&tables.passwords);
+        let encrypted = vault
+            .encrypt(new.password, &passwords_aad(password_id, new.user_id, new.domain))
+            .map_err(|e| RepoError::Encryption(e.to_string()))?;
+        let details = seal_details(vault, password_id, new.user_id, new.domain, new.details)?;
+
+        let category_id = match tables.categories.values().find(|c| c.category_name == new.category_name) {
+            Some(category) => category.category_id,
+            None => {
+                let category_id = next_id(&tables.categories);
+                tables.categories.insert(category_id, Category {
+                    category_id,
+                    category_name: new.category_name.to_string(),
+                });
+                category_id
+            }
+        };
+
+        let now = Utc::now().naive_utc();
+        let password = Password {
+            password_id,
+            user_id: new.user_id,
+            domain: new.domain.to_string(),
+            password_encrypted: encrypted,
+            created_at: now,
+            updated_at: now,
+            username_encrypted: details.username,
+            urls_encrypted: details.urls,
+            notes_encrypted: details.notes,
+            fields_encrypted: details.fields,
+        };
+        tables.passwords.insert(password_id, password.clone());
+        tables.password_category.insert((password_id, category_id));
+        Ok(password)
+    }
+
+    async fn list(&self, user_id: i64, filter: &PasswordFilter, page: &Page) -> Result<Paged<Password>, RepoError> {
+        let tables = self.tables();
+        let rows = tables
+            .passwords
+            .values()
+            .filter(|p| p.user_id == user_id)
+            .filter(|p| contains(&p.domain, &filter.domain))
+            .filter(|p| {
+                filter
+                    .category_id
+                    .is_none_or(|category_id| tables.password_category.contains(&(p.password_id, category_id)))
+            })
+            .filter(|p| filter.updated_after.is_none_or(|after| p.updated_at >= after))
+            .filter(|p| filter.updated_before.is_none_or(|before| p.updated_at < before))
+            .cloned()
+            .collect();
+        Ok(page.apply(rows))
+    }
+
+    async fn get(&self, user_id: i64, password_id: i64) -> Result<Option<Password>, RepoError> {
+        Ok(self.tables().owned_password(user_id, password_id).cloned())
+    }
+
+    async fn reveal(&self, vault: &UnlockedVault, user_id: i64, password_id: i64) -> Result<Option<RevealedPassword>, RepoError> {
+        let tables = self.tables();
+        let Some(password) = tables.owned_password(user_id, password_id) else {
+            return Ok(None);
+        };
+        let plain = vault
+            .decrypt(&password.password_encrypted, &passwords_aad(password_id, user_id, &password.domain))
+            .map_err(|e| RepoError::Decryption(e.to_string()))?;
+        Ok(Some(RevealedPassword {
+            password_id,
+            domain: password.domain.clone(),
+            password: plain,
+            details: open_details(vault, password)?,
+        }))
+    }
+
+    async fn update(&self, vault: &UnlockedVault, user_id: i64, password_id: i64, changes: &PasswordChanges<'_>) -> Result<(), RepoError> {
+        let mut tables = self.tables();
+        let current = tables.owned_password(user_id, password_id).ok_or(RepoError::NotFound)?;
+        let old_password = vault
+            .decrypt(&current.password_encrypted, &passwords_aad(password_id, user_id, &current.domain))
+            .map_err(|e| RepoError::Decryption(e.to_string()))?;
+        let old_details = open_details(vault, current)?;
+        let new_details = changes.apply(&old_details);
+
+        let new_domain = changes.domain.unwrap_or(&current.domain).to_string();
+        if tables.domain_taken(user_id, &new_domain, Some(password_id)) {
+            return Err(RepoError::Duplicate);
+        }
+        let mut old_values = replaced_values(&old_details, &new_details)?;
+        if let Some(old_domain) = changes.replaced_domain(&current.domain) {
+            old_values.insert(0, (FIELD_DOMAIN, old_domain.to_string()));
+        }
+        if changes.touches_password() {
+            old_values.insert(0, (FIELD_PASSWORD, old_password.clone()));
+        }
+        let history = old_values
+            .into_iter()
+            .enumerate()
+            .map(|(i, (field, old_value))| tables.new_history(vault, password_id, field, &old_value, i as i64))
+            .collect::<Result<Vec<_>, _>>()?;
+        let encrypted = vault
+            .encrypt(changes.password.unwrap_or(&old_password), &passwords_aad(password_id, user_id, &new_domain))
+            .map_err(|e| RepoError::Encryption(e.to_string()))?;
+        let details = seal_details(vault, password_id, user_id, &new_domain, &new_details)?;
+
+        for entry in history {
+            tables.history.insert(entry.history_id, entry);
+        }
+        let current = tables.passwords.get_mut(&password_id).ok_or(RepoError::NotFound)?;
+        current.domain = new_domain;
+        current.password_encrypted = encrypted;
+        current.username_encrypted = details.username;
+        current.urls_encrypted = details.urls;
+        current.notes_encrypted = details.notes;
+        current.fields_encrypted = details.fields;
+        current.updated_at = Utc::now().naive_utc();
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: i64, password_id: i64) -> Result<bool, RepoError> {
+        let mut tables = self.tables();
+        if tables.owned_password(user_id, password_id).is_none() {
+            return Ok(false);
+        }
+        Ok(tables.delete_password(password_id))
+    }
+
+    async fn users_with_at_least(&self, count: i64) -> Result<Vec<PasswordSummary>, RepoError> {
+        let tables = self.tables();
+        let mut counts: BTreeMap<i64, i64> = BTreeMap::new();
+        for password in tables.passwords.values() {
+            *counts.entry(password.user_id).or_default() += 1;
+        }
+        let mut summaries: Vec<PasswordSummary> = counts
+            .into_iter()
+            .filter(|(_, password_count)| *password_count >= count)
+            .filter_map(|(user_id, password_count)| {
+                tables.users.get(&user_id).map(|a| PasswordSummary {
+                    user_id,
+                    user_first_name: a.user.user_first_name.clone(),
+                    user_last_name: a.user.user_last_name.clone(),
+                    password_count,
+                })
+            })
+            .collect();
+        summaries.sort_by_key(|s| Reverse(s.password_count));
+        Ok(summaries)
+    }
+
+    async fn link_category(&self, user_id: i64, password_id: i64, category_id: i64) -> Result<(), RepoError> {
+        let mut tables = self.tables();
+        if tables.owned_password(user_id, password_id).is_none() {
+            return Err(RepoError::NotFound);
+        }
+        if !tables.categories.contains_key(&category_id) {
+            return Err(RepoError::InvalidReference);
+        }
+        if !tables.password_category.insert((password_id, category_id)) {
+            return Err(RepoError::Duplicate);
+        }
+        Ok(())
+    }
+
+    async fn unlink_category(&self, user_id: i64, password_id: i64, category_id: i64) -> Result<bool, RepoError> {
+        let mut tables = self.tables();
+        if tables.owned_password(user_id, password_id).is_none() {
+            return Ok(false);
+        }
+        Ok(tables.password_category.remove(&(password_id, category_id)))
+    }
+
+    async fn list_category_links(&self, user_id: i64, filter: &PasswordCategoryFilter, page: &Page) -> Result<Paged<PasswordCategory>, RepoError> {
+        let tables = self.tables();
+        let rows = tables
+            .password_category
+            .iter()
+            .filter(|(password_id, _)| tables.owned_password(user_id, *password_id).is_some())
+            .filter(|(password_id, _)| filter.password_id.is_none_or(|id| *password_id == id))
+            .filter(|(_, category_id)| filter.category_id.is_none_or(|id| *category_id == id))
+            .map(|(password_id, category_id)| PasswordCategory { password_id: *password_id, category_id: *category_id })
+            .collect();
+        Ok(page.apply(rows))
+    }
+}
+
+// ===================== PASSWORD HISTORY =====================
+#[async_trait]
+impl PasswordHistoryRepository for MemoryStore {
+    async fn list(&self, user_id: i64, filter: &HistoryFilter, page: &Page) -> Result<Paged<PasswordHistory>, RepoError> {
+        let tables = self.tables();
+        let rows = tables
+            .history
+            .values()
+            .filter(|h| tables.owned_password(user_id, h.password_id).is_some())
+            .filter(|h| filter.password_id.is_none_or(|password_id| h.password_id == password_id))
+            .filter(|h| filter.field.as_ref().is_none_or(|field| h.field == *field))
+            .filter(|h| filter.from.is_none_or(|from| h.changed_at >= from))
+            .filter(|h| filter.to.is_none_or(|to| h.changed_at < to))
+            .cloned()
+            .collect();
+        Ok(page.apply(rows))
+    }
+
+    async fn most_changed_domain(&self, user_id: i64) -> Result<Option<(String, i64)>, RepoError> {
+        let tables = self.tables();
+        let mut counts: BTreeMap<String, i64> = BTreeMap::new();
+        for entry in tables.history.values().filter(|h| h.field == FIELD_PASSWORD) {
+            if let Some(password) = tables.owned_password(user_id, entry.password_id) {
+                *counts.entry(password.domain.clone()).or_default() += 1;
+            }
+        }
+        Ok(counts.into_iter().max_by_key(|(_, count)| *count))
+    }
+}
+
+// ===================== CATEGORIES =====================
+#[async_trait]
+impl CategoryRepository for MemoryStore {
+    async fn create(&self, name: &str) -> Result<Category, RepoError> {
+        let mut tables = self.tables();
+        if tables.categories.values().any(|c| c.category_name == name) {
+            return Err(RepoError::Duplicate);
+        }
+        let category = Category {
+            category_id: next_id(&tables.categories),
+            category_name: name.to_string(),
+        };
+        tables.categories.insert(category.category_id, category.clone());
+        Ok(category)
+    }
+
+    async fn list(&self, filter: &CategoryFilter, page: &Page) -> Result<Paged<Category>, RepoError> {
+        let rows = self
+            .tables()
+            .categories
+            .values()
+            .filter(|c| contains(&c.category_name, &filter.name))
+            .cloned()
+            .collect();
+        Ok(page.apply(rows))
+    }
+
+    async fn get(&self, category_id: i64) -> Result<Option<Category>, RepoError> {
+        Ok(self.tables().categories.get(&category_id).cloned())
+    }
+
+    async fn rename(&self, category_id: i64, name: Option<&str>) -> Result<bool, RepoError> {
+        let mut tables = self.tables();
+        if let Some(name) = name
+            && tables.categories.values().any(|c| c.category_name == name && c.category_id != category_id)
+        {
+            return Err(RepoError::Duplicate);
+        }
+        let Some(category) = tables.categories.get_mut(&category_id) else {
+            return Ok(false);
+        };
+        if let Some(name) = name {
+            category.category_name = name.to_string();
+        }
+        Ok(true)
+    }
+
+    async fn delete(&self, category_id: i64) -> Result<bool, RepoError> {
+        let mut tables = self.tables();
+        if tables.categories.remove(&category_id).is_none() {
+            return Ok(false);
+        }
+        tables.password_category.retain(|(_, c)| *c != category_id);
+        Ok(true)
+    }
+
+    async fn search(&self, keyword: &str) -> Result<Vec<Category>, RepoError> {
+        // כמו ב-SQLite: מכיל, בלי הבדל בין אותיות גדולות וקטנות
+        let keyword = keyword.to_lowercase();
+        Ok(self
+            .tables()
+            .categories
+            .values()
+            .filter(|c| c.category_name.to_lowercase().contains(&keyword))
+            .cloned()
+            .collect())
+    }
+
+    async fn users_in(&self, name: &str) -> Result<Vec<UserShoppingInfo>, RepoError> {
+        let tables = self.tables();
+        let user_ids: BTreeSet<i64> = tables
+            .password_category
+            .iter()
+            .filter(|(_, category_id)| tables.categories.get(category_id).is_some_and(|c| c.category_name == name))
+            .filter_map(|(password_id, _)| tables.passwords.get(password_id).map(|p| p.user_id))
+            .collect();
+        Ok(user_ids
+            .into_iter()
+            .filter_map(|user_id| tables.users.get(&user_id))
+            .map(|a| UserShoppingInfo {
+                user_id: a.user.user_id,
+                user_first_name: a.user.user_first_name.clone(),
+                user_last_name: a.user.user_last_name.clone(),
+                category_name: name.to_string(),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+use std::sync::Arc;
+
+use actix_web::web;
+use sqlx::SqlitePool;
+
+pub mod users;
+pub mod passwords;
+pub mod password_history;
+pub mod categories;
+pub mod sqlite;
+// בלי DB: לבדיקות של הלוגיקה של ה-repositories
+#[cfg(test)]
+pub mod memory;
+
+use users::UserRepository;
+use passwords::PasswordRepository;
+use password_history::PasswordHistoryRepository;
+use categories::CategoryRepository;
+
+// ===================== ERRORS =====================
+#[derive(Debug)]
+pub enum RepoError {
+    NotFound,
+    /// שורה עם אותו ערך ייחודי כבר קיימת (מייל, דומיין של אותו משתמש, שם קטגוריה)
+    Duplicate,
+    /// הפניה לשורה שלא קיימת (foreign key), למשל קישור לקטגוריה שנמחקה
+    InvalidReference,
+    Encryption(String),
+    Decryption(String),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for RepoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepoError::NotFound => write!(f, "Not found"),
+            RepoError::Duplicate => write!(f, "Already exists"),
+            RepoError::InvalidReference => write!(f, "Referenced row does not exist"),
+            RepoError::Encryption(e) => write!(f, "Encryption error: {}", e),
+            RepoError::Decryption(e) => write!(f, "Decryption error: {}", e),
+            RepoError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RepoError {}
+
+impl From<sqlx::Error> for RepoError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => RepoError::Duplicate,
+            sqlx::Error::Database(ref db) if db.is_foreign_key_violation() => RepoError::InvalidReference,
+            e => RepoError::Database(e),
+        }
+    }
+}
+
+// ===================== REGISTRATION =====================
+/// ה-repositories שה-handlers מקבלים דרך web::Data, כולם מעל אותו מקור נתונים
+#[derive(Clone)]
+pub struct Repositories {
+    users: web::Data<dyn UserRepository>,
+    passwords: web::Data<dyn PasswordRepository>,
+    history: web::Data<dyn PasswordHistoryRepository>,
+    categories: web::Data<dyn CategoryRepository>,
+}
+
+impl Repositories {
+    pub fn sqlite(pool: SqlitePool) -> Self {
+        Self::from_store(Arc::new(sqlite::SqliteStore::new(pool)))
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self::from_store(Arc::new(memory::MemoryStore::default()))
+    }
+
+    fn from_store<S>(store: Arc<S>) -> Self
+    where
+        S: UserRepository + PasswordRepository + PasswordHistoryRepository + CategoryRepository + 'static,
+    {
+        let users: Arc<dyn UserRepository> = store.clone();
+        let passwords: Arc<dyn PasswordRepository> = store.clone();
+        let history: Arc<dyn PasswordHistoryRepository> = store.clone();
+        let categories: Arc<dyn CategoryRepository> = store;
+        Repositories {
+            users: web::Data::from(users),
+            passwords: web::Data::from(passwords),
+            history: web::Data::from(history),
+            categories: web::Data::from(categories),
+        }
+    }
+
+    pub fn register(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(self.users.clone())
+            .app_data(self.passwords.clone())
+            .app_data(self.history.clone())
+            .app_data(self.categories.clone());
+    }
+}
+
+// ===================== TESTS =====================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::password_category::PasswordCategoryFilter;
+    use crate::models::password_history::{HistoryFilter, FIELD_DOMAIN, FIELD_NOTES, FIELD_PASSWORD, FIELD_USERNAME};
+    use crate::models::passwords::EntryDetails;
+    use crate::repositories::passwords::{NewPassword, PasswordChanges};
+    use crate::utils::db::new_user;
+    use crate::utils::encryption::history_field_aad;
+    use crate::utils::keys::load_test_keys;
+    use crate::utils::pagination::{Page, Sort};
+    use crate::utils::vault::{UnlockedVault, VaultKey};
+
+    /// כל בדיקה רצה פעמיים: מול MemoryStore ומול SqliteStore על DB זמני עם כל המיגרציות
+    macro_rules! on_both_stores {
+        ($($name:ident),* $(,)?) => {$(
+            mod $name {
+                #[actix_web::test]
+                async fn memory() {
+                    super::$name(&super::Repositories::in_memory()).await;
+                }
+
+                #[actix_web::test]
+                async fn sqlite() {
+                    let pool = crate::utils::db::test_pool(concat!("repositories-", stringify!($name))).await;
+                    super::$name(&super::Repositories::sqlite(pool)).await;
+                }
+            }
+        )*};
+    }
+
+    on_both_stores!(
+        only_password_changes_count_towards_most_changed_domain,
+        password_update_keeps_old_value_and_reveals_new_one,
+        rename_to_taken_domain_changes_nothing,
+        passwords_of_other_users_look_missing,
+        deleting_user_deletes_their_passwords_and_history,
+        emails_are_unique_regardless_of_case,
+        category_links_belong_to_the_password_owner,
+    );
+
+    fn page(key: &'static str) -> Page {
+        Page { limit: 50, sort: Sort { key, descending: false }, after: None }
+    }
+
+    async fn user(repos: &Repositories, email: &str) -> UnlockedVault {
+        // מפתח השרת עוטף את מפתח הכספת של משתמש חדש
+        load_test_keys();
+        let user = repos.users.create(new_user(email)).await.unwrap();
+        UnlockedVault { user_id: user.user_id, key: VaultKey::generate() }
+    }
+
+    async fn entry(repos: &Repositories, vault: &UnlockedVault, domain: &str, details: &EntryDetails) -> i64 {
+        repos
+            .passwords
+            .create(vault, NewPassword { user_id: vault.user_id, domain, password: "p1", details, category_name: "web" })
+            .await
+            .unwrap()
+            .password_id
+    }
+
+    /// (field, הערך הקודם בטקסט גלוי) לפי הסדר שנשמרו
+    async fn history(repos: &Repositories, vault: &UnlockedVault, password_id: i64) -> Vec<(String, String)> {
+        let filter = HistoryFilter { password_id: Some(password_id), ..Default::default() };
+        repos
+            .history
+            .list(vault.user_id, &filter, &page("history_id"))
+            .await
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|h| {
+                let plain = vault
+                    .decrypt(&h.old_password_encrypted, &history_field_aad(h.history_id, h.password_id, &h.field))
+                    .unwrap();
+                (h.field, plain)
+            })
+            .collect()
+    }
+
+    async fn only_password_changes_count_towards_most_changed_domain(repos: &Repositories) {
+        let vault = user(repos, "a@x.io").await;
+        let id = entry(repos, &vault, "a.com", &EntryDetails { notes: Some("n1".to_string()), ..Default::default() }).await;
+        let other = entry(repos, &vault, "c.com", &EntryDetails::default()).await;
+
+        let rename = PasswordChanges { domain: Some("b.com"), ..Default::default() };
+        repos.passwords.update(&vault, vault.user_id, id, &rename).await.unwrap();
+        let notes = PasswordChanges { notes: Some("n2"), ..Default::default() };
+        repos.passwords.update(&vault, vault.user_id, id, &notes).await.unwrap();
+        for password in ["p2", "p3"] {
+            let change = PasswordChanges { password: Some(password), ..Default::default() };
+            repos.passwords.update(&vault, vault.user_id, other, &change).await.unwrap();
+        }
+
+        assert_eq!(
+            history(repos, &vault, id).await,
+            [(FIELD_DOMAIN.to_string(), "a.com".to_string()), (FIELD_NOTES.to_string(), "n1".to_string())]
+        );
+        assert_eq!(
+            repos.history.most_changed_domain(vault.user_id).await.unwrap(),
+            Some(("c.com".to_string(), 2))
+        );
+    }
+
+    async fn password_update_keeps_old_value_and_reveals_new_one(repos: &Repositories) {
+        let vault = user(repos, "a@x.io").await;
+        let details = EntryDetails { username: Some("me".to_string()), ..Default::default() };
+        let id = entry(repos, &vault, "a.com", &details).await;
+
+        let change = PasswordChanges { password: Some("p2"), username: Some(""), ..Default::default() };
+        repos.passwords.update(&vault, vault.user_id, id, &change).await.unwrap();
+
+        let revealed = repos.passwords.reveal(&vault, vault.user_id, id).await.unwrap().unwrap();
+        assert_eq!(revealed.password, "p2");
+        assert_eq!(revealed.details.username, None);
+        assert_eq!(
+            history(repos, &vault, id).await,
+            [(FIELD_PASSWORD.to_string(), "p1".to_string()), (FIELD_USERNAME.to_string(), "me".to_string())]
+        );
+    }
+
+    async fn rename_to_taken_domain_changes_nothing(repos: &Repositories) {
+        let vault = user(repos, "a@x.io").await;
+        let id = entry(repos, &vault, "a.com", &EntryDetails::default()).await;
+        entry(repos, &vault, "b.com", &EntryDetails::default()).await;
+
+        let change = PasswordChanges { domain: Some("b.com"), password: Some("p2"), ..Default::default() };
+        assert!(matches!(
+            repos.passwords.update(&vault, vault.user_id, id, &change).await,
+            Err(RepoError::Duplicate)
+        ));
+        let revealed = repos.passwords.reveal(&vault, vault.user_id, id).await.unwrap().unwrap();
+        assert_eq!((revealed.domain.as_str(), revealed.password.as_str()), ("a.com", "p1"));
+        assert!(history(repos, &vault, id).await.is_empty());
+
+        // אותו דומיין אצל משתמש אחר מותר
+        let other = user(repos, "b@x.io").await;
+        entry(repos, &other, "a.com", &EntryDetails::default()).await;
+    }
+
+    async fn passwords_of_other_users_look_missing(repos: &Repositories) {
+        let owner = user(repos, "a@x.io").await;
+        let stranger = user(repos, "b@x.io").await;
+        let id = entry(repos, &owner, "a.com", &EntryDetails::default()).await;
+
+        assert!(repos.passwords.get(stranger.user_id, id).await.unwrap().is_none());
+        assert!(repos.passwords.reveal(&stranger, stranger.user_id, id).await.unwrap().is_none());
+        let change = PasswordChanges { password: Some("p2"), ..Default::default() };
+        assert!(matches!(
+            repos.passwords.update(&stranger, stranger.user_id, id, &change).await,
+            Err(RepoError::NotFound)
+        ));
+        assert!(!repos.passwords.delete(stranger.user_id, id).await.unwrap());
+        assert!(repos.passwords.get(owner.user_id, id).await.unwrap().is_some());
+    }
+
+    async fn deleting_user_deletes_their_passwords_and_history(repos: &Repositories) {
+        let vault = user(repos, "a@x.io").await;
+        let id = entry(repos, &vault, "a.com", &EntryDetails::default()).await;
+        let change = PasswordChanges { password: Some("p2"), ..Default::default() };
+        repos.passwords.update(&vault, vault.user_id, id, &change).await.unwrap();
+
+        assert!(repos.users.delete(vault.user_id).await.unwrap());
+        assert!(repos.passwords.get(vault.user_id, id).await.unwrap().is_none());
+        assert!(history(repos, &vault, id).await.is_empty());
+    }
+
+    async fn emails_are_unique_regardless_of_case(repos: &Repositories) {
+        let vault = user(repos, "a@x.io").await;
+
+        assert!(repos.users.email_in_use("A@X.io", None).await.unwrap());
+        assert!(!repos.users.email_in_use("A@X.io", Some(vault.user_id)).await.unwrap());
+        assert!(matches!(repos.users.create(new_user("A@x.IO")).await, Err(RepoError::Duplicate)));
+    }
+
+    async fn category_links_belong_to_the_password_owner(repos: &Repositories) {
+        let owner = user(repos, "a@x.io").await;
+        let stranger = user(repos, "b@x.io").await;
+        let id = entry(repos, &owner, "a.com", &EntryDetails::default()).await;
+        let com = repos.categories.create(".com").await.unwrap().category_id;
+
+        assert!(matches!(repos.passwords.link_category(stranger.user_id, id, com).await, Err(RepoError::NotFound)));
+        assert!(matches!(repos.passwords.link_category(owner.user_id, id, com + 100).await, Err(RepoError::InvalidReference)));
+        repos.passwords.link_category(owner.user_id, id, com).await.unwrap();
+        assert!(matches!(repos.passwords.link_category(owner.user_id, id, com).await, Err(RepoError::Duplicate)));
+
+        let filter = PasswordCategoryFilter { category_id: Some(com), ..Default::default() };
+        let by_password = page("password_id");
+        let links = |user_id| repos.passwords.list_category_links(user_id, &filter, &by_password);
+        assert_eq!(links(owner.user_id).await.unwrap().items.len(), 1);
+        assert!(links(stranger.user_id).await.unwrap().items.is_empty());
+        let users: Vec<i64> = repos.categories.users_in(".com").await.unwrap().iter().map(|u| u.user_id).collect();
+        assert_eq!(users, [owner.user_id]);
+
+        assert!(!repos.passwords.unlink_category(stranger.user_id, id, com).await.unwrap());
+        assert!(repos.passwords.unlink_category(owner.user_id, id, com).await.unwrap());
+        assert!(repos.categories.users_in(".com").await.unwrap().is_empty());
+    }
+}
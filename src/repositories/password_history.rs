@@ -0,0 +1,12 @@
+use async_trait::async_trait;
+use crate::models::password_history::{HistoryFilter, PasswordHistory};
+use crate::repositories::RepoError;
+use crate::utils::pagination::{Page, Paged};
+
+#[async_trait]
+pub trait PasswordHistoryRepository: Send + Sync {
+    /// ההיסטוריה של הסיסמאות של המשתמש
+    async fn list(&self, user_id: i64, filter: &HistoryFilter, page: &Page) -> Result<Paged<PasswordHistory>, RepoError>;
+    /// הדומיין שהסיסמה שלו הוחלפה הכי הרבה פעמים, ומספר ההחלפות
+    async fn most_changed_domain(&self, user_id: i64) -> Result<Option<(String, i64)>, RepoError>;
+}
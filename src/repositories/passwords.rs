@@ -0,0 +1,153 @@
+use async_trait::async_trait;
+use crate::models::password_history::{FIELD_CUSTOM, FIELD_NOTES, FIELD_URLS, FIELD_USERNAME};
+use crate::models::password_category::{PasswordCategory, PasswordCategoryFilter};
+use crate::models::passwords::{CustomField, EntryDetails, Password, PasswordFilter, PasswordSummary, RevealedPassword};
+use crate::repositories::RepoError;
+use crate::utils::encryption::entry_field_aad;
+use crate::utils::pagination::{Page, Paged};
+use crate::utils::vault::UnlockedVault;
+
+/// סיסמה חדשה בטקסט גלוי. ההצפנה נעשית ב-repository, כי ה-AAD כולל את ה-password_id
+pub struct NewPassword<'a> {
+    pub user_id: i64,
+    pub domain: &'a str,
+    pub password: &'a str,
+    pub details: &'a EntryDetails,
+    pub category_name: &'a str,
+}
+
+/// שדה None לא משתנה. username או notes "" נמחקים
+#[derive(Default)]
+pub struct PasswordChanges<'a> {
+    pub domain: Option<&'a str>,
+    pub password: Option<&'a str>,
+    pub username: Option<&'a str>,
+    pub urls: Option<&'a [String]>,
+    pub notes: Option<&'a str>,
+    pub fields: Option<&'a [CustomField]>,
+}
+
+impl PasswordChanges<'_> {
+    /// הסיסמה הקודמת נשמרת בהיסטוריה רק כשהבקשה נוגעת בסיסמה עצמה
+    pub fn touches_password(&self) -> bool {
+        self.password.is_some()
+    }
+
+    /// הדומיין הקודם, אם הבקשה משנה אותו (נשמר בהיסטוריה עם field = domain)
+    pub fn replaced_domain<'d>(&self, old: &'d str) -> Option<&'d str> {
+        self.domain.filter(|domain| *domain != old).map(|_| old)
+    }
+
+    pub fn apply(&self, old: &EntryDetails) -> EntryDetails {
+        let text = |change: Option<&str>, old: &Option<String>| match change {
+            None => old.clone(),
+            Some("") => None,
+            Some(value) => Some(value.to_string()),
+        };
+        EntryDetails {
+            username: text(self.username, &old.username),
+            urls: self.urls.map_or_else(|| old.urls.clone(), <[String]>::to_vec),
+            notes: text(self.notes, &old.notes),
+            fields: self.fields.map_or_else(|| old.fields.clone(), <[CustomField]>::to_vec),
+        }
+    }
+}
+
+/// כל הפעולות מוגבלות לסיסמאות של user_id. סיסמה של משתמש אחר נראית כאילו היא לא קיימת
+#[async_trait]
+pub trait PasswordRepository: Send + Sync {
+    /// מצפין בכספת, משייך לקטגוריה (ויוצר אותה אם חסרה). Duplicate אם לדומיין כבר יש סיסמה
+    async fn create(&self, vault: &UnlockedVault, new: NewPassword<'_>) -> Result<Password, RepoError>;
+    /// filter.user_id לא נבדק כאן: user_id הוא הבעלים שהרשימה מוגבלת אליו
+    async fn list(&self, user_id: i64, filter: &PasswordFilter, page: &Page) -> Result<Paged<Password>, RepoError>;
+    async fn get(&self, user_id: i64, password_id: i64) -> Result<Option<Password>, RepoError>;
+    /// הסיסמה וכל הפרטים הנוספים, מפוענחים
+    async fn reveal(&self, vault: &UnlockedVault, user_id: i64, password_id: i64) -> Result<Option<RevealedPassword>, RepoError>;
+    /// שומר בהיסטוריה את הערכים הקודמים של מה שהשתנה ומעדכן, הכל או כלום. NotFound אם הסיסמה לא קיימת
+    async fn update(&self, vault: &UnlockedVault, user_id: i64, password_id: i64, changes: &PasswordChanges<'_>) -> Result<(), RepoError>;
+    /// ההיסטוריה והקישורים לקטגוריות נמחקים איתה
+    async fn delete(&self, user_id: i64, password_id: i64) -> Result<bool, RepoError>;
+    /// משתמשים עם לפחות count סיסמאות, מהכי הרבה
+    async fn users_with_at_least(&self, count: i64) -> Result<Vec<PasswordSummary>, RepoError>;
+    /// NotFound אם הסיסמה לא של user_id, InvalidReference אם הקטגוריה לא קיימת, Duplicate אם כבר מקושרות
+    async fn link_category(&self, user_id: i64, password_id: i64, category_id: i64) -> Result<(), RepoError>;
+    /// false אם אין קישור כזה לסיסמה של user_id
+    async fn unlink_category(&self, user_id: i64, password_id: i64, category_id: i64) -> Result<bool, RepoError>;
+    async fn list_category_links(&self, user_id: i64, filter: &PasswordCategoryFilter, page: &Page) -> Result<Paged<PasswordCategory>, RepoError>;
+}
+
+// ===================== ENTRY DETAILS =====================
+/// הפרטים כפי שהם נשמרים בשורה: כל אחד מוצפן בנפרד, None כשאין ערך
+pub struct SealedDetails {
+    pub username: Option<String>,
+    pub urls: Option<String>,
+    pub notes: Option<String>,
+    pub fields: Option<String>,
+}
+
+/// כל פרט בטקסט גלוי לפי שם השדה שלו (urls ו-fields כ-JSON). רשימה ריקה = אין ערך
+fn plain_values(details: &EntryDetails) -> Result<[(&'static str, Option<String>); 4], RepoError> {
+    fn json<T: serde::Serialize>(items: &[T]) -> Result<Option<String>, RepoError> {
+        if items.is_empty() {
+            return Ok(None);
+        }
+        serde_json::to_string(items).map(Some).map_err(|e| RepoError::Encryption(e.to_string()))
+    }
+    Ok([
+        (FIELD_USERNAME, details.username.clone()),
+        (FIELD_URLS, json(&details.urls)?),
+        (FIELD_NOTES, details.notes.clone()),
+        (FIELD_CUSTOM, json(&details.fields)?),
+    ])
+}
+
+/// מצפין כל פרט בכספת, קשור לשורה (כולל הדומיין) ולשם השדה
+pub fn seal_details(
+    vault: &UnlockedVault,
+    password_id: i64,
+    user_id: i64,
+    domain: &str,
+    details: &EntryDetails,
+) -> Result<SealedDetails, RepoError> {
+    let [username, urls, notes, fields] = plain_values(details)?.map(|(field, plain)| {
+        plain
+            .map(|plain| vault.encrypt(&plain, &entry_field_aad(password_id, user_id, domain, field)))
+            .transpose()
+            .map_err(|e| RepoError::Encryption(e.to_string()))
+    });
+    Ok(SealedDetails { username: username?, urls: urls?, notes: notes?, fields: fields? })
+}
+
+pub fn open_details(vault: &UnlockedVault, password: &Password) -> Result<EntryDetails, RepoError> {
+    let open = |field: &str, encrypted: &Option<String>| -> Result<Option<String>, RepoError> {
+        let aad = entry_field_aad(password.password_id, password.user_id, &password.domain, field);
+        encrypted
+            .as_ref()
+            .map(|encrypted| vault.decrypt(encrypted, &aad))
+            .transpose()
+            .map_err(|e| RepoError::Decryption(e.to_string()))
+    };
+    let parse_error = |e: serde_json::Error| RepoError::Decryption(e.to_string());
+    Ok(EntryDetails {
+        username: open(FIELD_USERNAME, &password.username_encrypted)?,
+        urls: match open(FIELD_URLS, &password.urls_encrypted)? {
+            Some(json) => serde_json::from_str(&json).map_err(parse_error)?,
+            None => Vec::new(),
+        },
+        notes: open(FIELD_NOTES, &password.notes_encrypted)?,
+        fields: match open(FIELD_CUSTOM, &password.fields_encrypted)? {
+            Some(json) => serde_json::from_str(&json).map_err(parse_error)?,
+            None => Vec::new(),
+        },
+    })
+}
+
+/// הערכים הקודמים של הפרטים שהשתנו, לשמירה בהיסטוריה. לפרט שלא היה לו ערך אין מה לשמור
+pub fn replaced_values(old: &EntryDetails, new: &EntryDetails) -> Result<Vec<(&'static str, String)>, RepoError> {
+    let new = plain_values(new)?;
+    Ok(plain_values(old)?
+        .into_iter()
+        .zip(new)
+        .filter_map(|((field, old), (_, new))| old.filter(|old| Some(old) != new.as_ref()).map(|old| (field, old)))
+        .collect())
+}
@@ -0,0 +1,871 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool};
+use crate::models::categories::{Category, CategoryFilter};
+use crate::models::password_category::{PasswordCategory, PasswordCategoryFilter, UserShoppingInfo};
+use crate::models::password_history::{HistoryFilter, PasswordHistory, FIELD_DOMAIN, FIELD_PASSWORD};
+use crate::models::passwords::{Password, PasswordFilter, PasswordSummary, RevealedPassword};
+use crate::models::users::{User, UserFilter};
+use crate::repositories::RepoError;
+use crate::repositories::categories::CategoryRepository;
+use crate::repositories::password_history::PasswordHistoryRepository;
+use crate::repositories::passwords::{open_details, replaced_values, seal_details, NewPassword, PasswordChanges, PasswordRepository};
+use crate::repositories::users::{NewUser, UserAccount, UserChanges, UserRepository};
+use crate::utils::db::in_transaction;
+use crate::utils::encryption::{history_field_aad, passwords_aad};
+use crate::utils::pagination::{fetch_page, Page, Paged};
+use crate::utils::search::replace_username_tokens;
+use crate::utils::vault::{UnlockedVault, WrappedVaultKey};
+
+/// כל ה-repositories מעל ה-pool של SQLite
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        SqliteStore { pool }
+    }
+}
+
+fn encryption_error(e: Box<dyn std::error::Error>) -> RepoError {
+    RepoError::Encryption(e.to_string())
+}
+
+fn decryption_error(e: Box<dyn std::error::Error>) -> RepoError {
+    RepoError::Decryption(e.to_string())
+}
+
+/// " AND instr(lower(column), lower(?)) > 0": מכיל, בלי הבדל רישיות ובלי תווים מיוחדים של LIKE
+fn push_contains(query: &mut QueryBuilder<'_, Sqlite>, column: &str, value: &str) {
+    query.push(format!(" AND instr(lower({}), lower(", column));
+    query.push_bind(value.to_string());
+    query.push(")) > 0");
+}
+
+// ===================== USERS =====================
+/// המפתח העטוף קשור ל-user_id, לכן הוא נכתב רק אחרי ההכנסה. קורה בתוך הטרנזקציה של create
+async fn insert_user(conn: &mut SqliteConnection, user: NewUser) -> Result<User, RepoError> {
+    let now = Utc::now().naive_utc();
+    let user_id = sqlx::query(
+        "INSERT INTO users (user_first_name, user_last_name, email, phone, password_hash_to_login, created_at, updated_at, last_login, is_active)
+         VALUES (?, ?, ?, ?, ?, ?, ?, NULL, 1)"
+    )
+    .bind(&user.user_first_name)
+    .bind(&user.user_last_name)
+    .bind(&user.email)
+    .bind(&user.phone)
+    .bind(&user.password_hash)
+    .bind(now)
+    .bind(now)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    let vault_key = user.vault_key.wrap(user_id).map_err(encryption_error)?;
+    sqlx::query("UPDATE users SET vault_key_salt = ?, vault_key_wrapped = ? WHERE user_id = ?")
+        .bind(&vault_key.salt)
+        .bind(&vault_key.wrapped)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let role = sqlx::query(
+        "INSERT INTO user_roles (user_id, role_id, assigned_at)
+         SELECT ?, role_id, ? FROM roles WHERE role_name = ?"
+    )
+    .bind(user_id)
+    .bind(now)
+    .bind(user.role)
+    .execute(&mut *conn)
+    .await?;
+    if role.rows_affected() == 0 {
+        return Err(RepoError::NotFound);
+    }
+
+    sqlx::query(
+        "INSERT INTO email_verification_tokens (user_id, email, token_hash, created_at, expires_at)
+         VALUES (?, ?, ?, ?, ?)"
+    )
+    .bind(user_id)
+    .bind(&user.email)
+    .bind(&user.verification.token_hash)
+    .bind(now)
+    .bind(user.verification.expires_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(User {
+        user_id,
+        user_first_name: user.user_first_name,
+        user_last_name: user.user_last_name,
+        email: user.email,
+        phone: user.phone,
+        password_hash_to_login: user.password_hash,
+        created_at: now,
+        updated_at: now,
+        last_login: None,
+        is_active: true,
+        email_verified_at: None,
+    })
+}
+
+#[async_trait]
+impl UserRepository for SqliteStore {
+    async fn create(&self, user: NewUser) -> Result<User, RepoError> {
+        in_transaction(&self.pool, async |conn| insert_user(conn, user).await).await
+    }
+
+    async fn list(&self, filter: &UserFilter, page: &Page) -> Result<Paged<User>, RepoError> {
+        let column = match page.sort.key {
+            "email" => "email",
+            "user_last_name" => "user_last_name",
+            "created_at" => "created_at",
+            _ => "user_id",
+        };
+        let push_filters = |query: &mut QueryBuilder<'_, Sqlite>| {
+            if let Some(email) = &filter.email {
+                push_contains(query, "email", email);
+            }
+            if let Some(is_active) = filter.is_active {
+                query.push(" AND is_active = ").push_bind(is_active);
+            }
+            if let Some(after) = filter.created_after {
+                query.push(" AND created_at >= ").push_bind(after);
+            }
+            if let Some(before) = filter.created_before {
+                query.push(" AND created_at < ").push_bind(before);
+            }
+        };
+        Ok(fetch_page(&self.pool, "SELECT *", "FROM users", push_filters, page, column, "user_id").await?)
+    }
+
+    async fn get(&self, user_id: i64) -> Result<Option<User>, RepoError> {
+        Ok(sqlx::query_as("SELECT * FROM users WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn account(&self, user_id: i64) -> Result<Option<UserAccount>, RepoError> {
+        Ok(sqlx::query_as("SELECT * FROM users WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn find_account_by_email(&self, email: &str) -> Result<Option<UserAccount>, RepoError> {
+        Ok(sqlx::query_as("SELECT * FROM users WHERE lower(email) = lower(?)")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn email_in_use(&self, email: &str, except_user_id: Option<i64>) -> Result<bool, RepoError> {
+        let row = sqlx::query("SELECT 1 FROM users WHERE lower(email) = lower(?) AND user_id != ?")
+            .bind(email)
+            .bind(except_user_id.unwrap_or(0))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn update(&self, user_id: i64, changes: &UserChanges) -> Result<bool, RepoError> {
+        let result = sqlx::query(
+            "UPDATE users SET
+                user_first_name = COALESCE(?, user_first_name),
+                user_last_name = COALESCE(?, user_last_name),
+                email = COALESCE(?, email),
+                phone = COALESCE(?, phone),
+                is_active = COALESCE(?, is_active),
+                email_verified_at = CASE WHEN ? THEN NULL ELSE email_verified_at END,
+                updated_at = ?
+            WHERE user_id = ?"
+        )
+        .bind(&changes.user_first_name)
+        .bind(&changes.user_last_name)
+        .bind(&changes.email)
+        .bind(&changes.phone)
+        .bind(changes.is_active)
+        .bind(changes.email_changed)
+        .bind(Utc::now().naive_utc())
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_password(&self, user_id: i64, password_hash: &str, vault_key: &WrappedVaultKey) -> Result<(), RepoError> {
+        sqlx::query(
+            "UPDATE users SET password_hash_to_login = ?, vault_key_salt = ?, vault_key_wrapped = ?, updated_at = ?
+             WHERE user_id = ?"
+        )
+        .bind(password_hash)
+        .bind(&vault_key.salt)
+        .bind(&vault_key.wrapped)
+        .bind(Utc::now().naive_utc())
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn replace_password_hash(&self, user_id: i64, expected: &str, new_hash: &str) -> Result<bool, RepoError> {
+        let result = sqlx::query("UPDATE users SET password_hash_to_login = ? WHERE user_id = ? AND password_hash_to_login = ?")
+            .bind(new_hash)
+            .bind(user_id)
+            .bind(expected)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_vault_key(&self, user_id: i64, vault_key: &WrappedVaultKey) -> Result<(), RepoError> {
+        sqlx::query("UPDATE users SET vault_key_salt = ?, vault_key_wrapped = ? WHERE user_id = ?")
+            .bind(&vault_key.salt)
+            .bind(&vault_key.wrapped)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_vault_escrow(&self, user_id: i64, escrow: Option<&str>) -> Result<(), RepoError> {
+        sqlx::query("UPDATE users SET vault_key_escrow = ? WHERE user_id = ?")
+            .bind(escrow)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn touch_last_login(&self, user_id: i64) -> Result<bool, RepoError> {
+        let result = sqlx::query("UPDATE users SET last_login = ? WHERE user_id = ? AND is_active = 1")
+            .bind(Utc::now().naive_utc())
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete(&self, user_id: i64) -> Result<bool, RepoError> {
+        // ON DELETE CASCADE מוחק את הסיסמאות, ההיסטוריה והתפקידים
+        let result = sqlx::query("DELETE FROM users WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn created_between_years(&self, from: i32, to: i32) -> Result<Vec<User>, RepoError> {
+        Ok(sqlx::query_as(
+            "SELECT * FROM users
+             WHERE strftime('%Y', created_at) BETWEEN ? AND ?
+             ORDER BY created_at ASC"
+        )
+        .bind(from.to_string())
+        .bind(to.to_string())
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
+
+// ===================== PASSWORDS =====================
+/// מוסיף שורת היסטוריה (הערך הקודם של field) בתוך הטרנזקציה של מי שקרא לה.
+/// ה-AAD כולל את ה-history_id, לכן ההצפנה נעשית רק אחרי ההכנסה
+async fn insert_history(
+    conn: &mut SqliteConnection,
+    vault: &UnlockedVault,
+    password_id: i64,
+    field: &str,
+    old_password: &str,
+) -> Result<PasswordHistory, RepoError> {
+    let now = Utc::now().naive_utc();
+    let history_id: i64 = sqlx::query(
+        "INSERT INTO password_history (password_id, field, old_password_encrypted, changed_at)
+         VALUES (?, ?, '', ?)
+         RETURNING history_id"
+    )
+    .bind(password_id)
+    .bind(field)
+    .bind(now)
+    .fetch_one(&mut *conn)
+    .await?
+    .get("history_id");
+
+    let old_password_encrypted = vault
+        .encrypt(old_password, &history_field_aad(history_id, password_id, field))
+        .map_err(encryption_error)?;
+    sqlx::query("UPDATE password_history SET old_password_encrypted = ? WHERE history_id = ?")
+        .bind(&old_password_encrypted)
+        .bind(history_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(PasswordHistory {
+        history_id,
+        password_id,
+        field: field.to_string(),
+        old_password_encrypted,
+        changed_at: now,
+    })
+}
+
+#[async_trait]
+impl PasswordRepository for SqliteStore {
+    async fn create(&self, vault: &UnlockedVault, new: NewPassword<'_>) -> Result<Password, RepoError> {
+        let now = Utc::now().naive_utc();
+
+        // בדיקת הכפילות, ההכנסה, הקטגוריה והקישור - הכל או כלום
+        in_transaction(&self.pool, async |conn| {
+            // 🔹 בדיקה אם כבר קיימת סיסמה לאותו user ולדומיין
+            let existing: Option<(i64,)> = sqlx::query_as("SELECT password_id FROM passwords WHERE user_id = ? AND domain = ?")
+                .bind(new.user_id)
+                .bind(new.domain)
+                .fetch_optional(&mut *conn)
+                .await?;
+            if existing.is_some() {
+                return Err(RepoError::Duplicate);
+            }
+
+            // 🔹 הכנסה לטבלת passwords. ה-AAD כולל את ה-password_id, לכן מצפינים רק אחרי שיש מזהה
+            let password_id = sqlx::query(
+                "INSERT INTO passwords (user_id, domain, password_encrypted, created_at, updated_at)
+                 VALUES (?, ?, '', ?, ?)"
+            )
+            .bind(new.user_id)
+            .bind(new.domain)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+
+            // 🔹 הצפנת הסיסמה במפתח הכספת של המשתמש, קשורה לשורה
+            let aad = passwords_aad(password_id, new.user_id, new.domain);
+            let encrypted = vault.encrypt(new.password, &aad).map_err(encryption_error)?;
+            let details = seal_details(vault, password_id, new.user_id, new.domain, new.details)?;
+
+            sqlx::query(
+                "UPDATE passwords SET
+                    password_encrypted = ?,
+                    username_encrypted = ?,
+                    urls_encrypted = ?,
+                    notes_encrypted = ?,
+                    fields_encrypted = ?
+                 WHERE password_id = ?"
+            )
+            .bind(&encrypted)
+            .bind(&details.username)
+            .bind(&details.urls)
+            .bind(&details.notes)
+            .bind(&details.fields)
+            .bind(password_id)
+            .execute(&mut *conn)
+            .await?;
+            replace_username_tokens(conn, &vault.key, password_id, new.details.username.as_deref()).await?;
+
+            // 🔹 בדיקה או יצירה של קטגוריה תואמת
+            let category_id: i64 = match sqlx::query("SELECT category_id FROM categories WHERE category_name = ?")
+                .bind(new.category_name)
+                .fetch_optional(&mut *conn)
+                .await?
+            {
+                Some(row) => row.get("category_id"),
+                None => sqlx::query("INSERT INTO categories (category_name) VALUES (?)")
+                    .bind(new.category_name)
+                    .execute(&mut *conn)
+                    .await?
+                    .last_insert_rowid(),
+            };
+
+            // 🔹 קישור בין הסיסמה לקטגוריה
+            sqlx::query("INSERT OR IGNORE INTO password_category (password_id, category_id) VALUES (?, ?)")
+                .bind(password_id)
+                .bind(category_id)
+                .execute(&mut *conn)
+                .await?;
+
+            Ok(Password {
+                password_id,
+                user_id: new.user_id,
+                domain: new.domain.to_string(),
+                password_encrypted: encrypted,
+                created_at: now,
+                updated_at: now,
+                username_encrypted: details.username,
+                urls_encrypted: details.urls,
+                notes_encrypted: details.notes,
+                fields_encrypted: details.fields,
+            })
+        })
+        .await
+    }
+
+    async fn list(&self, user_id: i64, filter: &PasswordFilter, page: &Page) -> Result<Paged<Password>, RepoError> {
+        let column = match page.sort.key {
+            "domain" => "p.domain",
+            "created_at" => "p.created_at",
+            "updated_at" => "p.updated_at",
+            _ => "p.password_id",
+        };
+        let push_filters = |query: &mut QueryBuilder<'_, Sqlite>| {
+            query.push(" AND p.user_id = ").push_bind(user_id);
+            if let Some(domain) = &filter.domain {
+                push_contains(query, "p.domain", domain);
+            }
+            if let Some(category_id) = filter.category_id {
+                query
+                    .push(" AND EXISTS (SELECT 1 FROM password_category pc WHERE pc.password_id = p.password_id AND pc.category_id = ")
+                    .push_bind(category_id)
+                    .push(")");
+            }
+            if let Some(after) = filter.updated_after {
+                query.push(" AND p.updated_at >= ").push_bind(after);
+            }
+            if let Some(before) = filter.updated_before {
+                query.push(" AND p.updated_at < ").push_bind(before);
+            }
+        };
+        Ok(fetch_page(&self.pool, "SELECT p.*", "FROM passwords p", push_filters, page, column, "p.password_id").await?)
+    }
+
+    async fn get(&self, user_id: i64, password_id: i64) -> Result<Option<Password>, RepoError> {
+        Ok(sqlx::query_as("SELECT * FROM passwords WHERE password_id = ? AND user_id = ?")
+            .bind(password_id)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn reveal(&self, vault: &UnlockedVault, user_id: i64, password_id: i64) -> Result<Option<RevealedPassword>, RepoError> {
+        let Some(password) = PasswordRepository::get(self, user_id, password_id).await? else {
+            return Ok(None);
+        };
+        let plain = vault
+            .decrypt(&password.password_encrypted, &passwords_aad(password_id, user_id, &password.domain))
+            .map_err(decryption_error)?;
+        let details = open_details(vault, &password)?;
+        Ok(Some(RevealedPassword {
+            password_id,
+            domain: password.domain,
+            password: plain,
+            details,
+        }))
+    }
+
+    async fn update(&self, vault: &UnlockedVault, user_id: i64, password_id: i64, changes: &PasswordChanges<'_>) -> Result<(), RepoError> {
+        // ההיסטוריה והעדכון באותה טרנזקציה: אם ההיסטוריה לא נשמרה, הרשומה לא משתנה
+        in_transaction(&self.pool, async |conn| {
+            // נביא את הרשומה הישנה ונשמור בהיסטוריה את מה שמשתנה
+            let current: Password = sqlx::query_as("SELECT * FROM passwords WHERE password_id = ? AND user_id = ?")
+                .bind(password_id)
+                .bind(user_id)
+                .fetch_optional(&mut *conn)
+                .await?
+                .ok_or(RepoError::NotFound)?;
+
+            let old_password = vault
+                .decrypt(&current.password_encrypted, &passwords_aad(password_id, user_id, &current.domain))
+                .map_err(decryption_error)?;
+            let old_details = open_details(vault, &current)?;
+            let new_details = changes.apply(&old_details);
+
+            if changes.touches_password() {
+                insert_history(conn, vault, password_id, FIELD_PASSWORD, &old_password).await?;
+            }
+            if let Some(old_domain) = changes.replaced_domain(&current.domain) {
+                insert_history(conn, vault, password_id, FIELD_DOMAIN, old_domain).await?;
+            }
+            for (field, old_value) in replaced_values(&old_details, &new_details)? {
+                insert_history(conn, vault, password_id, field, &old_value).await?;
+            }
+
+            // הדומיין הוא חלק מה-AAD, לכן מצפינים מחדש הכל גם כשרק הדומיין משתנה
+            let new_domain = changes.domain.unwrap_or(&current.domain);
+            let new_password = changes.password.unwrap_or(&old_password);
+            let encrypted = vault
+                .encrypt(new_password, &passwords_aad(password_id, user_id, new_domain))
+                .map_err(encryption_error)?;
+            let details = seal_details(vault, password_id, user_id, new_domain, &new_details)?;
+
+            sqlx::query(
+                "UPDATE passwords SET
+                    domain = ?,
+                    password_encrypted = ?,
+                    username_encrypted = ?,
+                    urls_encrypted = ?,
+                    notes_encrypted = ?,
+                    fields_encrypted = ?,
+                    updated_at = ?
+                 WHERE password_id = ? AND user_id = ?"
+            )
+            .bind(new_domain)
+            .bind(&encrypted)
+            .bind(&details.username)
+            .bind(&details.urls)
+            .bind(&details.notes)
+            .bind(&details.fields)
+            .bind(Utc::now().naive_utc())
+            .bind(password_id)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+            replace_username_tokens(conn, &vault.key, password_id, new_details.username.as_deref()).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete(&self, user_id: i64, password_id: i64) -> Result<bool, RepoError> {
+        // פקודה אחת, ולכן אטומית: ה-ON DELETE CASCADE מוחק איתה את ההיסטוריה ואת הקישורים לקטגוריות
+        let result = sqlx::query("DELETE FROM passwords WHERE password_id = ? AND user_id = ?")
+            .bind(password_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn users_with_at_least(&self, count: i64) -> Result<Vec<PasswordSummary>, RepoError> {
+        Ok(sqlx::query_as(
+            r#"
+            SELECT
+                u.user_id,
+                u.user_first_name,
+                u.user_last_name,
+                COUNT(p.password_id) AS password_count
+            FROM users u
+            JOIN passwords p ON u.user_id = p.user_id
+            GROUP BY u.user_id
+            HAVING COUNT(p.password_id) >= ?
+            ORDER BY password_count DESC;
+            "#
+        )
+        .bind(count)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    async fn link_category(&self, user_id: i64, password_id: i64, category_id: i64) -> Result<(), RepoError> {
+        // מקשרים רק סיסמה של user_id. הקטגוריה נבדקת ב-foreign key
+        let result = sqlx::query(
+            "INSERT INTO password_category (password_id, category_id)
+             SELECT password_id, ? FROM passwords WHERE password_id = ? AND user_id = ?"
+        )
+        .bind(category_id)
+        .bind(password_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn unlink_category(&self, user_id: i64, password_id: i64, category_id: i64) -> Result<bool, RepoError> {
+        let result = sqlx::query(
+            "DELETE FROM password_category
+             WHERE password_id = ? AND category_id = ?
+               AND password_id IN (SELECT password_id FROM passwords WHERE user_id = ?)"
+        )
+        .bind(password_id)
+        .bind(category_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_category_links(&self, user_id: i64, filter: &PasswordCategoryFilter, page: &Page) -> Result<Paged<PasswordCategory>, RepoError> {
+        let push_filters = |query: &mut QueryBuilder<'_, Sqlite>| {
+            query.push(" AND p.user_id = ").push_bind(user_id);
+            if let Some(password_id) = filter.password_id {
+                query.push(" AND pc.password_id = ").push_bind(password_id);
+            }
+            if let Some(category_id) = filter.category_id {
+                query.push(" AND pc.category_id = ").push_bind(category_id);
+            }
+        };
+        // לקישור אין מזהה: העמודה השנייה מכריעה בין שורות עם אותו ערך
+        let (column, tie) = match page.sort.key {
+            "category_id" => ("pc.category_id", "pc.password_id"),
+            _ => ("pc.password_id", "pc.category_id"),
+        };
+        Ok(fetch_page(
+            &self.pool,
+            "SELECT pc.password_id, pc.category_id",
+            "FROM password_category pc JOIN passwords p ON p.password_id = pc.password_id",
+            push_filters,
+            page,
+            column,
+            tie,
+        )
+        .await?)
+    }
+}
+
+// ===================== PASSWORD HISTORY =====================
+#[async_trait]
+impl PasswordHistoryRepository for SqliteStore {
+    async fn list(&self, user_id: i64, filter: &HistoryFilter, page: &Page) -> Result<Paged<PasswordHistory>, RepoError> {
+        let column = match page.sort.key {
+            "changed_at" => "ph.changed_at",
+            _ => "ph.history_id",
+        };
+        let push_filters = |query: &mut QueryBuilder<'_, Sqlite>| {
+            query.push(" AND p.user_id = ").push_bind(user_id);
+            if let Some(password_id) = filter.password_id {
+                query.push(" AND ph.password_id = ").push_bind(password_id);
+            }
+            if let Some(field) = &filter.field {
+                query.push(" AND ph.field = ").push_bind(field.clone());
+            }
+            if let Some(from) = filter.from {
+                query.push(" AND ph.changed_at >= ").push_bind(from);
+            }
+            if let Some(to) = filter.to {
+                query.push(" AND ph.changed_at < ").push_bind(to);
+            }
+        };
+        Ok(fetch_page(
+            &self.pool,
+            "SELECT ph.history_id, ph.password_id, ph.field, ph.old_password_encrypted, ph.changed_at",
+            "FROM password_history ph JOIN passwords p ON p.password_id = ph.password_id",
+            push_filters,
+            page,
+            column,
+            "ph.history_id",
+        )
+        .await?)
+    }
+
+    async fn most_changed_domain(&self, user_id: i64) -> Result<Option<(String, i64)>, RepoError> {
+        Ok(sqlx::query_as(
+            r#"
+            SELECT p.domain, COUNT(ph.history_id) AS change_count
+            FROM passwords p
+            JOIN password_history ph ON p.password_id = ph.password_id
+            WHERE p.user_id = ? AND ph.field = 'password'
+            GROUP BY p.domain
+            ORDER BY change_count DESC
+            LIMIT 1;
+            "#
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+}
+
+// ===================== CATEGORIES =====================
+#[async_trait]
+impl CategoryRepository for SqliteStore {
+    async fn create(&self, name: &str) -> Result<Category, RepoError> {
+        let category_id = sqlx::query("INSERT INTO categories (category_name) VALUES (?)")
+            .bind(name)
+            .execute(&self.pool)
+            .await?
+            .last_insert_rowid();
+        Ok(Category {
+            category_id,
+            category_name: name.to_string(),
+        })
+    }
+
+    async fn list(&self, filter: &CategoryFilter, page: &Page) -> Result<Paged<Category>, RepoError> {
+        let column = match page.sort.key {
+            "category_name" => "category_name",
+            _ => "category_id",
+        };
+        let push_filters = |query: &mut QueryBuilder<'_, Sqlite>| {
+            if let Some(name) = &filter.name {
+                push_contains(query, "category_name", name);
+            }
+        };
+        Ok(fetch_page(&self.pool, "SELECT *", "FROM categories", push_filters, page, column, "category_id").await?)
+    }
+
+    async fn get(&self, category_id: i64) -> Result<Option<Category>, RepoError> {
+        Ok(sqlx::query_as("SELECT * FROM categories WHERE category_id = ?")
+            .bind(category_id)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn rename(&self, category_id: i64, name: Option<&str>) -> Result<bool, RepoError> {
+        let result = sqlx::query("UPDATE categories SET category_name = COALESCE(?, category_name) WHERE category_id = ?")
+            .bind(name)
+            .bind(category_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete(&self, category_id: i64) -> Result<bool, RepoError> {
+        let result = sqlx::query("DELETE FROM categories WHERE category_id = ?")
+            .bind(category_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn search(&self, keyword: &str) -> Result<Vec<Category>, RepoError> {
+        // instr ולא LIKE: % ו-_ במילת החיפוש הם תווים רגילים
+        Ok(sqlx::query_as("SELECT * FROM categories WHERE instr(lower(category_name), lower(?)) > 0 ORDER BY category_id")
+            .bind(keyword)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn users_in(&self, name: &str) -> Result<Vec<UserShoppingInfo>, RepoError> {
+        Ok(sqlx::query_as(
+            r#"
+            SELECT DISTINCT
+                u.user_id,
+                u.user_first_name,
+                u.user_last_name,
+                c.category_name
+            FROM users u
+            JOIN passwords p ON u.user_id = p.user_id
+            JOIN password_category pc ON p.password_id = pc.password_id
+            JOIN categories c ON pc.category_id = c.category_id
+            WHERE c.category_name = ?
+            ORDER BY u.user_id;
+            "#
+        )
+        .bind(name)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
+
+// ===================== TESTS =====================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::passwords::EntryDetails;
+    use crate::middleware::permissions::ROLE_USER;
+    use crate::utils::db::{new_user, test_pool};
+    use crate::utils::keys::load_test_keys;
+    use crate::utils::vault::VaultKey;
+
+    async fn store(name: &str) -> SqliteStore {
+        load_test_keys();
+        SqliteStore::new(test_pool(name).await)
+    }
+
+    /// משתמש עם רשומה אחת: סיסמה p1 והערה n1
+    async fn store_with_entry(name: &str) -> (SqliteStore, UnlockedVault, i64) {
+        let store = store(name).await;
+        let user = UserRepository::create(&store, new_user("a@x.io")).await.unwrap();
+        let vault = UnlockedVault { user_id: user.user_id, key: VaultKey::generate() };
+        let details = EntryDetails { notes: Some("n1".to_string()), ..Default::default() };
+        let entry = NewPassword { user_id: user.user_id, domain: "a.com", password: "p1", details: &details, category_name: "web" };
+        let password_id = PasswordRepository::create(&store, &vault, entry).await.unwrap().password_id;
+        (store, vault, password_id)
+    }
+
+    async fn count(store: &SqliteStore, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table)).fetch_one(&store.pool).await.unwrap()
+    }
+
+    async fn fail_on(store: &SqliteStore, event: &str) {
+        sqlx::query(&format!("CREATE TRIGGER fail_write BEFORE {} BEGIN SELECT RAISE(ABORT, 'injected failure'); END", event))
+            .execute(&store.pool)
+            .await
+            .unwrap();
+    }
+
+    /// הרשומה לא השתנתה ואין לה היסטוריה
+    async fn assert_untouched(store: &SqliteStore, vault: &UnlockedVault, password_id: i64) {
+        let revealed = store.reveal(vault, vault.user_id, password_id).await.unwrap().unwrap();
+        assert_eq!((revealed.domain.as_str(), revealed.password.as_str()), ("a.com", "p1"));
+        assert_eq!(revealed.details.notes.as_deref(), Some("n1"));
+        let history: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM password_history WHERE password_id = ?")
+            .bind(password_id)
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(history, 0);
+    }
+
+    #[actix_web::test]
+    async fn update_is_not_applied_when_history_cannot_be_written() {
+        let (store, vault, id) = store_with_entry("update-history-failure").await;
+        fail_on(&store, "INSERT ON password_history").await;
+
+        let changes = PasswordChanges { password: Some("p2"), notes: Some("n2"), ..Default::default() };
+        assert!(PasswordRepository::update(&store, &vault, vault.user_id, id, &changes).await.is_err());
+        assert_untouched(&store, &vault, id).await;
+    }
+
+    #[actix_web::test]
+    async fn history_is_not_kept_when_update_fails() {
+        let (store, vault, id) = store_with_entry("update-row-failure").await;
+        // ההיסטוריה כבר נכתבה כשהעדכון של השורה נכשל
+        fail_on(&store, "UPDATE ON passwords").await;
+
+        let changes = PasswordChanges { domain: Some("b.com"), password: Some("p2"), ..Default::default() };
+        assert!(PasswordRepository::update(&store, &vault, vault.user_id, id, &changes).await.is_err());
+        assert_untouched(&store, &vault, id).await;
+    }
+
+    #[actix_web::test]
+    async fn failed_create_leaves_nothing_behind() {
+        let store = store("create-password-failure").await;
+        let user = UserRepository::create(&store, new_user("a@x.io")).await.unwrap();
+        let vault = UnlockedVault { user_id: user.user_id, key: VaultKey::generate() };
+        let categories = count(&store, "categories").await;
+        // הסיסמה, הקטגוריה החדשה ושורת החיפוש כבר נכתבו כשהקישור נכשל
+        fail_on(&store, "INSERT ON password_category").await;
+
+        let details = EntryDetails::default();
+        let entry = NewPassword { user_id: user.user_id, domain: "a.com", password: "p1", details: &details, category_name: "brand-new" };
+        assert!(PasswordRepository::create(&store, &vault, entry).await.is_err());
+
+        assert_eq!(count(&store, "passwords").await, 0);
+        assert_eq!(count(&store, "categories").await, categories);
+        assert_eq!(count(&store, "password_search").await, 0);
+
+        // הדומיין לא נשאר תפוס
+        sqlx::query("DROP TRIGGER fail_write").execute(&store.pool).await.unwrap();
+        let entry = NewPassword { user_id: user.user_id, domain: "a.com", password: "p1", details: &details, category_name: "brand-new" };
+        PasswordRepository::create(&store, &vault, entry).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn create_user_writes_user_role_and_verification_token() {
+        let store = store("create-user").await;
+        let created = UserRepository::create(&store, new_user("a@x.io")).await.unwrap();
+
+        assert_eq!(count(&store, "email_verification_tokens").await, 1);
+        let roles: Vec<String> = sqlx::query_scalar(
+            "SELECT r.role_name FROM user_roles ur JOIN roles r ON r.role_id = ur.role_id WHERE ur.user_id = ?"
+        )
+        .bind(created.user_id)
+        .fetch_all(&store.pool)
+        .await
+        .unwrap();
+        assert_eq!(roles, [ROLE_USER]);
+    }
+
+    #[actix_web::test]
+    async fn failed_signup_leaves_nothing_behind() {
+        let store = store("create-user-failure").await;
+
+        // כל שלב אחרי ההכנסה של המשתמש נכשל בתורו
+        for table in ["user_roles", "email_verification_tokens"] {
+            fail_on(&store, &format!("INSERT ON {}", table)).await;
+            assert!(UserRepository::create(&store, new_user("a@x.io")).await.is_err(), "{}", table);
+            for written in ["users", "user_roles", "email_verification_tokens"] {
+                assert_eq!(count(&store, written).await, 0, "{} left behind when {} failed", written, table);
+            }
+            sqlx::query("DROP TRIGGER fail_write").execute(&store.pool).await.unwrap();
+        }
+
+        // המייל לא נשאר תפוס על ידי חשבון חצי-כתוב
+        UserRepository::create(&store, new_user("a@x.io")).await.unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use crate::models::users::{User, UserFilter};
+use crate::repositories::RepoError;
+use crate::utils::pagination::{Page, Paged};
+use crate::utils::vault::{PendingVaultKey, WrappedVaultKey};
+
+/// שורת users כולל מפתח הכספת העטוף. רק ל-login ולהחלפת סיסמה, לא יוצאת ללקוח
+#[derive(Clone, sqlx::FromRow)]
+pub struct UserAccount {
+    #[sqlx(flatten)]
+    pub user: User,
+    pub vault_key_salt: Option<String>,
+    pub vault_key_wrapped: Option<String>,
+    pub vault_key_escrow: Option<String>,
+}
+
+impl UserAccount {
+    /// None למשתמשים ישנים שנוצרו לפני שהיה מפתח כספת
+    pub fn vault_key(&self) -> Option<WrappedVaultKey> {
+        Some(WrappedVaultKey {
+            salt: self.vault_key_salt.clone()?,
+            wrapped: self.vault_key_wrapped.clone()?,
+        })
+    }
+}
+
+/// משתמש חדש, אחרי שהסיסמה עברה hash. מפתח הכספת נעטף ב-repository, כי הוא קשור ל-user_id
+pub struct NewUser {
+    pub user_first_name: String,
+    pub user_last_name: String,
+    pub email: String,
+    pub phone: String,
+    pub password_hash: String,
+    pub vault_key: PendingVaultKey,
+    /// התפקיד שהחשבון מקבל בהרשמה
+    pub role: &'static str,
+    pub verification: NewVerificationToken,
+}
+
+/// token לאימות המייל של משתמש חדש. נשמר רק ה-hash, ה-token עצמו נשלח במייל אחרי ה-commit
+pub struct NewVerificationToken {
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+}
+
+/// שדה None לא משתנה
+#[derive(Default)]
+pub struct UserChanges {
+    pub user_first_name: Option<String>,
+    pub user_last_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub is_active: Option<bool>,
+    /// כתובת חדשה מחזירה את החשבון למצב ממתין לאימות
+    pub email_changed: bool,
+}
+
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    /// החשבון נוצר פעיל וממתין לאימות המייל. המשתמש, התפקיד וה-token נכתבים יחד, הכל או כלום:
+    /// חשבון בלי תפקיד או בלי token היה תופס את המייל בלי דרך לאמת אותו. Duplicate אם המייל תפוס
+    async fn create(&self, user: NewUser) -> Result<User, RepoError>;
+    async fn list(&self, filter: &UserFilter, page: &Page) -> Result<Paged<User>, RepoError>;
+    async fn get(&self, user_id: i64) -> Result<Option<User>, RepoError>;
+    async fn account(&self, user_id: i64) -> Result<Option<UserAccount>, RepoError>;
+    /// בלי הבדל בין אותיות גדולות וקטנות
+    async fn find_account_by_email(&self, email: &str) -> Result<Option<UserAccount>, RepoError>;
+    /// האם הכתובת כבר שייכת למשתמש אחר (בלי הבדל בין אותיות גדולות וקטנות)
+    async fn email_in_use(&self, email: &str, except_user_id: Option<i64>) -> Result<bool, RepoError>;
+    /// false אם המשתמש לא קיים
+    async fn update(&self, user_id: i64, changes: &UserChanges) -> Result<bool, RepoError>;
+    /// סיסמת התחברות חדשה יחד עם מפתח הכספת העטוף בה
+    async fn set_password(&self, user_id: i64, password_hash: &str, vault_key: &WrappedVaultKey) -> Result<(), RepoError>;
+    /// מחליף את ה-hash רק אם הוא עדיין expected (הסיסמה לא השתנתה בינתיים)
+    async fn replace_password_hash(&self, user_id: i64, expected: &str, new_hash: &str) -> Result<bool, RepoError>;
+    async fn set_vault_key(&self, user_id: i64, vault_key: &WrappedVaultKey) -> Result<(), RepoError>;
+    /// None מוחק את העותק (שחזור הכספת כבוי)
+    async fn set_vault_escrow(&self, user_id: i64, escrow: Option<&str>) -> Result<(), RepoError>;
+    /// last_login מתעדכן רק לחשבון פעיל. false אם החשבון הושבת או נמחק
+    async fn touch_last_login(&self, user_id: i64) -> Result<bool, RepoError>;
+    /// הסיסמאות, ההיסטוריה והתפקידים של המשתמש נמחקים איתו
+    async fn delete(&self, user_id: i64) -> Result<bool, RepoError>;
+    async fn created_between_years(&self, from: i32, to: i32) -> Result<Vec<User>, RepoError>;
+}
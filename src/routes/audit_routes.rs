@@ -0,0 +1,11 @@
+use actix_web::web;
+use crate::controllers::audit_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission, ROLE_ADMIN, ROLE_AUDITOR};
+
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("GET", "/audit", Access::Roles(&[ROLE_ADMIN, ROLE_AUDITOR])),
+];
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_audit_log);
+}
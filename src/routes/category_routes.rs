@@ -1,5 +1,13 @@
 use actix_web::web;
 use crate::controllers::categories_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission, ROLE_ADMIN};
+
+// הקטגוריות משותפות לכל המשתמשים, לכן רק admin משנה אותן
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("POST", "/categories", Access::Roles(&[ROLE_ADMIN])),
+    RoutePermission::new("PUT", "/categories/{id}", Access::Roles(&[ROLE_ADMIN])),
+    RoutePermission::new("DELETE", "/categories/{id}", Access::Roles(&[ROLE_ADMIN])),
+];
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(create_category);
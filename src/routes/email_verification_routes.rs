@@ -0,0 +1,12 @@
+use actix_web::web;
+use crate::controllers::email_verification_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission};
+
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("POST", "/auth/verify-email", Access::Public),
+];
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(verify_email);
+    cfg.service(resend_verification);
+}
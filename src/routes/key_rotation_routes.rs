@@ -0,0 +1,15 @@
+use actix_web::web;
+use crate::controllers::key_rotation_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission, ROLE_ADMIN, ROLE_AUDITOR};
+
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("POST", "/admin/key-rotation", Access::Roles(&[ROLE_ADMIN])),
+    RoutePermission::new("GET", "/admin/key-rotation", Access::Roles(&[ROLE_ADMIN, ROLE_AUDITOR])),
+    RoutePermission::new("GET", "/admin/key-rotation/{id}", Access::Roles(&[ROLE_ADMIN, ROLE_AUDITOR])),
+];
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(start_key_rotation);
+    cfg.service(get_key_rotation_jobs);
+    cfg.service(get_key_rotation_job);
+}
@@ -0,0 +1,13 @@
+use actix_web::web;
+use crate::controllers::lockout_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission, ROLE_ADMIN, ROLE_AUDITOR};
+
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("GET", "/admin/lockouts", Access::Roles(&[ROLE_ADMIN, ROLE_AUDITOR])),
+    RoutePermission::new("DELETE", "/admin/lockouts/{scope}/{key}", Access::Roles(&[ROLE_ADMIN])),
+];
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_lockouts);
+    cfg.service(clear_lockout);
+}
@@ -0,0 +1,11 @@
+use actix_web::web;
+use crate::controllers::login_history_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission, ROLE_ADMIN};
+
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("GET", "/users/{id}/logins", Access::SelfOr(&[ROLE_ADMIN])),
+];
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_user_logins);
+}
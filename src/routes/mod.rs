@@ -2,4 +2,37 @@ pub mod user_routes;
 pub mod password_routes;
 pub mod password_category_routes;
 pub mod password_history_routes;
-pub mod category_routes;
\ No newline at end of file
+pub mod category_routes;
+pub mod key_rotation_routes;
+pub mod session_routes;
+pub mod role_routes;
+pub mod audit_routes;
+pub mod totp_routes;
+pub mod lockout_routes;
+pub mod login_history_routes;
+pub mod password_reset_routes;
+pub mod email_verification_routes;
+pub mod search_routes;
+
+use crate::middleware::permissions::PermissionTable;
+
+/// טבלאות ההרשאות של כל מודולי ה-routes, לפי הסדר שבו הם נבדקים
+pub fn permission_table() -> PermissionTable {
+    PermissionTable::new([
+        role_routes::PERMISSIONS,
+        user_routes::PERMISSIONS,
+        password_routes::PERMISSIONS,
+        password_category_routes::PERMISSIONS,
+        password_history_routes::PERMISSIONS,
+        category_routes::PERMISSIONS,
+        key_rotation_routes::PERMISSIONS,
+        session_routes::PERMISSIONS,
+        audit_routes::PERMISSIONS,
+        totp_routes::PERMISSIONS,
+        lockout_routes::PERMISSIONS,
+        login_history_routes::PERMISSIONS,
+        password_reset_routes::PERMISSIONS,
+        email_verification_routes::PERMISSIONS,
+        search_routes::PERMISSIONS,
+    ])
+}
@@ -1,6 +1,11 @@
 
 use actix_web::web;
 use crate::controllers::password_category_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission, ROLE_ADMIN};
+
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("GET", "/password_category/users_with_com", Access::Roles(&[ROLE_ADMIN])),
+];
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(create_password_category);
@@ -1,5 +1,12 @@
 use actix_web::web;
 use crate::controllers::password_history_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission};
+
+// כל משתמש רואה רק את ההיסטוריה של הסיסמאות שלו
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("GET", "/password_history", Access::Authenticated),
+    RoutePermission::new("GET", "/password_history/most_changed_domain", Access::Authenticated),
+];
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(get_password_history);
@@ -0,0 +1,13 @@
+use actix_web::web;
+use crate::controllers::password_reset_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission};
+
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("POST", "/auth/forgot-password", Access::Public),
+    RoutePermission::new("POST", "/auth/reset-password", Access::Public),
+];
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(forgot_password);
+    cfg.service(reset_password);
+}
@@ -1,11 +1,18 @@
 use actix_web::web;
 use crate::controllers::passwords_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission, ROLE_ADMIN};
+
+// שאר נתיבי /passwords פתוחים לכל משתמש מחובר ומוגבלים לשורות שלו בקונטרולר
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("GET", "/passwords/users_with_3_or_more", Access::Roles(&[ROLE_ADMIN])),
+];
 
 pub fn config(cfg: &mut web::ServiceConfig) {
         cfg.service(get_users_with_3_or_more_passwords);
         cfg.service(create_password);
         cfg.service(get_passwords);
         cfg.service(get_password);
+        cfg.service(reveal_password);
         cfg.service(update_password);
         cfg.service(delete_password);
 }
@@ -0,0 +1,17 @@
+use actix_web::web;
+use crate::controllers::roles_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission, ROLE_ADMIN, ROLE_AUDITOR};
+
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("GET", "/roles", Access::Roles(&[ROLE_ADMIN, ROLE_AUDITOR])),
+    RoutePermission::new("GET", "/users/{id}/roles", Access::SelfOr(&[ROLE_ADMIN, ROLE_AUDITOR])),
+    RoutePermission::new("POST", "/users/{id}/roles", Access::Roles(&[ROLE_ADMIN])),
+    RoutePermission::new("DELETE", "/users/{id}/roles/{role_name}", Access::Roles(&[ROLE_ADMIN])),
+];
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_roles);
+    cfg.service(get_user_roles);
+    cfg.service(add_user_role);
+    cfg.service(remove_user_role);
+}
@@ -0,0 +1,12 @@
+use actix_web::web;
+use crate::controllers::search_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission};
+
+// החיפוש עובר רק על הסיסמאות של המשתמש המחובר
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("GET", "/search", Access::Authenticated),
+];
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(search);
+}
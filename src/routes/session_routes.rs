@@ -0,0 +1,12 @@
+use actix_web::web;
+use crate::controllers::sessions_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission};
+
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("POST", "/auth/refresh", Access::Public),
+];
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(refresh);
+    cfg.service(logout);
+}
@@ -0,0 +1,14 @@
+use actix_web::web;
+use crate::controllers::totp_controller::*;
+use crate::middleware::permissions::{Access, RoutePermission};
+
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("POST", "/login/totp", Access::Public),
+];
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(enroll_totp);
+    cfg.service(confirm_totp);
+    cfg.service(disable_totp);
+    cfg.service(login_totp);
+}
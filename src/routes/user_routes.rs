@@ -4,10 +4,27 @@ use crate::controllers::users_controller::{
     get_users,
     get_user,
     update_user,
+    change_password,
+    get_vault_recovery,
+    enable_vault_recovery,
+    disable_vault_recovery,
+    restore_vault,
     delete_user,
     login,
     get_users_created_in_range,
 };
+use crate::middleware::permissions::{Access, RoutePermission, ROLE_ADMIN, ROLE_AUDITOR};
+
+// created_in_range לפני {id}, החוק הראשון שמתאים קובע
+pub const PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission::new("POST", "/users", Access::Public),
+    RoutePermission::new("POST", "/login", Access::Public),
+    RoutePermission::new("GET", "/users", Access::Roles(&[ROLE_ADMIN])),
+    RoutePermission::new("GET", "/users/created_in_range", Access::Roles(&[ROLE_ADMIN])),
+    RoutePermission::new("GET", "/users/{id}", Access::SelfOr(&[ROLE_ADMIN, ROLE_AUDITOR])),
+    RoutePermission::new("PUT", "/users/{id}", Access::SelfOr(&[ROLE_ADMIN])),
+    RoutePermission::new("DELETE", "/users/{id}", Access::Roles(&[ROLE_ADMIN])),
+];
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg
@@ -16,6 +33,11 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         .service(get_users)
         .service(get_user)
         .service(update_user)
+        .service(change_password)
+        .service(get_vault_recovery)
+        .service(enable_vault_recovery)
+        .service(disable_vault_recovery)
+        .service(restore_vault)
         .service(delete_user)
         .service(login)
         ;
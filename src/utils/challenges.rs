@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use crate::utils::tokens::{generate_token, hash_token};
+use crate::utils::vault::VaultKey;
+
+// כמה זמן יש להזין את הקוד אחרי שהסיסמה אומתה, וכמה ניסיונות
+const CHALLENGE_MINUTES: i64 = 5;
+const MAX_ATTEMPTS: u32 = 5;
+
+struct LoginChallenge {
+    user_id: i64,
+    vault_key: VaultKey,
+    expires_at: NaiveDateTime,
+    attempts: u32,
+}
+
+/// התחברויות שעברו את שלב הסיסמה ומחכות לגורם השני.
+/// נשמרות בזיכרון בלבד כי הן מחזיקות את מפתח הכספת הפתוח
+#[derive(Default)]
+pub struct LoginChallenges {
+    challenges: Mutex<HashMap<String, LoginChallenge>>,
+}
+
+impl LoginChallenges {
+    /// מחזיר את ה-token של האתגר ואת זמן התפוגה שלו
+    pub fn create(&self, user_id: i64, vault_key: VaultKey) -> (String, NaiveDateTime) {
+        let now = Utc::now().naive_utc();
+        let token = generate_token();
+        let expires_at = now + Duration::minutes(CHALLENGE_MINUTES);
+
+        let mut challenges = self.challenges.lock().unwrap();
+        challenges.retain(|_, c| c.expires_at > now);
+        challenges.insert(hash_token(&token), LoginChallenge { user_id, vault_key, expires_at, attempts: 0 });
+        (token, expires_at)
+    }
+
+    /// המשתמש של אתגר שעדיין בתוקף
+    pub fn user_id(&self, token: &str) -> Option<i64> {
+        let challenges = self.challenges.lock().unwrap();
+        challenges
+            .get(&hash_token(token))
+            .filter(|c| c.expires_at > Utc::now().naive_utc())
+            .map(|c| c.user_id)
+    }
+
+    /// קוד שגוי. אחרי יותר מדי ניסיונות האתגר נמחק וצריך להתחבר מחדש
+    pub fn fail(&self, token: &str) {
+        let key = hash_token(token);
+        let mut challenges = self.challenges.lock().unwrap();
+        if let Some(c) = challenges.get_mut(&key) {
+            c.attempts += 1;
+            if c.attempts >= MAX_ATTEMPTS {
+                challenges.remove(&key);
+            }
+        }
+    }
+
+    /// האתגר הושלם: מוציא אותו ומחזיר את מפתח הכספת
+    pub fn complete(&self, token: &str) -> Option<(i64, VaultKey)> {
+        self.challenges
+            .lock()
+            .unwrap()
+            .remove(&hash_token(token))
+            .filter(|c| c.expires_at > Utc::now().naive_utc())
+            .map(|c| (c.user_id, c.vault_key))
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::{Connection, SqlitePool, Row};
+use crate::config::DatabaseSettings;
+
+// ===================== CONNECTION =====================
+/// ההגדרות של כל חיבור ל-SQLite. foreign_keys תמיד דולק: בלי זה ON DELETE CASCADE לא עושה כלום
+pub fn connect_options(settings: &DatabaseSettings) -> Result<SqliteConnectOptions, sqlx::Error> {
+    Ok(SqliteConnectOptions::from_str(&settings.url)?
+        .create_if_missing(true)
+        .foreign_keys(true)
+        .journal_mode(SqliteJournalMode::from_str(&settings.journal_mode)?)
+        .synchronous(SqliteSynchronous::from_str(&settings.synchronous)?)
+        .busy_timeout(Duration::from_millis(settings.busy_timeout_ms)))
+}
+
+pub async fn connect_pool(settings: &DatabaseSettings) -> Result<SqlitePool, sqlx::Error> {
+    SqlitePoolOptions::new()
+        .max_connections(settings.max_connections)
+        .connect_with(connect_options(settings)?)
+        .await
+}
+
+// ===================== UNIT OF WORK =====================
+/// מריץ כמה פעולות כטרנזקציה אחת: commit רק אם כולן הצליחו.
+/// שגיאה באמצע מבטלת הכל, כי טרנזקציה שלא עברה commit עושה rollback כשהיא נזרקת
+pub async fn in_transaction<T, E>(
+    pool: &SqlitePool,
+    work: impl AsyncFnOnce(&mut SqliteConnection) -> Result<T, E>,
+) -> Result<T, E>
+where
+    E: From<sqlx::Error>,
+{
+    let mut tx = pool.begin().await?;
+    let value = work(&mut tx).await?;
+    tx.commit().await?;
+    Ok(value)
+}
+
+// ===================== ORPHANS =====================
+/// כמה שורות בכל טבלה מפנות לשורה שלא קיימת (נשארו מלפני ש-foreign_keys הופעל)
+pub async fn orphan_counts(conn: &mut SqliteConnection) -> Result<BTreeMap<String, u64>, sqlx::Error> {
+    let mut counts = BTreeMap::new();
+    for row in sqlx::query("PRAGMA foreign_key_check").fetch_all(&mut *conn).await? {
+        *counts.entry(row.get::<String, _>("table")).or_default() += 1;
+    }
+    Ok(counts)
+}
+
+/// מוחק שורות יתומות כאילו ה-CASCADE היה פועל מההתחלה. הכל בטרנזקציה אחת
+pub async fn delete_orphans(conn: &mut SqliteConnection) -> Result<BTreeMap<String, u64>, sqlx::Error> {
+    let mut deleted = BTreeMap::new();
+    let mut tx = conn.begin().await?;
+
+    // מחיקה של סיסמה יתומה מוחקת (ב-CASCADE) גם את ההיסטוריה והקטגוריות שלה, לכן בודקים שוב
+    loop {
+        let orphans = sqlx::query("PRAGMA foreign_key_check").fetch_all(&mut *tx).await?;
+        if orphans.is_empty() {
+            break;
+        }
+        let mut removed = 0;
+        for row in orphans {
+            let table: String = row.get("table");
+            // טבלה בלי rowid (WITHOUT ROWID) צריך לתקן ידנית
+            let Some(rowid) = row.get::<Option<i64>, _>("rowid") else {
+                continue;
+            };
+            let result = sqlx::query(&format!("DELETE FROM \"{}\" WHERE rowid = ?", table.replace('"', "\"\"")))
+                .bind(rowid)
+                .execute(&mut *tx)
+                .await?;
+            removed += result.rows_affected();
+            *deleted.entry(table).or_default() += result.rows_affected();
+        }
+        if removed == 0 {
+            return Err(sqlx::Error::Protocol("orphan rows left that cannot be deleted by rowid".into()));
+        }
+    }
+
+    tx.commit().await?;
+    Ok(deleted)
+}
+
+// ===================== TESTS =====================
+/// DB ריק בקובץ זמני אחרי כל המיגרציות, לבדיקות מול SQLite אמיתי (טרנזקציות, triggers).
+/// name מפריד בין בדיקות שרצות במקביל. חיבור אחד: טרנזקציה שנזרקה עושה rollback לפני הפקודה הבאה
+#[cfg(test)]
+pub async fn test_pool(name: &str) -> SqlitePool {
+    let path = std::env::temp_dir().join(format!("pms-test-{}-{}.db", std::process::id(), name));
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+    let settings = DatabaseSettings { url: format!("sqlite://{}", path.display()), max_connections: 1, ..Default::default() };
+    crate::utils::migrations::run_pending(&settings).await.unwrap();
+    connect_pool(&settings).await.unwrap()
+}
+
+/// משתמש חדש לבדיקות. המפתח נעטף בלי Argon2, כדי שהבדיקות לא יחכו לגזירת המפתח
+#[cfg(test)]
+pub fn new_user(email: &str) -> crate::repositories::users::NewUser {
+    use crate::repositories::users::{NewUser, NewVerificationToken};
+    use crate::utils::tokens::{generate_token, hash_token};
+    use crate::utils::vault::{PendingVaultKey, VaultKey};
+
+    NewUser {
+        user_first_name: "A".to_string(),
+        user_last_name: "B".to_string(),
+        email: email.to_string(),
+        phone: String::new(),
+        password_hash: "hash".to_string(),
+        vault_key: PendingVaultKey::without_password(VaultKey::generate()),
+        role: crate::middleware::permissions::ROLE_USER,
+        verification: NewVerificationToken {
+            token_hash: hash_token(&generate_token()),
+            expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::hours(24),
+        },
+    }
+}
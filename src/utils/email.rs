@@ -0,0 +1,110 @@
+// מגבלות האורך של RFC 5321
+pub const MAX_EMAIL_LEN: usize = 254;
+const MAX_LOCAL_LEN: usize = 64;
+const MAX_LABEL_LEN: usize = 63;
+
+/// בודק שהכתובת תקינה ומחזיר אותה בצורה אחידה (בלי רווחים, באותיות קטנות),
+/// כך ש-Dana@Example.com ו-dana@example.com הם אותו חשבון
+pub fn normalize_email(raw: &str) -> Result<String, &'static str> {
+    let email = raw.trim().to_lowercase();
+    if email.is_empty() || email.len() > MAX_EMAIL_LEN {
+        return Err("Email address has an invalid length");
+    }
+
+    let Some((local, domain)) = email.split_once('@') else {
+        return Err("Email address must contain @");
+    };
+
+    // החלק המקומי: התווים הנפוצים (בלי מרכאות ובלי הערות), בלי נקודה בהתחלה, בסוף או כפולה
+    if local.is_empty()
+        || local.len() > MAX_LOCAL_LEN
+        || local.starts_with('.')
+        || local.ends_with('.')
+        || local.contains("..")
+        || !local.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+/=?^_`{|}~.-".contains(c))
+    {
+        return Err("Email address has an invalid local part");
+    }
+
+    // הדומיין: לפחות שתי תוויות, כל אחת אותיות/ספרות/מקף (לא בקצוות), וסיומת של אותיות
+    // או סיומת בשפה אחרת ב-punycode (xn--4dbrk0ce = ישראל)
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid_labels = labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= MAX_LABEL_LEN
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    let valid_tld = labels
+        .last()
+        .is_some_and(|tld| (tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic())) || tld.starts_with("xn--"));
+    if labels.len() < 2 || !valid_labels || !valid_tld {
+        return Err("Email address has an invalid domain");
+    }
+
+    Ok(email)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_are_trimmed_and_lowercased() {
+        let cases = [
+            ("dana@example.com", "dana@example.com"),
+            ("  Dana@Example.COM\n", "dana@example.com"),
+            ("First.Last+Tag@Mail.Example.co.il", "first.last+tag@mail.example.co.il"),
+            ("o'brien@example.org", "o'brien@example.org"),
+            ("dana@xn--mnchen-3ya.de", "dana@xn--mnchen-3ya.de"),
+            ("dana@example.XN--4DBRK0CE", "dana@example.xn--4dbrk0ce"),
+        ];
+        for (raw, normalized) in cases {
+            assert_eq!(normalize_email(raw), Ok(normalized.to_string()), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn invalid_addresses_are_rejected() {
+        let longest_local = "a".repeat(MAX_LOCAL_LEN);
+        let too_long_local = format!("{}@example.com", "a".repeat(MAX_LOCAL_LEN + 1));
+        let too_long_label = format!("dana@{}.com", "a".repeat(MAX_LABEL_LEN + 1));
+        // 64 + 1 + 63 + 1 + 63 + 1 + 61 = 254, ועוד תו אחד
+        let longest = format!("{}@{}.{}.{}", longest_local, "b".repeat(63), "c".repeat(63), "d".repeat(61));
+        let too_long = format!("{}e", longest);
+        assert_eq!(normalize_email(&longest).map(|email| email.len()), Ok(MAX_EMAIL_LEN));
+        assert!(normalize_email(&format!("{}@example.com", longest_local)).is_ok());
+
+        let rejected = [
+            "",
+            "   ",
+            "dana",
+            "dana.example.com",
+            "@example.com",
+            "dana@",
+            ".dana@example.com",
+            "dana.@example.com",
+            "da..na@example.com",
+            "da na@example.com",
+            "\"dana\"@example.com",
+            "dana@@example.com",
+            "dana@localhost",
+            "dana@example.c",
+            "dana@example.123",
+            "dana@-example.com",
+            "dana@example-.com",
+            "dana@example..com",
+            "dana@exa_mple.com",
+            // רק punycode: כתובות בשפה אחרת לא ממירים כאן
+            "dana@דוגמה.ישראל",
+            "דנה@example.com",
+            too_long_local.as_str(),
+            too_long_label.as_str(),
+            too_long.as_str(),
+        ];
+        for raw in rejected {
+            assert!(normalize_email(raw).is_err(), "{:?}", raw);
+        }
+    }
+}
@@ -1,44 +1,388 @@
 use aes_gcm::Aes256Gcm; // AES256
-use aes_gcm::aead::{Aead, KeyInit, OsRng, generic_array::GenericArray};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
 use aes_gcm::Nonce;
-use rand_core::RngCore;
+use std::sync::atomic::{AtomicBool, Ordering};
 use base64::{engine::general_purpose, Engine as _};
+use crate::config;
+use crate::middleware::request_id;
+use crate::utils::keys::key_ring;
 
-// 32 בייט מפתח
-const KEY_BYTES: [u8; 32] = [
+// המפתח הקבוע הישן - משמש רק כדי לפענח שורות ישנות עד שיוצפנו מחדש, וכבוי כברירת מחדל
+// (keys.allow_legacy_key). keys migrate-legacy מצפין אותן מחדש כדי שאפשר יהיה למחוק אותו
+const LEGACY_KEY_BYTES: [u8; 32] = [
     1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,
     17,18,19,20,21,22,23,24,25,26,27,28,29,30,31,32
 ];
 
-pub fn encrypt_password(password: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let key = GenericArray::from_slice(&KEY_BYTES);
-    let cipher = Aes256Gcm::new(key);
+// מבנה הנתונים המוצפן (לפני base64):
+// version (1) | key_id (4, big endian) | nonce (12) | ciphertext + tag
+// גרסה 1 - בלי associated data, גרסה 2 - קשור לשורה שלו דרך AAD
+pub const ENVELOPE_VERSION_UNBOUND: u8 = 1;
+pub const ENVELOPE_VERSION: u8 = 2;
+const HEADER_LEN: usize = 5;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+// key_id 0 מסמן ערך שהוצפן במפתח הכספת האישי של המשתמש ולא במפתח של השרת
+pub const VAULT_KEY_ID: u32 = 0;
 
-    let mut nonce_bytes = [0u8; 12];
-    OsRng.fill_bytes(&mut nonce_bytes);
-    let nonce = GenericArray::from_slice(&nonce_bytes);
+// נדלק כשכבר אין ערכים לא קשורים (key_rotation_controller::enforce_bound_if_migrated).
+// ערכים חדשים תמיד נכתבים קשורים, לכן הוא לא נכבה יותר
+static BOUND_ONLY: AtomicBool = AtomicBool::new(false);
 
-    let ciphertext = cipher.encrypt(nonce, password.as_bytes())
+// ===================== ASSOCIATED DATA =====================
+/// AAD של שורה בטבלת passwords
+pub fn passwords_aad(password_id: i64, user_id: i64, domain: &str) -> Vec<u8> {
+    format!("passwords:{}:{}:{}", password_id, user_id, domain).into_bytes()
+}
+
+/// AAD של פרט נוסף באותה שורה של passwords (username, urls, notes, fields)
+pub fn entry_field_aad(password_id: i64, user_id: i64, domain: &str, field: &str) -> Vec<u8> {
+    format!("passwords:{}:{}:{}:{}", password_id, user_id, domain, field).into_bytes()
+}
+
+/// AAD של שורה בטבלת password_history
+pub fn history_aad(history_id: i64, password_id: i64) -> Vec<u8> {
+    format!("password_history:{}:{}", history_id, password_id).into_bytes()
+}
+
+/// AAD של ערך ישן בהיסטוריה לפי השדה. לסיסמה עצמה נשאר history_aad, כדי שהשורות הקיימות ייפתחו
+pub fn history_field_aad(history_id: i64, password_id: i64, field: &str) -> Vec<u8> {
+    if field == "password" {
+        return history_aad(history_id, password_id);
+    }
+    format!("password_history:{}:{}:{}", history_id, password_id, field).into_bytes()
+}
+
+/// AAD של סוד ה-TOTP של משתמש
+pub fn totp_aad(user_id: i64) -> Vec<u8> {
+    format!("users_totp:{}", user_id).into_bytes()
+}
+
+/// AAD של מפתח הכספת העטוף, בשתי השכבות (המפתח מהסיסמה ומפתח השרת)
+pub fn vault_key_aad(user_id: i64) -> Vec<u8> {
+    format!("users_vault_key:{}", user_id).into_bytes()
+}
+
+/// AAD של העותק של מפתח הכספת שמוצפן במפתח השרת (לאיפוס סיסמה)
+pub fn vault_escrow_aad(user_id: i64) -> Vec<u8> {
+    format!("users_vault_escrow:{}", user_id).into_bytes()
+}
+
+// ===================== SERVER KEY =====================
+pub fn encrypt_password(password: &str, aad: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let ring = key_ring()?;
+    seal(ring.active_id(), ring.active().as_bytes(), password, aad)
+}
+
+pub fn decrypt_password(encoded: &str, aad: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    decrypt_server(encoded, aad, config::get().keys.allow_legacy_key)
+}
+
+/// allow_legacy: לנסות גם את המפתח הקבוע הישן, אחרי כל המפתחות של ה-key ring
+fn decrypt_server(encoded: &str, aad: &[u8], allow_legacy: bool) -> Result<String, Box<dyn std::error::Error>> {
+    let data = general_purpose::STANDARD.decode(encoded)?;
+    let ring = key_ring()?;
+
+    if let Some((version, key_id)) = envelope_header(&data)
+        && let Ok(key) = ring.get(key_id)
+    {
+        match open(key.as_bytes(), version, &data[HEADER_LEN..], aad) {
+            Ok(plain) => return Ok(plain),
+            // ערך קשור שלא נפתח עם ה-AAD של השורה הזאת הוא כנראה ערך שהועתק משורה אחרת
+            Err(e) if version == ENVELOPE_VERSION => return Err(e),
+            Err(_) => {}
+        }
+    }
+
+    // פורמט ישן בלי כותרת: nonce || ciphertext, בלי לדעת איזה מפתח
+    check_unbound_allowed()?;
+    for (_, key) in ring.all() {
+        if let Ok(plain) = decrypt_raw(key.as_bytes(), &data, b"") {
+            return Ok(plain);
+        }
+    }
+    if !allow_legacy {
+        return Err("Decryption failed with every configured key (the old built-in key is off, see keys.allow_legacy_key)".into());
+    }
+    decrypt_raw(&LEGACY_KEY_BYTES, &data, b"")
+}
+
+/// הצפנה עם מפתח מסוים מה-key ring ולא עם הפעיל, כמו ערך שנכתב לפני החלפת המפתח
+#[cfg(test)]
+pub fn encrypt_with_key_id(key_id: u32, password: &str, aad: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    seal(key_id, key_ring()?.get(key_id)?.as_bytes(), password, aad)
+}
+
+// ===================== VAULT KEY =====================
+/// הצפנה עם מפתח כספת של משתמש
+pub fn encrypt_with_vault_key(key: &[u8; 32], password: &str, aad: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    seal(VAULT_KEY_ID, key, password, aad)
+}
+
+/// פענוח עם מפתח כספת. ערכים ישנים שעוד מוצפנים במפתח השרת מפוענחים כרגיל
+pub fn decrypt_with_vault_key(key: &[u8; 32], encoded: &str, aad: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let data = general_purpose::STANDARD.decode(encoded)?;
+    match envelope_header(&data) {
+        Some((version, VAULT_KEY_ID)) => open(key, version, &data[HEADER_LEN..], aad),
+        _ => decrypt_password(encoded, aad),
+    }
+}
+
+/// מפענח ערך של מפתח כספת רק אם הוא נפתח במפתח הזה. None בלי לרשום שגיאת integrity,
+/// כדי לנסות מפתח של כספת קודמת על כל הערכים של המשתמש
+pub fn open_with_vault_key(key: &[u8; 32], encoded: &str, aad: &[u8]) -> Option<String> {
+    let data = general_purpose::STANDARD.decode(encoded).ok()?;
+    match envelope_header(&data)? {
+        (ENVELOPE_VERSION, VAULT_KEY_ID) => decrypt_raw(key, &data[HEADER_LEN..], aad).ok(),
+        (_, VAULT_KEY_ID) if !bound_only() => decrypt_raw(key, &data[HEADER_LEN..], b"").ok(),
+        _ => None,
+    }
+}
+
+/// תנאי SQL לערך בגרסה 2 (קשור לשורה) בעמודה, בלי קשר למפתח. בודק את הכותרת בלי לשלוף את הערך:
+/// בייט ראשון 2 הוא 'A' ואחריו תו בין g ל-v ב-base64, ובאורך של כותרת, nonce ותג לפחות
+pub fn bound_value_sql(column: &str) -> String {
+    format!(
+        "({column} GLOB 'A[g-v]*' AND length({column}) * 3 / 4 - (length({column}) - length(rtrim({column}, '='))) >= {})",
+        HEADER_LEN + NONCE_LEN + TAG_LEN
+    )
+}
+
+/// האם ערך של מפתח השרת נפתח עם ה-AAD הזה. בודק בלי לרשום שגיאת integrity,
+/// כדי להבחין בין ערך קשור לבין ערך בגרסה 2 שנחתם עם AAD ריק (מפתח כספת עטוף ישן)
+pub fn opens_with(encoded: &str, aad: &[u8]) -> bool {
+    let Ok(data) = general_purpose::STANDARD.decode(encoded) else {
+        return false;
+    };
+    match (envelope_header(&data), key_ring()) {
+        (Some((ENVELOPE_VERSION, key_id)), Ok(ring)) => ring
+            .get(key_id)
+            .is_ok_and(|key| decrypt_raw(key.as_bytes(), &data[HEADER_LEN..], aad).is_ok()),
+        _ => false,
+    }
+}
+
+/// האם הערך מוצפן במפתח כספת וכבר קשור לשורה שלו
+pub fn is_bound_vault_value(encoded: &str) -> bool {
+    match general_purpose::STANDARD.decode(encoded) {
+        Ok(data) => envelope_header(&data) == Some((ENVELOPE_VERSION, VAULT_KEY_ID)),
+        Err(_) => false,
+    }
+}
+
+// ===================== ROTATION =====================
+/// האם הערך לא מוצפן עם המפתח הפעיל או לא קשור לשורה (ולכן צריך הצפנה מחדש).
+/// ערכים במפתח כספת לא שייכים ל-key ring ולכן לא נוגעים בהם
+pub fn needs_rewrap(encoded: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let data = general_purpose::STANDARD.decode(encoded)?;
+    Ok(match envelope_header(&data) {
+        Some((_, VAULT_KEY_ID)) => false,
+        Some((version, key_id)) => version != ENVELOPE_VERSION || key_id != key_ring()?.active_id(),
+        None => true,
+    })
+}
+
+/// מפענח ומצפין מחדש עם המפתח הפעיל ועם ה-AAD של השורה. None אם הערך כבר מעודכן.
+/// allow_legacy: גם ערכים של המפתח הקבוע הישן, כשההגדרה כבויה (keys migrate-legacy)
+pub fn rewrap(encoded: &str, aad: &[u8], allow_legacy: bool) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if !needs_rewrap(encoded)? {
+        return Ok(None);
+    }
+    let plain = decrypt_server(encoded, aad, allow_legacy || config::get().keys.allow_legacy_key)?;
+    Ok(Some(encrypt_password(&plain, aad)?))
+}
+
+// ===================== ENVELOPE =====================
+fn envelope_header(data: &[u8]) -> Option<(u8, u32)> {
+    let version = *data.first()?;
+    if data.len() < HEADER_LEN + NONCE_LEN + TAG_LEN
+        || (version != ENVELOPE_VERSION && version != ENVELOPE_VERSION_UNBOUND)
+    {
+        return None;
+    }
+    Some((version, u32::from_be_bytes([data[1], data[2], data[3], data[4]])))
+}
+
+// ===================== ROW BINDING =====================
+/// מכאן והלאה ערכים לא קשורים (גרסה 1, בלי כותרת, מפתח כספת עטוף בלי user_id) נדחים
+pub fn refuse_unbound() {
+    BOUND_ONLY.store(true, Ordering::SeqCst);
+}
+
+pub fn bound_only() -> bool {
+    BOUND_ONLY.load(Ordering::SeqCst)
+}
+
+fn check_unbound_allowed() -> Result<(), Box<dyn std::error::Error>> {
+    if bound_only() {
+        return Err("Refusing ciphertext that is not bound to its row".into());
+    }
+    Ok(())
+}
+
+fn open(key: &[u8; 32], version: u8, body: &[u8], aad: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    if version == ENVELOPE_VERSION {
+        return decrypt_raw(key, body, aad).map_err(|_| {
+            // מחוץ לבקשה (עבודת הצפנה מחדש, CLI) אין request id
+            let request_id = request_id::in_request().unwrap_or_else(|| "-".to_string());
+            log::error!(
+                "❌ [{}] Integrity check failed: ciphertext does not belong to {}",
+                request_id,
+                String::from_utf8_lossy(aad)
+            );
+            "Integrity check failed: ciphertext does not belong to this row".into()
+        });
+    }
+    check_unbound_allowed()?;
+    decrypt_raw(key, body, b"")
+}
+
+fn seal(key_id: u32, key: &[u8; 32], password: &str, aad: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let cipher = Aes256Gcm::new_from_slice(key)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let ciphertext = cipher.encrypt(&nonce, Payload { msg: password.as_bytes(), aad })
         .map_err(|e| format!("Encryption failed: {}", e))?;
 
-    let mut combined = nonce_bytes.to_vec();
+    let mut combined = vec![ENVELOPE_VERSION];
+    combined.extend(key_id.to_be_bytes());
+    combined.extend(nonce);
     combined.extend(ciphertext);
 
     Ok(general_purpose::STANDARD.encode(&combined))
 }
 
-pub fn decrypt_password(encoded: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let data = general_purpose::STANDARD.decode(encoded)?;
-    if data.len() < 12 { return Err("Data too short".into()); }
+fn decrypt_raw(key: &[u8; 32], data: &[u8], aad: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    if data.len() < NONCE_LEN { return Err("Data too short".into()); }
 
-    let (nonce_bytes, ciphertext) = data.split_at(12);
-    let nonce = GenericArray::from_slice(nonce_bytes);
+    let (nonce_bytes, ciphertext) = data.split_at(NONCE_LEN);
+    let nonce_bytes: [u8; NONCE_LEN] = nonce_bytes.try_into()?;
+    let nonce = Nonce::from(nonce_bytes);
 
-    let key = GenericArray::from_slice(&KEY_BYTES);
-    let cipher = Aes256Gcm::new(key);
+    let cipher = Aes256Gcm::new_from_slice(key)?;
 
-    let plaintext = cipher.decrypt(nonce, ciphertext)
+    let plaintext = cipher.decrypt(&nonce, Payload { msg: ciphertext, aad })
         .map_err(|e| format!("Decryption failed: {}", e))?;
 
     Ok(String::from_utf8(plaintext)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::utils::keys::{load_test_keys, TEST_RETIRED_KEY_ID};
+
+    const VAULT_KEY: [u8; 32] = [9; 32];
+
+    fn setup() {
+        let _ = config::init(Config::default());
+        load_test_keys();
+    }
+
+    /// ערך בגרסה 1 כמו שנכתב לפני ה-AAD: אותה מעטפה, בלי associated data
+    fn seal_unbound(key_id: u32, key: &[u8; 32], password: &str) -> String {
+        let mut data = general_purpose::STANDARD.decode(seal(key_id, key, password, b"").unwrap()).unwrap();
+        data[0] = ENVELOPE_VERSION_UNBOUND;
+        general_purpose::STANDARD.encode(data)
+    }
+
+    fn header(encoded: &str) -> Option<(u8, u32)> {
+        envelope_header(&general_purpose::STANDARD.decode(encoded).unwrap())
+    }
+
+    #[test]
+    fn server_key_round_trip_in_both_versions() {
+        setup();
+        let ring = key_ring().unwrap();
+        let aad = passwords_aad(1, 2, "a.com");
+
+        let bound = encrypt_password("p1", &aad).unwrap();
+        assert_eq!(header(&bound), Some((ENVELOPE_VERSION, ring.active_id())));
+        assert_eq!(decrypt_server(&bound, &aad, false).unwrap(), "p1");
+
+        let unbound = seal_unbound(ring.active_id(), ring.active().as_bytes(), "p1");
+        assert_eq!(header(&unbound), Some((ENVELOPE_VERSION_UNBOUND, ring.active_id())));
+        // לערך לא קשור אין AAD, לכן הוא נפתח בכל שורה
+        assert_eq!(decrypt_server(&unbound, &aad, false).unwrap(), "p1");
+        assert_eq!(decrypt_server(&unbound, b"", false).unwrap(), "p1");
+    }
+
+    #[test]
+    fn vault_key_round_trip_in_both_versions() {
+        setup();
+        let aad = entry_field_aad(1, 2, "a.com", "notes");
+
+        let bound = encrypt_with_vault_key(&VAULT_KEY, "n1", &aad).unwrap();
+        assert_eq!(header(&bound), Some((ENVELOPE_VERSION, VAULT_KEY_ID)));
+        assert!(is_bound_vault_value(&bound));
+        assert_eq!(decrypt_with_vault_key(&VAULT_KEY, &bound, &aad).unwrap(), "n1");
+        assert_eq!(open_with_vault_key(&VAULT_KEY, &bound, &aad).as_deref(), Some("n1"));
+        assert_eq!(open_with_vault_key(&[8; 32], &bound, &aad), None);
+
+        let unbound = seal_unbound(VAULT_KEY_ID, &VAULT_KEY, "n1");
+        assert!(!is_bound_vault_value(&unbound));
+        assert_eq!(decrypt_with_vault_key(&VAULT_KEY, &unbound, &aad).unwrap(), "n1");
+        // ערכים במפתח כספת לא שייכים ל-key ring
+        assert!(!needs_rewrap(&bound).unwrap() && !needs_rewrap(&unbound).unwrap());
+    }
+
+    #[test]
+    fn bound_value_is_rejected_in_another_row() {
+        setup();
+        let aad = passwords_aad(1, 2, "a.com");
+        let other_rows = [passwords_aad(3, 2, "a.com"), passwords_aad(1, 4, "a.com"), passwords_aad(1, 2, "b.com"), Vec::new()];
+
+        let server = encrypt_password("p1", &aad).unwrap();
+        let vault = encrypt_with_vault_key(&VAULT_KEY, "p1", &aad).unwrap();
+        for other in &other_rows {
+            assert!(decrypt_password(&server, other).is_err());
+            assert!(decrypt_server(&server, other, true).is_err(), "the legacy key must not be tried");
+            assert!(!opens_with(&server, other));
+            assert!(decrypt_with_vault_key(&VAULT_KEY, &vault, other).is_err());
+            assert_eq!(open_with_vault_key(&VAULT_KEY, &vault, other), None);
+        }
+        // השדות של אותה שורה לא מחליפים מקום
+        let notes = encrypt_with_vault_key(&VAULT_KEY, "n1", &entry_field_aad(1, 2, "a.com", "notes")).unwrap();
+        assert!(decrypt_with_vault_key(&VAULT_KEY, &notes, &entry_field_aad(1, 2, "a.com", "username")).is_err());
+        assert!(decrypt_with_vault_key(&VAULT_KEY, &notes, &aad).is_err());
+    }
+
+    #[test]
+    fn retired_key_still_decrypts_and_is_rewrapped_with_the_active_key() {
+        setup();
+        let active = key_ring().unwrap().active_id();
+        let aad = history_aad(5, 1);
+
+        let old = encrypt_with_key_id(TEST_RETIRED_KEY_ID, "p1", &aad).unwrap();
+        assert_eq!(header(&old), Some((ENVELOPE_VERSION, TEST_RETIRED_KEY_ID)));
+        assert_eq!(decrypt_password(&old, &aad).unwrap(), "p1");
+        assert!(needs_rewrap(&old).unwrap());
+
+        let new = rewrap(&old, &aad, false).unwrap().unwrap();
+        assert_eq!(header(&new), Some((ENVELOPE_VERSION, active)));
+        assert_eq!(decrypt_password(&new, &aad).unwrap(), "p1");
+        assert!(!needs_rewrap(&new).unwrap());
+        assert_eq!(rewrap(&new, &aad, false).unwrap(), None);
+
+        // ערך לא קשור במפתח הפעיל נקשר לשורה
+        let unbound = seal_unbound(active, key_ring().unwrap().active().as_bytes(), "p1");
+        assert!(needs_rewrap(&unbound).unwrap());
+        let bound = rewrap(&unbound, &aad, false).unwrap().unwrap();
+        assert_eq!(header(&bound), Some((ENVELOPE_VERSION, active)));
+        assert!(opens_with(&bound, &aad));
+    }
+
+    #[test]
+    fn unknown_key_and_short_values_are_not_envelopes() {
+        setup();
+        let aad = passwords_aad(1, 2, "a.com");
+        let mut data = general_purpose::STANDARD.decode(encrypt_password("p1", &aad).unwrap()).unwrap();
+        data[1..HEADER_LEN].copy_from_slice(&99u32.to_be_bytes());
+        assert!(decrypt_password(&general_purpose::STANDARD.encode(&data), &aad).is_err());
+
+        assert_eq!(envelope_header(&data[..HEADER_LEN + NONCE_LEN + TAG_LEN - 1]), None);
+        assert_eq!(envelope_header(&[3; 40]), None);
+        assert!(decrypt_password("not base64!", &aad).is_err());
+    }
+}
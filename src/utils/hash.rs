@@ -1,17 +1,220 @@
-use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use argon2::{Algorithm, Argon2, Params, ParamsBuilder, PasswordHasher, PasswordVerifier, Version};
 use argon2::password_hash::{SaltString, PasswordHash, Error};
+use base64::{engine::general_purpose, Engine as _};
 use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+
+use crate::config::{HashSettings, MEMORY_KIB_ENV, PEPPER_ENV, TIME_COST_ENV};
+
+// ה-pepper הוא סוד של השרת שנכנס לכל hash, כך שדליפה של ה-DB לבדה לא מספיקה לניחוש
+const MIN_PEPPER_LEN: usize = 16;
+// גבולות להצעה: לא פחות מהמינימום של OWASP, ולא כל כך הרבה שכמה התחברויות במקביל יגמרו את הזיכרון
+const MIN_SUGGESTED_MEMORY_KIB: u32 = 19 * 1024;
+const MAX_SUGGESTED_MEMORY_KIB: u32 = 1024 * 1024;
+
+static HASH_CONFIG: OnceLock<HashConfig> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum HashConfigError {
+    AlreadyInitialized,
+    Invalid(String),
+}
+
+impl fmt::Display for HashConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashConfigError::AlreadyInitialized => write!(f, "password hashing already initialized"),
+            HashConfigError::Invalid(msg) => write!(f, "invalid password hashing configuration: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for HashConfigError {}
+
+// ===================== CONFIG =====================
+pub struct HashConfig {
+    pub memory_kib: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    pepper: Option<Vec<u8>>,
+    // מזהה קצר של ה-pepper, נשמר בתוך ה-hash (keyid) כדי לדעת אם הוא נוצר איתו
+    pepper_id: Option<[u8; 4]>,
+}
+
+impl Default for HashConfig {
+    fn default() -> Self {
+        let defaults = HashSettings::default();
+        HashConfig {
+            memory_kib: defaults.memory_kib,
+            time_cost: defaults.time_cost,
+            parallelism: defaults.parallelism,
+            pepper: None,
+            pepper_id: None,
+        }
+    }
+}
+
+impl HashConfig {
+    fn params(&self) -> Result<Params, Error> {
+        let mut builder = ParamsBuilder::new();
+        builder.m_cost(self.memory_kib)?.t_cost(self.time_cost)?.p_cost(self.parallelism)?;
+        if let Some(id) = &self.pepper_id {
+            builder.keyid(id)?;
+        }
+        Ok(builder.params()?)
+    }
+
+    fn hasher(&self) -> Result<Argon2<'_>, Error> {
+        let params = self.params()?;
+        match &self.pepper {
+            Some(pepper) => Ok(Argon2::new_with_secret(pepper, Algorithm::Argon2id, Version::V0x13, params)?),
+            None => Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)),
+        }
+    }
+
+    pub fn has_pepper(&self) -> bool {
+        self.pepper.is_some()
+    }
+}
+
+/// בונה את הפרמטרים וה-pepper מההגדרות
+pub fn config_from_settings(settings: &HashSettings) -> Result<HashConfig, HashConfigError> {
+    let mut config = HashConfig {
+        memory_kib: settings.memory_kib,
+        time_cost: settings.time_cost,
+        parallelism: settings.parallelism,
+        pepper: None,
+        pepper_id: None,
+    };
+
+    if let Some(encoded) = &settings.pepper {
+        let pepper = general_purpose::STANDARD
+            .decode(encoded.expose().trim())
+            .map_err(|e| HashConfigError::Invalid(format!("{} is not valid base64: {}", PEPPER_ENV, e)))?;
+        if pepper.len() < MIN_PEPPER_LEN {
+            return Err(HashConfigError::Invalid(format!("{} must be at least {} bytes", PEPPER_ENV, MIN_PEPPER_LEN)));
+        }
+        let digest = Sha256::digest(&pepper);
+        config.pepper_id = Some([digest[0], digest[1], digest[2], digest[3]]);
+        config.pepper = Some(pepper);
+    }
 
+    // פרמטרים לא חוקיים נתפסים כאן ולא בהתחברות הראשונה
+    config.params().map_err(|e| HashConfigError::Invalid(e.to_string()))?;
+    Ok(config)
+}
+
+pub fn init_hasher(config: HashConfig) -> Result<(), HashConfigError> {
+    let summary = format!(
+        "Argon2id m={} KiB, t={}, p={}{}",
+        config.memory_kib,
+        config.time_cost,
+        config.parallelism,
+        if config.has_pepper() { ", with pepper" } else { "" }
+    );
+    HASH_CONFIG.set(config).map_err(|_| HashConfigError::AlreadyInitialized)?;
+    log::info!("🔒 Password hashing: {}", summary);
+    Ok(())
+}
+
+fn config() -> &'static HashConfig {
+    HASH_CONFIG.get_or_init(HashConfig::default)
+}
+
+// ===================== HASH / VERIFY =====================
 pub fn hash_password(password: &str) -> Result<String, Error> {
     let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let hash = argon2.hash_password(password.as_bytes(), &salt)?.to_string();
+    let hash = config().hasher()?.hash_password(password.as_bytes(), &salt)?.to_string();
     Ok(hash)
 }
 
 pub fn verify_password(password: &str, hash: &str) -> Result<bool, Error> {
     let parsed_hash = PasswordHash::new(hash)?;
-    let argon2 = Argon2::default();
+    let config = config();
 
+    // hash עם keyid נוצר עם pepper; hash ישן בלי keyid נבדק בלי pepper (ויעודכן בהתחברות)
+    let argon2 = match parsed_hash.params.get_str("keyid") {
+        Some(_) if hash_pepper_id(&parsed_hash) != config.pepper_id => return Ok(false),
+        Some(_) => config.hasher()?,
+        None => Argon2::default(),
+    };
     Ok(argon2.verify_password(password.as_bytes(), &parsed_hash).is_ok())
 }
+
+fn hash_pepper_id(hash: &PasswordHash) -> Option<[u8; 4]> {
+    let params = Params::try_from(hash).ok()?;
+    params.keyid().try_into().ok()
+}
+
+/// האם ה-hash נוצר עם אלגוריתם, פרמטרים או pepper שונים מהנוכחיים (ולכן כדאי לחשב אותו מחדש)
+pub fn needs_rehash(hash: &str) -> bool {
+    let Ok(parsed_hash) = PasswordHash::new(hash) else {
+        return true;
+    };
+    let Ok(params) = Params::try_from(&parsed_hash) else {
+        return true;
+    };
+    let config = config();
+
+    parsed_hash.algorithm != Algorithm::Argon2id.ident()
+        || parsed_hash.version != Some(Version::V0x13.into())
+        || params.m_cost() != config.memory_kib
+        || params.t_cost() != config.time_cost
+        || params.p_cost() != config.parallelism
+        || hash_pepper_id(&parsed_hash) != config.pepper_id
+}
+
+// ===================== BENCHMARK =====================
+/// מודד hash אחד עם הפרמטרים הנוכחיים ומציע פרמטרים שמגיעים לזמן היעד.
+/// רק מדפיס הצעה: שינוי הפרמטרים הוא החלטה של מי שמגדיר את השרת
+pub fn benchmark_on_startup(target_ms: u64) {
+    if target_ms == 0 {
+        return;
+    }
+
+    let config = config();
+    let started = Instant::now();
+    if let Err(e) = hash_password("benchmark password") {
+        log::warn!("⚠️ Password hashing benchmark failed: {}", e);
+        return;
+    }
+    let elapsed = started.elapsed();
+    let (memory_kib, time_cost) = suggest_params(config, elapsed, Duration::from_millis(target_ms));
+
+    log::info!("⏱️ One password hash takes {} ms (target {} ms)", elapsed.as_millis(), target_ms);
+    if memory_kib == MIN_SUGGESTED_MEMORY_KIB && elapsed > Duration::from_millis(target_ms) {
+        log::warn!("⏱️ This machine cannot reach the target with the minimum safe parameters");
+    }
+    if (memory_kib, time_cost) != (config.memory_kib, config.time_cost) {
+        log::info!(
+            "⏱️ Suggested: {}={} {}={} (existing hashes are upgraded on the next login)",
+            MEMORY_KIB_ENV, memory_kib, TIME_COST_ENV, time_cost
+        );
+    }
+}
+
+/// הזמן גדל בערך לינארית עם זיכרון × מעברים. מגדילים קודם את הזיכרון (מה שמקשה על GPU),
+/// ורק מעבר לגבול הזיכרון מוסיפים מעברים
+fn suggest_params(config: &HashConfig, elapsed: Duration, target: Duration) -> (u32, u32) {
+    let ratio = target.as_secs_f64() / elapsed.as_secs_f64().max(0.001);
+    // הפרש של פחות מ-20% לא שווה שינוי
+    if (0.8..=1.2).contains(&ratio) {
+        return (config.memory_kib, config.time_cost);
+    }
+
+    let work = config.memory_kib as f64 * config.time_cost as f64 * ratio;
+    let min_memory = (8 * config.parallelism).max(MIN_SUGGESTED_MEMORY_KIB);
+    let mut time_cost = config.time_cost.max(1);
+    let mut memory_kib = work / time_cost as f64;
+    if memory_kib > MAX_SUGGESTED_MEMORY_KIB as f64 {
+        time_cost = (work / MAX_SUGGESTED_MEMORY_KIB as f64).ceil() as u32;
+        memory_kib = work / time_cost as f64;
+    }
+    // מעגלים ל-MiB שלם
+    let memory_kib = ((memory_kib / 1024.0).round() as u32 * 1024).max(min_memory);
+    (memory_kib, time_cost)
+}
@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use rand_core::{OsRng, RngCore};
+
+use crate::config::{KeySettings, MASTER_KEY_ENV, MASTER_KEY_FILE_ENV, MASTER_PASSPHRASE_ENV};
+
+const SALT_LEN: usize = 16;
+
+static KEY_RING: OnceLock<KeyRing> = OnceLock::new();
+
+/// מפתח AES-256 שנטען פעם אחת בעליית השרת
+#[derive(Clone)]
+pub struct MasterKey([u8; 32]);
+
+impl MasterKey {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, KeyError> {
+        let key: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| KeyError::InvalidKey(format!("expected 32 bytes, got {}", bytes.len())))?;
+        Ok(MasterKey(key))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+// לא מדפיסים את המפתח עצמו ללוגים
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MasterKey(***)")
+    }
+}
+
+#[derive(Debug)]
+pub enum KeyError {
+    NotConfigured,
+    AlreadyInitialized,
+    InvalidKey(String),
+    UnknownKeyId(u32),
+    InsecurePermissions(PathBuf),
+    Io(PathBuf, std::io::Error),
+    Derivation(String),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::NotConfigured => write!(
+                f,
+                "no master key configured: set {}, {} or {}",
+                MASTER_KEY_ENV, MASTER_KEY_FILE_ENV, MASTER_PASSPHRASE_ENV
+            ),
+            KeyError::AlreadyInitialized => write!(f, "master key already initialized"),
+            KeyError::InvalidKey(msg) => write!(f, "invalid master key: {}", msg),
+            KeyError::UnknownKeyId(id) => write!(f, "no key with id {} in the key ring", id),
+            KeyError::InsecurePermissions(path) => write!(
+                f,
+                "key file {} must not be readable by group/others (chmod 600)",
+                path.display()
+            ),
+            KeyError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            KeyError::Derivation(msg) => write!(f, "key derivation failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+/// מקור שממנו אפשר לטעון את המפתח הראשי
+pub trait KeyProvider {
+    fn name(&self) -> &'static str;
+    fn load(&self) -> Result<MasterKey, KeyError>;
+}
+
+// ===================== ENV =====================
+/// מפתח של 32 בייט בקידוד base64 בתוך משתנה סביבה
+pub struct EnvKeyProvider {
+    pub value: String,
+}
+
+impl KeyProvider for EnvKeyProvider {
+    fn name(&self) -> &'static str {
+        "environment"
+    }
+
+    fn load(&self) -> Result<MasterKey, KeyError> {
+        decode_key(&self.value)
+    }
+}
+
+// ===================== KEY FILE =====================
+/// קובץ שמכיל את המפתח ב-base64, עם הרשאות 600 בלבד
+pub struct FileKeyProvider {
+    pub path: PathBuf,
+}
+
+impl KeyProvider for FileKeyProvider {
+    fn name(&self) -> &'static str {
+        "key file"
+    }
+
+    fn load(&self) -> Result<MasterKey, KeyError> {
+        check_permissions(&self.path)?;
+        let content = fs::read_to_string(&self.path).map_err(|e| KeyError::Io(self.path.clone(), e))?;
+        decode_key(&content)
+    }
+}
+
+// ===================== PASSPHRASE =====================
+/// מפתח שנגזר מסיסמה בעזרת Argon2id, עם salt ששמור בקובץ
+pub struct PassphraseKeyProvider {
+    pub passphrase: String,
+    pub salt_path: PathBuf,
+}
+
+impl KeyProvider for PassphraseKeyProvider {
+    fn name(&self) -> &'static str {
+        "passphrase"
+    }
+
+    fn load(&self) -> Result<MasterKey, KeyError> {
+        if self.passphrase.is_empty() {
+            return Err(KeyError::InvalidKey("passphrase is empty".into()));
+        }
+        let salt = load_or_create_salt(&self.salt_path)?;
+        let key = derive_key(self.passphrase.as_bytes(), &salt)?;
+        Ok(MasterKey(key))
+    }
+}
+
+/// Argon2id עם פרמטרים קבועים, כדי שאותה סיסמה תמיד תיתן אותו מפתח
+pub fn derive_key(secret: &[u8], salt: &[u8]) -> Result<[u8; 32], KeyError> {
+    let params = Params::new(64 * 1024, 3, 1, Some(32)).map_err(|e| KeyError::Derivation(e.to_string()))?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+    let mut out = [0u8; 32];
+    argon2
+        .hash_password_into(secret, salt, &mut out)
+        .map_err(|e| KeyError::Derivation(e.to_string()))?;
+    Ok(out)
+}
+
+fn load_or_create_salt(path: &Path) -> Result<Vec<u8>, KeyError> {
+    if path.exists() {
+        let content = fs::read_to_string(path).map_err(|e| KeyError::Io(path.to_path_buf(), e))?;
+        return general_purpose::STANDARD
+            .decode(content.trim())
+            .map_err(|e| KeyError::InvalidKey(format!("bad salt file: {}", e)));
+    }
+
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    write_private_file(path, &general_purpose::STANDARD.encode(salt))?;
+    log::info!("🔑 Created new master key salt at {}", path.display());
+    Ok(salt.to_vec())
+}
+
+fn decode_key(encoded: &str) -> Result<MasterKey, KeyError> {
+    let bytes = general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| KeyError::InvalidKey(format!("not valid base64: {}", e)))?;
+    MasterKey::from_bytes(&bytes)
+}
+
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> Result<(), KeyError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let meta = fs::metadata(path).map_err(|e| KeyError::Io(path.to_path_buf(), e))?;
+    if meta.permissions().mode() & 0o077 != 0 {
+        return Err(KeyError::InsecurePermissions(path.to_path_buf()));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(path: &Path) -> Result<(), KeyError> {
+    fs::metadata(path).map_err(|e| KeyError::Io(path.to_path_buf(), e))?;
+    Ok(())
+}
+
+fn write_private_file(path: &Path, content: &str) -> Result<(), KeyError> {
+    fs::write(path, content).map_err(|e| KeyError::Io(path.to_path_buf(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+            .map_err(|e| KeyError::Io(path.to_path_buf(), e))?;
+    }
+    Ok(())
+}
+
+// ===================== PROVIDER SELECTION =====================
+/// בחירת מקור המפתח לפי ההגדרות (לפי סדר עדיפות). אין מקור => שגיאה
+pub fn provider_from_config(settings: &KeySettings) -> Result<Box<dyn KeyProvider>, KeyError> {
+    if let Some(value) = &settings.master_key {
+        return Ok(Box::new(EnvKeyProvider { value: value.expose().to_string() }));
+    }
+    if let Some(path) = &settings.master_key_file {
+        return Ok(Box::new(FileKeyProvider { path: path.clone() }));
+    }
+    if let Some(passphrase) = &settings.passphrase {
+        return Ok(Box::new(PassphraseKeyProvider {
+            passphrase: passphrase.expose().to_string(),
+            salt_path: settings.salt_file.clone(),
+        }));
+    }
+    Err(KeyError::NotConfigured)
+}
+
+// ===================== KEY RING =====================
+/// כל המפתחות שהשרת מכיר: המפתח הפעיל מצפין, כל השאר רק מפענחים
+#[derive(Debug)]
+pub struct KeyRing {
+    active_id: u32,
+    keys: BTreeMap<u32, MasterKey>,
+}
+
+impl KeyRing {
+    pub fn new(active_id: u32, active: MasterKey) -> Self {
+        let mut keys = BTreeMap::new();
+        keys.insert(active_id, active);
+        KeyRing { active_id, keys }
+    }
+
+    pub fn add_retired(&mut self, id: u32, key: MasterKey) -> Result<(), KeyError> {
+        if self.keys.contains_key(&id) {
+            return Err(KeyError::InvalidKey(format!("duplicate key id {}", id)));
+        }
+        self.keys.insert(id, key);
+        Ok(())
+    }
+
+    pub fn active_id(&self) -> u32 {
+        self.active_id
+    }
+
+    pub fn active(&self) -> &MasterKey {
+        &self.keys[&self.active_id]
+    }
+
+    pub fn get(&self, id: u32) -> Result<&MasterKey, KeyError> {
+        self.keys.get(&id).ok_or(KeyError::UnknownKeyId(id))
+    }
+
+    /// המפתח הפעיל קודם, ואחריו כל הישנים
+    pub fn all(&self) -> impl Iterator<Item = (u32, &MasterKey)> {
+        std::iter::once((self.active_id, self.active()))
+            .chain(self.keys.iter().filter(|(id, _)| **id != self.active_id).map(|(id, key)| (*id, key)))
+    }
+}
+
+/// קובץ מפתחות ישנים: שורה לכל מפתח בפורמט id:base64
+fn load_retired_keys(path: &Path, ring: &mut KeyRing) -> Result<(), KeyError> {
+    check_permissions(path)?;
+    let content = fs::read_to_string(path).map_err(|e| KeyError::Io(path.to_path_buf(), e))?;
+
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let (id, key) = line
+            .split_once(':')
+            .ok_or_else(|| KeyError::InvalidKey(format!("bad retired key line in {}", path.display())))?;
+        let id = parse_key_id(id)?;
+        ring.add_retired(id, decode_key(key)?)?;
+    }
+    Ok(())
+}
+
+// מזהה 0 שמור למפתחות הכספת של המשתמשים
+fn parse_key_id(value: &str) -> Result<u32, KeyError> {
+    match value.trim().parse() {
+        Ok(0) | Err(_) => Err(KeyError::InvalidKey(format!("bad key id '{}' (must be 1 or more)", value))),
+        Ok(id) => Ok(id),
+    }
+}
+
+/// טוען את המפתח הפעיל מהספק ואת המפתחות הישנים, ושומר אותם לכל חיי התהליך
+pub fn init_key_ring(provider: &dyn KeyProvider, settings: &KeySettings) -> Result<(), KeyError> {
+    let active_id = settings.active_key_id;
+    let mut ring = KeyRing::new(active_id, provider.load()?);
+
+    if let Some(path) = &settings.retired_keys_file {
+        load_retired_keys(path, &mut ring)?;
+    }
+
+    let count = ring.keys.len();
+    KEY_RING.set(ring).map_err(|_| KeyError::AlreadyInitialized)?;
+    log::info!("🔑 Master key #{} loaded from {} ({} keys in ring)", active_id, provider.name(), count);
+    Ok(())
+}
+
+/// מפתח ישן בטבעת של הבדיקות, כדי לבדוק פענוח והצפנה מחדש אחרי החלפת מפתח
+#[cfg(test)]
+pub const TEST_RETIRED_KEY_ID: u32 = 7;
+
+/// מפתחות קבועים לבדיקות. נטענים פעם אחת לכל הבדיקות, הקריאות הבאות לא עושות כלום
+#[cfg(test)]
+pub fn load_test_keys() {
+    let active = decode_key("MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTIzNDU2Nzg5MDE=").unwrap();
+    let mut ring = KeyRing::new(KeySettings::default().active_key_id, active);
+    ring.add_retired(TEST_RETIRED_KEY_ID, MasterKey([7; 32])).unwrap();
+    let _ = KEY_RING.set(ring);
+}
+
+pub fn key_ring() -> Result<&'static KeyRing, KeyError> {
+    KEY_RING.get().ok_or(KeyError::NotConfigured)
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::Utc;
+
+use crate::config::MailSettings;
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub enum MailError {
+    InvalidConfig(String),
+    InvalidAddress(String),
+    Io(std::io::Error),
+    Smtp(String),
+}
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailError::InvalidConfig(msg) => write!(f, "invalid mail configuration: {}", msg),
+            MailError::InvalidAddress(addr) => write!(f, "invalid mail address: {}", addr),
+            MailError::Io(e) => write!(f, "mail I/O error: {}", e),
+            MailError::Smtp(reply) => write!(f, "SMTP server refused: {}", reply),
+        }
+    }
+}
+
+impl std::error::Error for MailError {}
+
+impl From<std::io::Error> for MailError {
+    fn from(e: std::io::Error) -> Self {
+        MailError::Io(e)
+    }
+}
+
+/// דרך לשלוח מייל. השליחה חוסמת, לכן קוראים לה מתוך web::block
+pub trait MailTransport: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn send(&self, mail: &Mail) -> Result<(), MailError>;
+}
+
+/// שולח ברקע, כך שזמן התשובה לא תלוי בשרת המייל (ולא מגלה אם החשבון קיים).
+/// כישלון נרשם ללוג בלבד
+pub fn send_in_background(transport: Arc<dyn MailTransport>, mail: Mail) {
+    actix_web::rt::spawn(async move {
+        let name = transport.name();
+        let to = mail.to.clone();
+        match web::block(move || transport.send(&mail)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::warn!("⚠️ Failed to send mail to {} via {}: {}", to, name, e),
+            Err(e) => log::warn!("⚠️ Failed to send mail to {} via {}: {}", to, name, e),
+        }
+    });
+}
+
+fn check_address(address: &str) -> Result<(), MailError> {
+    // כתובת עם שורה חדשה יכולה להזריק פקודות SMTP או כותרות
+    if address.is_empty() || address.contains(['\r', '\n', '<', '>']) {
+        return Err(MailError::InvalidAddress(address.to_string()));
+    }
+    Ok(())
+}
+
+fn format_message(from: &str, mail: &Mail) -> String {
+    let subject: String = mail.subject.chars().filter(|c| *c != '\r' && *c != '\n').collect();
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+        from,
+        mail.to,
+        subject,
+        Utc::now().to_rfc2822(),
+        mail.body.replace("\r\n", "\n").replace('\n', "\r\n")
+    )
+}
+
+// ===================== SMTP =====================
+/// SMTP בלי TLS ובלי הזדהות - מיועד לשרת מקומי (MailHog, Mailpit)
+pub struct SmtpTransport {
+    pub host: String,
+    pub port: u16,
+    pub from: String,
+}
+
+impl SmtpTransport {
+    fn expect(reader: &mut impl BufRead, code: &str) -> Result<(), MailError> {
+        // תשובה יכולה להיות כמה שורות: "250-..." ואז "250 ..."
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(MailError::Smtp("connection closed".to_string()));
+            }
+            if !line.starts_with(code) {
+                return Err(MailError::Smtp(line.trim_end().to_string()));
+            }
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    fn command(stream: &mut TcpStream, reader: &mut impl BufRead, command: &str, code: &str) -> Result<(), MailError> {
+        stream.write_all(command.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        Self::expect(reader, code)
+    }
+}
+
+impl MailTransport for SmtpTransport {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    fn send(&self, mail: &Mail) -> Result<(), MailError> {
+        check_address(&mail.to)?;
+
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(Some(SMTP_TIMEOUT))?;
+        stream.set_write_timeout(Some(SMTP_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        Self::expect(&mut reader, "220")?;
+        Self::command(&mut stream, &mut reader, "EHLO passmensystem", "250")?;
+        Self::command(&mut stream, &mut reader, &format!("MAIL FROM:<{}>", self.from), "250")?;
+        Self::command(&mut stream, &mut reader, &format!("RCPT TO:<{}>", mail.to), "250")?;
+        Self::command(&mut stream, &mut reader, "DATA", "354")?;
+
+        // שורה שמתחילה בנקודה מוכפלת (dot-stuffing), ונקודה לבד מסיימת את ההודעה
+        let message = format_message(&self.from, mail).replace("\r\n.", "\r\n..");
+        stream.write_all(message.as_bytes())?;
+        Self::command(&mut stream, &mut reader, ".", "250")?;
+        Self::command(&mut stream, &mut reader, "QUIT", "221")
+    }
+}
+
+// ===================== FILE =====================
+/// כותב כל מייל לקובץ .eml (לבדיקות ולפיתוח בלי שרת מייל)
+pub struct FileTransport {
+    pub dir: PathBuf,
+    pub from: String,
+}
+
+impl MailTransport for FileTransport {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn send(&self, mail: &Mail) -> Result<(), MailError> {
+        check_address(&mail.to)?;
+        fs::create_dir_all(&self.dir)?;
+
+        let file_name = format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%d%H%M%S%f"),
+            mail.to.replace(|c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '@', "_")
+        );
+        fs::write(self.dir.join(file_name), format_message(&self.from, mail))?;
+        Ok(())
+    }
+}
+
+// ===================== FROM CONFIG =====================
+/// בוחר transport לפי mail.transport (ברירת מחדל: file)
+pub fn transport_from_config(settings: &MailSettings) -> Result<Box<dyn MailTransport>, MailError> {
+    check_address(&settings.from)?;
+
+    match settings.transport.as_str() {
+        "smtp" => Ok(Box::new(SmtpTransport {
+            host: settings.smtp_host.clone(),
+            port: settings.smtp_port,
+            from: settings.from.clone(),
+        })),
+        "file" => Ok(Box::new(FileTransport { dir: settings.dir.clone(), from: settings.from.clone() })),
+        other => Err(MailError::InvalidConfig(format!("unknown mail transport '{}'", other))),
+    }
+}
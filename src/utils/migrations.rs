@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::sqlite::SqliteConnection;
+use sqlx::{ConnectOptions, Connection, Row};
+use crate::config::DatabaseSettings;
+use crate::utils::db::{connect_options, orphan_counts};
+
+// קבצי ה-SQL שבתיקייה migrations נכנסים לבינארי בזמן הקימפול
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// חיבור נפרד למיגרציות, עם foreign_keys=OFF: בנייה מחדש של טבלה (DROP + RENAME)
+/// הייתה מפעילה ON DELETE CASCADE על הטבלאות שמפנות אליה
+async fn connect(settings: &DatabaseSettings) -> Result<SqliteConnection, MigrateError> {
+    Ok(connect_options(settings)?.foreign_keys(false).connect().await?)
+}
+
+async fn applied_versions(conn: &mut SqliteConnection) -> Result<HashMap<i64, Vec<u8>>, MigrateError> {
+    conn.ensure_migrations_table().await?;
+    Ok(conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m.checksum.into_owned()))
+        .collect())
+}
+
+// ===================== ADOPTION =====================
+// 0002 תוקן אחרי שכבר רץ (מחיקת היסטוריה בלי password_id). על DB שהוא כבר רץ בו התיקון לא משנה כלום,
+// לכן ה-checksum הישן מתעדכן לחדש במקום שהשרת יסרב לעלות
+const REVISED_MIGRATIONS: &[(i64, &str)] = &[(
+    2,
+    "9a1907164ebf8f41d9f39b381644dcbca771f8f70f183f6d44da76cb08c61b2ea9e12203f57e0fce28e37bdff7c29e3b",
+)];
+
+// לפני 0006 השרת הוסיף את העמודות האלה ל-users בכל עלייה, כל אחת אם היא חסרה
+const STARTUP_COLUMNS_VERSION: i64 = 6;
+const STARTUP_COLUMNS: &[(&str, &str)] = &[
+    ("vault_key_salt", "TEXT"),
+    ("vault_key_wrapped", "TEXT"),
+    ("vault_key_escrow", "TEXT"),
+    ("totp_secret_encrypted", "TEXT"),
+    ("totp_enabled", "INTEGER NOT NULL DEFAULT 0"),
+    ("totp_last_step", "INTEGER"),
+    ("email_verified_at", "TIMESTAMP"),
+];
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn is_revised(version: i64, checksum: &[u8]) -> bool {
+    REVISED_MIGRATIONS.iter().any(|(v, old)| *v == version && hex(checksum) == *old)
+}
+
+async fn accept_revised_checksums(conn: &mut SqliteConnection, applied: &HashMap<i64, Vec<u8>>) -> Result<(), MigrateError> {
+    for (version, _) in REVISED_MIGRATIONS {
+        let Some(migration) = MIGRATOR.iter().find(|m| m.version == *version && m.migration_type.is_up_migration()) else {
+            continue;
+        };
+        if applied.get(version).is_some_and(|checksum| is_revised(*version, checksum)) {
+            sqlx::query("UPDATE _sqlx_migrations SET checksum = ? WHERE version = ?")
+                .bind(&*migration.checksum)
+                .bind(version)
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// DB שהשרת כבר הוסיף בו עמודות של 0006: ALTER TABLE ADD COLUMN נכשל על עמודה קיימת,
+/// לכן משלימים כאן את החסרות ורושמים את 0006 כאילו רצה
+async fn adopt_startup_columns(conn: &mut SqliteConnection, applied: &HashMap<i64, Vec<u8>>) -> Result<(), MigrateError> {
+    if applied.contains_key(&STARTUP_COLUMNS_VERSION) {
+        return Ok(());
+    }
+    let Some(migration) = MIGRATOR
+        .iter()
+        .find(|m| m.version == STARTUP_COLUMNS_VERSION && m.migration_type.is_up_migration())
+    else {
+        return Ok(());
+    };
+    let existing: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info('users')")
+        .fetch_all(&mut *conn)
+        .await?;
+    if !STARTUP_COLUMNS.iter().any(|(column, _)| existing.iter().any(|name| name == column)) {
+        return Ok(());
+    }
+
+    let mut tx = conn.begin().await?;
+    for (column, definition) in STARTUP_COLUMNS {
+        if existing.iter().any(|name| name == column) {
+            continue;
+        }
+        sqlx::query(&format!("ALTER TABLE users ADD COLUMN {} {}", column, definition))
+            .execute(&mut *tx)
+            .await?;
+        if *column == "email_verified_at" {
+            sqlx::query("UPDATE users SET email_verified_at = created_at").execute(&mut *tx).await?;
+        }
+    }
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+         VALUES (?, ?, TRUE, ?, 0)"
+    )
+    .bind(migration.version)
+    .bind(&*migration.description)
+    .bind(&*migration.checksum)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    log::info!("🗄️ Adopted migration {} {} (columns added at startup by an older build)", migration.version, migration.description);
+    Ok(())
+}
+
+/// 0002 מוחק שורות היסטוריה בלי password_id. כאן רק מדווחים כמה, לפני שזה קורה
+async fn report_unowned_history(conn: &mut SqliteConnection, applied: &HashMap<i64, Vec<u8>>) -> Result<(), MigrateError> {
+    let has_table = sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'password_history'")
+        .fetch_optional(&mut *conn)
+        .await?
+        .is_some();
+    if applied.contains_key(&2) || !has_table {
+        return Ok(());
+    }
+    let count: i64 = sqlx::query("SELECT COUNT(*) AS count FROM password_history WHERE password_id IS NULL")
+        .fetch_one(&mut *conn)
+        .await?
+        .get("count");
+    if count > 0 {
+        log::warn!("⚠️ Deleting {} password_history rows that belong to no password (password_id is NULL)", count);
+    }
+    Ok(())
+}
+
+// ===================== UP =====================
+/// מריץ את כל המיגרציות שעוד לא רצו (בעליית השרת ו-migrate up)
+pub async fn run_pending(settings: &DatabaseSettings) -> Result<(), MigrateError> {
+    let mut conn = connect(settings).await?;
+    let adopted = applied_versions(&mut conn).await?;
+    accept_revised_checksums(&mut conn, &adopted).await?;
+    report_unowned_history(&mut conn, &adopted).await?;
+    adopt_startup_columns(&mut conn, &adopted).await?;
+    let applied = applied_versions(&mut conn).await?;
+
+    MIGRATOR.run(&mut conn).await?;
+    for migration in MIGRATOR.iter().filter(|m| m.migration_type.is_up_migration()) {
+        if !applied.contains_key(&migration.version) {
+            log::info!("🗄️ Applied migration {} {}", migration.version, migration.description);
+        }
+    }
+
+    warn_foreign_key_violations(&mut conn).await?;
+    conn.close().await?;
+    Ok(())
+}
+
+/// שורות ישנות שמפנות לשורה שכבר לא קיימת לא נמחקות כאן, רק מדווחות (db repair מוחק אותן)
+async fn warn_foreign_key_violations(conn: &mut SqliteConnection) -> Result<(), MigrateError> {
+    for (table, count) in orphan_counts(conn).await? {
+        log::warn!("⚠️ {} rows in {} reference a row that no longer exists, run `db repair`", count, table);
+    }
+    Ok(())
+}
+
+// ===================== DOWN =====================
+/// מבטל את המיגרציה האחרונה שרצה. None אם אין מה לבטל
+pub async fn revert_last(settings: &DatabaseSettings) -> Result<Option<i64>, MigrateError> {
+    let mut conn = connect(settings).await?;
+    let applied = applied_versions(&mut conn).await?;
+
+    let mut versions: Vec<i64> = applied.into_keys().collect();
+    versions.sort_unstable();
+    let Some(last) = versions.pop() else {
+        return Ok(None);
+    };
+    let target = versions.last().copied().unwrap_or(0);
+
+    MIGRATOR.undo(&mut conn, target).await?;
+    conn.close().await?;
+    Ok(Some(last))
+}
+
+// ===================== STATUS =====================
+/// מדפיס לכל מיגרציה אם היא רצה, ממתינה, או שהקובץ השתנה מאז שרצה
+pub async fn print_status(settings: &DatabaseSettings) -> Result<(), MigrateError> {
+    let mut conn = connect(settings).await?;
+    let applied = applied_versions(&mut conn).await?;
+    let dirty = conn.dirty_version().await?;
+
+    for migration in MIGRATOR.iter().filter(|m| m.migration_type.is_up_migration()) {
+        let state = match applied.get(&migration.version) {
+            _ if dirty == Some(migration.version) => "failed (fix the database and rerun)",
+            Some(checksum) if checksum.as_slice() != &*migration.checksum && !is_revised(migration.version, checksum) => {
+                "applied, file changed since"
+            }
+            Some(_) => "applied",
+            None => "pending",
+        };
+        println!("{:>4}  {:<40} {}", migration.version, migration.description, state);
+    }
+
+    // DB שעבר מיגרציה עם בינארי חדש יותר
+    for version in applied.keys() {
+        if MIGRATOR.iter().all(|m| m.version != *version) {
+            println!("{:>4}  {:<40} applied, unknown to this build", version, "?");
+        }
+    }
+
+    conn.close().await?;
+    Ok(())
+}
@@ -1,2 +1,14 @@
 pub mod hash;
-pub mod encryption;
\ No newline at end of file
+pub mod encryption;
+pub mod keys;
+pub mod vault;
+pub mod db;
+pub mod tokens;
+pub mod totp;
+pub mod challenges;
+pub mod mailer;
+pub mod email;
+pub mod password_policy;pub mod migrations;
+pub mod validation;
+pub mod pagination;
+pub mod search;
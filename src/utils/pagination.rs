@@ -0,0 +1,423 @@
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::http::header::HeaderName;
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::NaiveDateTime;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, QueryBuilder, Sqlite, SqlitePool};
+
+use crate::errors::{ApiError, ErrorCode};
+use crate::utils::validation::Validator;
+
+pub const DEFAULT_LIMIT: usize = 50;
+pub const MAX_LIMIT: usize = 200;
+
+// כמה שורות מתאימות לפילטרים (בלי קשר לעמוד), והמקום של העמוד הבא
+pub const TOTAL_COUNT_HEADER: HeaderName = HeaderName::from_static("x-total-count");
+pub const NEXT_CURSOR_HEADER: HeaderName = HeaderName::from_static("x-next-cursor");
+
+// ===================== SORT & CURSOR =====================
+/// מפתח מיון מתוך SORT_KEYS של הרשימה. "-key" בבקשה = מהגדול לקטן
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub key: &'static str,
+    pub descending: bool,
+}
+
+impl Sort {
+    fn parse(raw: &str, keys: &[&'static str]) -> Option<Sort> {
+        let (name, descending) = match raw.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (raw, false),
+        };
+        keys.iter().find(|key| **key == name).map(|key| Sort { key, descending })
+    }
+
+    fn token(&self) -> String {
+        if self.descending { format!("-{}", self.key) } else { self.key.to_string() }
+    }
+}
+
+/// הערך של עמודת המיון בשורה האחרונה של העמוד
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CursorValue {
+    Int(i64),
+    Text(String),
+    Time(NaiveDateTime),
+}
+
+/// שורה שאפשר לדפדף אחריה: ערך המיון ומזהה ייחודי שמכריע בין שורות עם אותו ערך
+pub trait Sortable {
+    fn position(&self, key: &str) -> (CursorValue, i64);
+}
+
+/// המקום שבו העמוד הקודם נגמר. ללקוח הוא מחרוזת אטומה
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    pub value: CursorValue,
+    pub id: i64,
+}
+
+// "sort|id|tag|value" ב-base64. הערך אחרון, כך שגם | בתוכו לא מפריע
+fn encode_cursor(sort: Sort, cursor: &Cursor) -> String {
+    let (tag, value) = match &cursor.value {
+        CursorValue::Int(v) => ("i", v.to_string()),
+        CursorValue::Text(v) => ("s", v.clone()),
+        CursorValue::Time(v) => ("t", v.format("%Y-%m-%dT%H:%M:%S%.f").to_string()),
+    };
+    let raw = format!("{}|{}|{}|{}", sort.token(), cursor.id, tag, value);
+    general_purpose::URL_SAFE_NO_PAD.encode(raw)
+}
+
+fn decode_cursor(encoded: &str, sort: Sort) -> Result<Cursor, &'static str> {
+    let invalid = "Invalid cursor";
+    let bytes = general_purpose::URL_SAFE_NO_PAD.decode(encoded).map_err(|_| invalid)?;
+    let raw = String::from_utf8(bytes).map_err(|_| invalid)?;
+    let mut parts = raw.splitn(4, '|');
+    let (Some(token), Some(id), Some(tag), Some(value)) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid);
+    };
+    if token != sort.token() {
+        return Err("Cursor was issued for a different sort");
+    }
+    let id = id.parse().map_err(|_| invalid)?;
+    let value = match tag {
+        "i" => CursorValue::Int(value.parse().map_err(|_| invalid)?),
+        "s" => CursorValue::Text(value.to_string()),
+        "t" => CursorValue::Time(value.parse().map_err(|_| invalid)?),
+        _ => return Err(invalid),
+    };
+    Ok(Cursor { value, id })
+}
+
+// ===================== PAGE =====================
+/// עמוד מבוקש: עד limit שורות אחרי after, לפי sort
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub limit: usize,
+    pub sort: Sort,
+    pub after: Option<Cursor>,
+}
+
+/// עמוד של תוצאות. total סופר את כל השורות שמתאימות לפילטרים
+#[derive(Debug)]
+pub struct Paged<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub next: Option<String>,
+}
+
+impl Page {
+    /// rows הן עד limit + 1 שורות אחרי ה-cursor, כבר ממוינות. השורה העודפת רק אומרת שיש עוד עמוד
+    pub fn finish<T: Sortable>(&self, mut rows: Vec<T>, total: i64) -> Paged<T> {
+        let next = if rows.len() > self.limit {
+            rows.truncate(self.limit);
+            rows.last().map(|last| {
+                let (value, id) = last.position(self.sort.key);
+                encode_cursor(self.sort, &Cursor { value, id })
+            })
+        } else {
+            None
+        };
+        Paged { items: rows, total, next }
+    }
+
+    /// אותו דבר בזיכרון (ל-MemoryStore): rows הן כל השורות שעברו את הפילטרים
+    #[cfg(test)]
+    pub fn apply<T: Sortable>(&self, rows: Vec<T>) -> Paged<T> {
+        let total = rows.len() as i64;
+        let mut keyed: Vec<((CursorValue, i64), T)> = rows.into_iter().map(|row| (row.position(self.sort.key), row)).collect();
+        keyed.sort_by(|(a, _), (b, _)| if self.sort.descending { b.cmp(a) } else { a.cmp(b) });
+
+        let rows = keyed
+            .into_iter()
+            .filter(|(position, _)| match &self.after {
+                None => true,
+                Some(after) => {
+                    let after = (after.value.clone(), after.id);
+                    if self.sort.descending { *position < after } else { *position > after }
+                }
+            })
+            .take(self.limit + 1)
+            .map(|(_, row)| row)
+            .collect();
+        self.finish(rows, total)
+    }
+
+    /// מוסיף ל-WHERE את התנאי "אחרי ה-cursor" (אם יש), ואז ORDER BY ו-LIMIT.
+    /// column ו-tie הם שמות עמודות קבועים מהקוד, לא מהבקשה
+    pub fn push_sql(&self, query: &mut QueryBuilder<'_, Sqlite>, column: &str, tie: &str) {
+        let (op, direction) = if self.sort.descending { ("<", "DESC") } else { (">", "ASC") };
+        if let Some(after) = &self.after {
+            query.push(format!(" AND ({} {} ", column, op));
+            push_value(query, &after.value);
+            query.push(format!(" OR ({} = ", column));
+            push_value(query, &after.value);
+            query.push(format!(" AND {} {} ", tie, op));
+            query.push_bind(after.id);
+            query.push("))");
+        }
+        query.push(format!(" ORDER BY {} {}, {} {} LIMIT ", column, direction, tie, direction));
+        query.push_bind((self.limit + 1) as i64);
+    }
+}
+
+/// COUNT ועמוד אחד מאותו FROM ואותם פילטרים. push_filters מוסיף תנאים שמתחילים ב-" AND"
+pub async fn fetch_page<T>(
+    pool: &SqlitePool,
+    select: &str,
+    from: &str,
+    push_filters: impl Fn(&mut QueryBuilder<'_, Sqlite>),
+    page: &Page,
+    column: &str,
+    tie: &str,
+) -> Result<Paged<T>, sqlx::Error>
+where
+    T: for<'r> FromRow<'r, SqliteRow> + Sortable + Send + Unpin,
+{
+    let mut count = QueryBuilder::new(format!("SELECT COUNT(*) {} WHERE 1 = 1", from));
+    push_filters(&mut count);
+    let total: i64 = count.build_query_scalar().fetch_one(pool).await?;
+
+    let mut query = QueryBuilder::new(format!("{} {} WHERE 1 = 1", select, from));
+    push_filters(&mut query);
+    page.push_sql(&mut query, column, tie);
+    let rows = query.build_query_as::<T>().fetch_all(pool).await?;
+    Ok(page.finish(rows, total))
+}
+
+fn push_value(query: &mut QueryBuilder<'_, Sqlite>, value: &CursorValue) {
+    match value {
+        CursorValue::Int(v) => query.push_bind(*v),
+        CursorValue::Text(v) => query.push_bind(v.clone()),
+        CursorValue::Time(v) => query.push_bind(*v),
+    };
+}
+
+// ===================== EXTRACTOR =====================
+/// הפילטרים של רשימה אחת (שדות ה-query שאינם limit/cursor/sort) ומפתחות המיון שלה
+pub trait ListFilters: DeserializeOwned {
+    const SORT_KEYS: &'static [&'static str];
+    /// למשל "-changed_at"
+    const DEFAULT_SORT: &'static str;
+}
+
+#[derive(Deserialize)]
+struct PageParams {
+    limit: Option<usize>,
+    cursor: Option<String>,
+    sort: Option<String>,
+}
+
+/// `?limit=&cursor=&sort=` ועוד הפילטרים של F, אותו דבר בכל נקודת רשימה
+pub struct ListQuery<F> {
+    pub page: Page,
+    pub filters: F,
+}
+
+impl<F: ListFilters> ListQuery<F> {
+    fn parse(query: &str) -> Result<Self, ApiError> {
+        let malformed = |e: actix_web::error::QueryPayloadError| ApiError::new(ErrorCode::MalformedRequest, e.to_string());
+        let params = web::Query::<PageParams>::from_query(query).map_err(malformed)?.into_inner();
+        let filters = web::Query::<F>::from_query(query).map_err(malformed)?.into_inner();
+
+        let mut v = Validator::new();
+        let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            v.error("limit", format!("Must be between 1 and {}", MAX_LIMIT));
+        }
+
+        let default_sort = Sort::parse(F::DEFAULT_SORT, F::SORT_KEYS).expect("DEFAULT_SORT is one of SORT_KEYS");
+        let sort = match params.sort.as_deref() {
+            None => default_sort,
+            Some(raw) => Sort::parse(raw, F::SORT_KEYS).unwrap_or_else(|| {
+                v.error("sort", format!("Must be one of: {} (prefix with - for descending)", F::SORT_KEYS.join(", ")));
+                default_sort
+            }),
+        };
+
+        let after = match params.cursor.as_deref() {
+            None => None,
+            Some(raw) => decode_cursor(raw, sort).map_err(|msg| v.error("cursor", msg)).ok(),
+        };
+        v.finish()?;
+
+        Ok(ListQuery { page: Page { limit, sort, after }, filters })
+    }
+}
+
+impl<F: ListFilters> FromRequest for ListQuery<F> {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Self::parse(req.query_string()))
+    }
+}
+
+// ===================== RESPONSE =====================
+/// הגוף נשאר מערך JSON כמו קודם; הספירה וה-cursor הבא בכותרות
+pub fn paged_response<T, U: Serialize>(page: Paged<T>, map: impl FnMut(T) -> U) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    response.insert_header((TOTAL_COUNT_HEADER, page.total.to_string()));
+    if let Some(next) = &page.next {
+        response.insert_header((NEXT_CURSOR_HEADER, next.clone()));
+    }
+    response.json(page.items.into_iter().map(map).collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::ResponseError;
+    use crate::utils::db::test_pool;
+
+    #[derive(Debug, Clone, FromRow)]
+    struct Row {
+        id: i64,
+        score: i64,
+        name: String,
+    }
+
+    impl Sortable for Row {
+        fn position(&self, key: &str) -> (CursorValue, i64) {
+            let value = match key {
+                "score" => CursorValue::Int(self.score),
+                "name" => CursorValue::Text(self.name.clone()),
+                _ => CursorValue::Int(self.id),
+            };
+            (value, self.id)
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct RowFilter {}
+
+    impl ListFilters for RowFilter {
+        const SORT_KEYS: &'static [&'static str] = &["id", "score", "name"];
+        const DEFAULT_SORT: &'static str = "id";
+    }
+
+    // רוב השורות חולקות ערך מיון עם שורות אחרות, כולל רצף שארוך מעמוד
+    fn rows() -> Vec<Row> {
+        let scores = [3, 1, 3, 2, 3, 1, 3, 3, 2, 1, 3];
+        let names = ["b", "a|b", "b", "a", "c", "a|b", "b", "b", "a", "c", "b"];
+        scores
+            .iter()
+            .zip(names)
+            .enumerate()
+            .map(|(i, (score, name))| Row { id: i as i64 + 1, score: *score, name: name.to_string() })
+            .collect()
+    }
+
+    fn expected_order(sort: Sort) -> Vec<i64> {
+        let mut rows: Vec<((CursorValue, i64), i64)> = rows().into_iter().map(|row| (row.position(sort.key), row.id)).collect();
+        rows.sort();
+        if sort.descending {
+            rows.reverse();
+        }
+        rows.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// עובר על כל העמודים עם ה-cursor שכל עמוד מחזיר, כמו שלקוח עושה
+    async fn walk(pool: &SqlitePool, sort: Sort, limit: usize) -> Vec<i64> {
+        let mut ids = Vec::new();
+        let mut after = None;
+        loop {
+            let page = Page { limit, sort, after };
+            let paged: Paged<Row> = fetch_page(pool, "SELECT *", "FROM pages", |_| {}, &page, sort.key, "id").await.unwrap();
+            assert_eq!(paged.total, rows().len() as i64);
+            assert!(paged.items.len() <= limit);
+
+            let in_memory = page.apply(rows());
+            let memory_ids: Vec<i64> = in_memory.items.iter().map(|row| row.id).collect();
+            assert_eq!(paged.items.iter().map(|row| row.id).collect::<Vec<_>>(), memory_ids, "{:?}", sort);
+            assert_eq!(paged.next, in_memory.next, "{:?}", sort);
+
+            ids.extend(paged.items.iter().map(|row| row.id));
+            let Some(next) = paged.next else {
+                return ids;
+            };
+            after = Some(decode_cursor(&next, sort).unwrap());
+        }
+    }
+
+    #[actix_web::test]
+    async fn pages_through_duplicate_sort_values_without_skipping_or_repeating() {
+        let pool = test_pool("pagination-duplicates").await;
+        sqlx::query("CREATE TABLE pages (id INTEGER PRIMARY KEY, score INTEGER NOT NULL, name TEXT NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        for row in rows() {
+            sqlx::query("INSERT INTO pages (id, score, name) VALUES (?, ?, ?)")
+                .bind(row.id)
+                .bind(row.score)
+                .bind(&row.name)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        for key in ["id", "score", "name"] {
+            for descending in [false, true] {
+                let sort = Sort { key, descending };
+                let expected = expected_order(sort);
+                for limit in [1, 2, 3, 4, rows().len(), MAX_LIMIT] {
+                    assert_eq!(walk(&pool, sort, limit).await, expected, "{:?} limit {}", sort, limit);
+                }
+            }
+        }
+    }
+
+    fn parse(query: &str) -> Result<ListQuery<RowFilter>, ApiError> {
+        ListQuery::<RowFilter>::parse(query)
+    }
+
+    #[test]
+    fn cursors_round_trip_every_value_type() {
+        let time = NaiveDateTime::parse_from_str("2024-02-29 13:45:10.123456", "%Y-%m-%d %H:%M:%S%.f").unwrap();
+        let sort = Sort { key: "name", descending: true };
+        for value in [CursorValue::Int(-5), CursorValue::Text("a|b||".to_string()), CursorValue::Time(time)] {
+            let encoded = encode_cursor(sort, &Cursor { value: value.clone(), id: 42 });
+            let query = parse(&format!("sort=-name&cursor={}", encoded)).unwrap();
+            let after = query.page.after.unwrap();
+            assert_eq!((after.value, after.id), (value, 42));
+        }
+    }
+
+    #[test]
+    fn tampered_cursors_are_a_bad_request() {
+        let sort = Sort { key: "score", descending: false };
+        let valid = encode_cursor(sort, &Cursor { value: CursorValue::Int(3), id: 7 });
+        assert!(parse(&format!("sort=score&cursor={}", valid)).is_ok());
+
+        let encode = |raw: &str| general_purpose::URL_SAFE_NO_PAD.encode(raw);
+        let mut flipped = valid.clone().into_bytes();
+        flipped[0] ^= 0x01;
+        let tampered = [
+            // cursor של מיון אחר
+            format!("sort=-score&cursor={}", valid),
+            format!("sort=name&cursor={}", valid),
+            // לא base64, או base64 של משהו אחר
+            "sort=score&cursor=not%20base64!".to_string(),
+            format!("sort=score&cursor={}", String::from_utf8(flipped).unwrap()),
+            format!("sort=score&cursor={}", &valid[..valid.len() - 2]),
+            format!("sort=score&cursor={}", general_purpose::URL_SAFE_NO_PAD.encode([0xff, 0xfe])),
+            format!("sort=score&cursor={}", encode("score|7|i")),
+            format!("sort=score&cursor={}", encode("score|seven|i|3")),
+            format!("sort=score&cursor={}", encode("score|7|i|three")),
+            format!("sort=score&cursor={}", encode("score|7|x|3")),
+            format!("sort=score&cursor={}", encode("score|7|t|yesterday")),
+            "sort=score&cursor=".to_string(),
+        ];
+        for query in tampered {
+            let error = parse(&query).err().unwrap_or_else(|| panic!("{} was accepted", query));
+            assert_eq!(error.status_code(), actix_web::http::StatusCode::BAD_REQUEST, "{}", query);
+        }
+    }
+}
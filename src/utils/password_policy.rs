@@ -0,0 +1,43 @@
+use std::collections::HashSet;
+
+// סיסמת ההתחברות היא גם הסיסמה הראשית של הכספת, לכן הדרישות מחמירות
+pub const MIN_PASSWORD_LEN: usize = 12;
+pub const MAX_PASSWORD_LEN: usize = 128;
+// מתוך: אותיות קטנות, אותיות גדולות, ספרות, סימנים
+const MIN_CHARACTER_CLASSES: usize = 3;
+
+/// בודק סיסמה ראשית חדשה. מחזיר את כל הדרישות שלא התקיימו, כדי שהלקוח יציג אותן יחד
+pub fn check_master_password(password: &str, email: &str) -> Result<(), Vec<String>> {
+    let mut problems = Vec::new();
+    let length = password.chars().count();
+
+    if length < MIN_PASSWORD_LEN {
+        problems.push(format!("Password must be at least {} characters long", MIN_PASSWORD_LEN));
+    }
+    if length > MAX_PASSWORD_LEN {
+        problems.push(format!("Password must be at most {} characters long", MAX_PASSWORD_LEN));
+    }
+
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_ascii_digit()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ];
+    if classes.iter().filter(|present| **present).count() < MIN_CHARACTER_CLASSES {
+        problems.push("Password must mix at least three of: lowercase, uppercase, digits, symbols".to_string());
+    }
+
+    // סיסמה שמבוססת על כתובת המייל קלה לניחוש
+    let lowered = password.to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default().to_lowercase();
+    if local_part.len() >= 3 && lowered.contains(&local_part) {
+        problems.push("Password must not contain your email address".to_string());
+    }
+
+    if password.chars().collect::<HashSet<_>>().len() < 5 {
+        problems.push("Password must not repeat the same few characters".to_string());
+    }
+
+    if problems.is_empty() { Ok(()) } else { Err(problems) }
+}
@@ -0,0 +1,56 @@
+use std::collections::BTreeSet;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::SqliteConnection;
+use crate::utils::vault::VaultKey;
+
+// מפתח נפרד למזהים, שנגזר ממפתח הכספת, כדי שה-HMAC לא ישתמש במפתח ההצפנה עצמו
+const TOKEN_KEY_LABEL: &[u8] = b"password_search_tokens";
+// 16 בייט מספיקים כדי ששתי מילים לא יקבלו אותו מזהה
+const TOKEN_LEN: usize = 16;
+
+/// מילים כמו שה-tokenizer של password_search (unicode61) מפרק אותן: אותיות וספרות, באותיות קטנות
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// המזהה העיוור של מילה אחת בכספת הזאת. אותה מילה בכספות שונות נותנת מזהים שונים
+pub fn blind_token(vault_key: &VaultKey, word: &str) -> String {
+    let key = hmac(vault_key.as_bytes(), TOKEN_KEY_LABEL);
+    hmac(&key, word.as_bytes())[..TOKEN_LEN].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// המזהים של כל המילים בטקסט, בלי כפילויות
+pub fn blind_tokens(vault_key: &VaultKey, text: &str) -> BTreeSet<String> {
+    words(text).map(|word| blind_token(vault_key, &word)).collect()
+}
+
+/// מחליף את המזהים של ה-username של רשומה, בתוך הטרנזקציה של מי שקרא לה
+pub async fn replace_username_tokens(
+    conn: &mut SqliteConnection,
+    vault_key: &VaultKey,
+    password_id: i64,
+    username: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM password_search_tokens WHERE password_id = ?")
+        .bind(password_id)
+        .execute(&mut *conn)
+        .await?;
+    for token in blind_tokens(vault_key, username.unwrap_or_default()) {
+        sqlx::query("INSERT INTO password_search_tokens (password_id, token) VALUES (?, ?)")
+            .bind(password_id)
+            .bind(token)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
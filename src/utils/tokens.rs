@@ -0,0 +1,16 @@
+use base64::{engine::general_purpose, Engine as _};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// token אקראי של 32 בייט, בטוח לשימוש ב-URL ובכותרות
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// ב-DB נשמר רק ה-hash של ה-token, כך שדליפה של הטבלה לא נותנת גישה
+pub fn hash_token(token: &str) -> String {
+    let digest = Sha256::digest(token.as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
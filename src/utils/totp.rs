@@ -0,0 +1,232 @@
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use sha1::Sha1;
+use subtle::ConstantTimeEq;
+
+// RFC 6238 עם ברירות המחדל שכל אפליקציות ה-authenticator תומכות בהן
+pub const TOTP_STEP_SECONDS: i64 = 30;
+pub const TOTP_DIGITS: u32 = 6;
+pub const TOTP_ISSUER: &str = "PassMenSystem";
+const SECRET_LEN: usize = 20;
+// מקבלים גם את הקוד הקודם והבא בגלל הפרשי שעונים
+const ALLOWED_DRIFT: i64 = 1;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// ===================== SECRET =====================
+/// סוד חדש, בקידוד base32 כמו שאפליקציות ה-authenticator מצפות
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_LEN];
+    OsRng.fill_bytes(&mut secret);
+    base32_encode(&secret)
+}
+
+/// כתובת otpauth:// להצגה כ-QR
+pub fn otpauth_uri(account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        uri_encode(TOTP_ISSUER),
+        uri_encode(account),
+        secret,
+        uri_encode(TOTP_ISSUER),
+        TOTP_DIGITS,
+        TOTP_STEP_SECONDS
+    )
+}
+
+// ===================== VERIFY =====================
+/// בודק קוד מול הסוד. מחזיר את ה-step שהתאים, כדי שאפשר יהיה לסרב לשימוש חוזר
+/// באותו קוד (step שלא גדול מ-last_step נדחה)
+pub fn verify_code(secret: &str, code: &str, unix_time: i64, last_step: Option<i64>) -> Option<i64> {
+    let key = base32_decode(secret)?;
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let current = unix_time.div_euclid(TOTP_STEP_SECONDS);
+    (current - ALLOWED_DRIFT..=current + ALLOWED_DRIFT)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| {
+            let expected = format!("{:0width$}", code_at(&key, *step), width = TOTP_DIGITS as usize);
+            bool::from(expected.as_bytes().ct_eq(code.as_bytes()))
+        })
+}
+
+fn code_at(key: &[u8], step: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(&(step as u64).to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // dynamic truncation (RFC 4226 סעיף 5.3)
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+    binary % 10u32.pow(TOTP_DIGITS)
+}
+
+// ===================== RECOVERY CODES =====================
+/// קודי שחזור חד-פעמיים בפורמט xxxxx-xxxxx. נשמרים ב-DB רק כ-hash
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; RECOVERY_CODE_LEN];
+            OsRng.fill_bytes(&mut bytes);
+            let chars: String = bytes
+                .iter()
+                .map(|b| BASE32_ALPHABET[(*b & 0x1f) as usize].to_ascii_lowercase() as char)
+                .collect();
+            format!("{}-{}", &chars[..RECOVERY_CODE_LEN / 2], &chars[RECOVERY_CODE_LEN / 2..])
+        })
+        .collect()
+}
+
+/// הצורה שממנה נשמר ה-hash (xxxxx-xxxxx), בלי תלות ברווחים, במקפים ובאותיות גדולות.
+/// משמש גם ביצירת הקודים וגם בבדיקה שלהם
+pub fn normalize_recovery_code(code: &str) -> String {
+    let chars: String = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if chars.len() != RECOVERY_CODE_LEN || !chars.is_ascii() {
+        return chars;
+    }
+    format!("{}-{}", &chars[..RECOVERY_CODE_LEN / 2], &chars[RECOVERY_CODE_LEN / 2..])
+}
+
+// ===================== ENCODING =====================
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            out.push(BASE32_ALPHABET[((buffer >> (bits - 5)) & 0x1f) as usize] as char);
+            bits -= 5;
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for c in encoded.chars().filter(|c| *c != '=' && !c.is_whitespace()) {
+        let value = BASE32_ALPHABET.iter().position(|a| *a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            out.push((buffer >> (bits - 8)) as u8);
+            bits -= 8;
+        }
+    }
+    Some(out)
+}
+
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 נספח B, SHA-1: הסוד "12345678901234567890" וששת הספרות האחרונות של כל קוד
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+    const RFC_VECTORS: [(i64, &str); 6] = [
+        (59, "287082"),
+        (1111111109, "081804"),
+        (1111111111, "050471"),
+        (1234567890, "005924"),
+        (2000000000, "279037"),
+        (20000000000, "353130"),
+    ];
+
+    #[test]
+    fn matches_the_rfc_6238_sha1_vectors() {
+        let secret = base32_encode(RFC_SECRET);
+        for (time, code) in RFC_VECTORS {
+            let step = time / TOTP_STEP_SECONDS;
+            assert_eq!(format!("{:06}", code_at(RFC_SECRET, step)), code, "time {}", time);
+            assert_eq!(verify_code(&secret, code, time, None), Some(step), "time {}", time);
+        }
+    }
+
+    #[test]
+    fn accepts_one_step_of_drift_and_no_more() {
+        let secret = base32_encode(RFC_SECRET);
+        let (time, code) = RFC_VECTORS[3];
+        let step = time / TOTP_STEP_SECONDS;
+        assert_eq!(verify_code(&secret, code, time + TOTP_STEP_SECONDS, None), Some(step));
+        assert_eq!(verify_code(&secret, code, time - TOTP_STEP_SECONDS, None), Some(step));
+        assert_eq!(verify_code(&secret, code, time + 2 * TOTP_STEP_SECONDS, None), None);
+    }
+
+    #[test]
+    fn refuses_a_code_at_or_before_the_last_used_step() {
+        let secret = base32_encode(RFC_SECRET);
+        let (time, code) = RFC_VECTORS[3];
+        let step = time / TOTP_STEP_SECONDS;
+        assert_eq!(verify_code(&secret, code, time, Some(step)), None);
+        assert_eq!(verify_code(&secret, code, time, Some(step + 1)), None);
+        assert_eq!(verify_code(&secret, code, time, Some(step - 1)), Some(step));
+    }
+
+    #[test]
+    fn rejects_codes_that_are_not_six_digits() {
+        let secret = base32_encode(RFC_SECRET);
+        for code in ["", "28708", "2870820", "28708a", "28 082"] {
+            assert_eq!(verify_code(&secret, code, 59, None), None, "{:?}", code);
+        }
+        assert_eq!(verify_code("not base32!", "287082", 59, None), None);
+    }
+
+    #[test]
+    fn base32_round_trips_every_length() {
+        let data: Vec<u8> = (0..=255).collect();
+        for len in 0..=21 {
+            let encoded = base32_encode(&data[..len]);
+            assert_eq!(encoded.len(), (len * 8).div_ceil(5));
+            assert_eq!(base32_decode(&encoded).as_deref(), Some(&data[..len]), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn base32_decode_takes_lowercase_padding_and_spaces() {
+        // RFC 4648 סעיף 10
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        for input in ["MZXW6YTBOI======", "mzxw6ytboi", "mzxw 6ytb oi==", "MZXW6YTBOI"] {
+            assert_eq!(base32_decode(input).as_deref(), Some(&b"foobar"[..]), "{:?}", input);
+        }
+        assert_eq!(base32_decode("MZXW1"), None);
+    }
+
+    #[test]
+    fn generated_secrets_decode_to_twenty_bytes() {
+        assert_eq!(base32_decode(&generate_secret()).map(|key| key.len()), Some(SECRET_LEN));
+    }
+
+    #[test]
+    fn recovery_codes_normalize_to_the_stored_form() {
+        for code in generate_recovery_codes() {
+            assert_eq!(normalize_recovery_code(&code), code);
+        }
+        for input in ["abcde-fghij", " ABCDE-FGHIJ ", "abcdefghij", "abc de fg-hij", "ABCDE - FGHIJ", "abcde--fghij\n"] {
+            assert_eq!(normalize_recovery_code(input), "abcde-fghij", "{:?}", input);
+        }
+        assert_eq!(normalize_recovery_code("abc-def"), "abcdef");
+    }
+}
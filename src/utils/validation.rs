@@ -0,0 +1,368 @@
+use crate::errors::{ApiError, FieldError};
+use crate::utils::email::normalize_email;
+
+// ===================== LIMITS =====================
+pub const MAX_NAME_LEN: usize = 100;
+pub const MAX_CATEGORY_NAME_LEN: usize = 64;
+pub const MAX_ROLE_NAME_LEN: usize = 32;
+// RFC 1035: שם מלא עד 253 תווים, כל תווית עד 63
+pub const MAX_DOMAIN_LEN: usize = 253;
+const MAX_DOMAIN_LABEL_LEN: usize = 63;
+// סיסמה שמורה בכספת (לפני הצפנה)
+pub const MAX_SECRET_LEN: usize = 4096;
+// סיסמת התחברות קיימת (login, change-password). סיסמאות חדשות עוברות את password_policy
+pub const MAX_LOGIN_PASSWORD_LEN: usize = 1024;
+// tokens של אימות מייל, איפוס, refresh ו-challenge
+pub const MAX_TOKEN_LEN: usize = 256;
+pub const MAX_CODE_LEN: usize = 32;
+// פרטים נוספים של רשומה בכספת
+pub const MAX_USERNAME_LEN: usize = 254;
+pub const MAX_URLS: usize = 20;
+const MAX_URL_LEN: usize = 2048;
+pub const MAX_NOTES_LEN: usize = 10_000;
+pub const MAX_CUSTOM_FIELDS: usize = 50;
+pub const MAX_FIELD_NAME_LEN: usize = 64;
+// E.164: עד 15 ספרות
+const MIN_PHONE_DIGITS: usize = 7;
+const MAX_PHONE_DIGITS: usize = 15;
+
+/// DTO שנבדק לפני שמשהו ממנו נכתב ל-DB: `body.validate()?` בתחילת ה-handler
+pub trait Validate {
+    fn validate(&self) -> Result<(), ApiError>;
+}
+
+// ===================== VALIDATOR =====================
+/// אוסף את כל השדות הלא תקינים, כדי שהלקוח יקבל אותם יחד ולא אחד בכל פעם
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn error(&mut self, field: &str, message: impl Into<String>) -> &mut Self {
+        self.errors.push(FieldError { field: field.to_string(), message: message.into() });
+        self
+    }
+
+    fn check(&mut self, field: &str, result: Result<(), String>) -> &mut Self {
+        if let Err(message) = result {
+            self.error(field, message);
+        }
+        self
+    }
+
+    /// טקסט חובה: לא ריק (גם לא רק רווחים), עד max תווים, בלי תווי בקרה
+    pub fn text(&mut self, field: &str, value: &str, max: usize) -> &mut Self {
+        self.check(field, check_text(value, max))
+    }
+
+    pub fn optional_text(&mut self, field: &str, value: Option<&str>, max: usize) -> &mut Self {
+        match value {
+            Some(value) => self.text(field, value, max),
+            None => self,
+        }
+    }
+
+    /// ערך שלא מוצג (סיסמה, token): לא ריק ועד max תווים. רווחים ותווים אחרים מותרים
+    pub fn secret(&mut self, field: &str, value: &str, max: usize) -> &mut Self {
+        self.check(field, check_secret(value, max))
+    }
+
+    pub fn optional_secret(&mut self, field: &str, value: Option<&str>, max: usize) -> &mut Self {
+        match value {
+            Some(value) => self.secret(field, value, max),
+            None => self,
+        }
+    }
+
+    pub fn email(&mut self, field: &str, value: &str) -> &mut Self {
+        self.check(field, normalize_email(value).map(|_| ()).map_err(str::to_string))
+    }
+
+    pub fn optional_email(&mut self, field: &str, value: Option<&str>) -> &mut Self {
+        match value {
+            Some(value) => self.email(field, value),
+            None => self,
+        }
+    }
+
+    /// טלפון לא חובה: ריק מוחק אותו
+    pub fn optional_phone(&mut self, field: &str, value: Option<&str>) -> &mut Self {
+        match value {
+            Some(value) if !value.is_empty() => self.check(field, check_phone(value)),
+            _ => self,
+        }
+    }
+
+    pub fn domain(&mut self, field: &str, value: &str) -> &mut Self {
+        self.check(field, check_domain(value))
+    }
+
+    pub fn optional_domain(&mut self, field: &str, value: Option<&str>) -> &mut Self {
+        match value {
+            Some(value) => self.domain(field, value),
+            None => self,
+        }
+    }
+
+    pub fn url(&mut self, field: &str, value: &str) -> &mut Self {
+        self.check(field, check_url(value))
+    }
+
+    pub fn id(&mut self, field: &str, value: i64) -> &mut Self {
+        if value <= 0 {
+            self.error(field, "Must be a positive id");
+        }
+        self
+    }
+
+    pub fn finish(&mut self) -> Result<(), ApiError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::validation(std::mem::take(&mut self.errors)))
+        }
+    }
+}
+
+// ===================== RULES =====================
+fn check_length(value: &str, max: usize) -> Result<(), String> {
+    if value.chars().count() > max {
+        return Err(format!("Must be at most {} characters long", max));
+    }
+    Ok(())
+}
+
+fn check_text(value: &str, max: usize) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err("Must not be empty".to_string());
+    }
+    check_length(value, max)?;
+    if value.chars().any(char::is_control) {
+        return Err("Must not contain control characters".to_string());
+    }
+    Ok(())
+}
+
+fn check_secret(value: &str, max: usize) -> Result<(), String> {
+    if value.is_empty() {
+        return Err("Must not be empty".to_string());
+    }
+    check_length(value, max)
+}
+
+/// כתובת מלאה עם scheme (https://example.com/login, android://com.example.app), בלי רווחים
+fn check_url(value: &str) -> Result<(), String> {
+    check_length(value, MAX_URL_LEN)?;
+    let valid = match value.split_once("://") {
+        Some((scheme, rest)) => {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+                && !rest.is_empty()
+                && !value.chars().any(|c| c.is_whitespace() || c.is_control())
+        }
+        None => false,
+    };
+    if !valid {
+        return Err("Must be a URL with a scheme, like https://example.com/login".to_string());
+    }
+    Ok(())
+}
+
+/// ספרות, רווחים, מקפים, סוגריים ו-+ אחד בהתחלה
+fn check_phone(value: &str) -> Result<(), String> {
+    let digits = value.chars().filter(char::is_ascii_digit).count();
+    let number = value.strip_prefix('+').unwrap_or(value);
+    let allowed = number.chars().all(|c| c.is_ascii_digit() || " -()".contains(c));
+    if !allowed || !(MIN_PHONE_DIGITS..=MAX_PHONE_DIGITS).contains(&digits) {
+        return Err(format!(
+            "Must be a phone number of {} to {} digits, optionally starting with +",
+            MIN_PHONE_DIGITS, MAX_PHONE_DIGITS
+        ));
+    }
+    Ok(())
+}
+
+/// שם host בלבד (example.com, mail.example.co.il, localhost, דוגמה.ישראל), בלי scheme, port או path.
+/// דומיין בשפה אחרת נבדק אחרי המרה ל-punycode, אבל נשמר כמו שהמשתמש כתב אותו
+fn check_domain(value: &str) -> Result<(), String> {
+    let invalid = || Err("Must be a host name like example.com, without scheme, port or path".to_string());
+    if value.is_empty() || value.len() > MAX_DOMAIN_LEN {
+        return Err(format!("Must be 1 to {} characters long", MAX_DOMAIN_LEN));
+    }
+    let Ok(ascii) = idna::domain_to_ascii(value) else {
+        return invalid();
+    };
+    if ascii.len() > MAX_DOMAIN_LEN {
+        return Err(format!("Must be 1 to {} characters long", MAX_DOMAIN_LEN));
+    }
+    let valid_labels = ascii.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= MAX_DOMAIN_LABEL_LEN
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    if !valid_labels {
+        return invalid();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeat(part: &str, count: usize) -> String {
+        part.repeat(count)
+    }
+
+    #[test]
+    fn domains_are_checked_after_punycode() {
+        let label = repeat("a", MAX_DOMAIN_LABEL_LEN);
+        // 4 תוויות של 63 ועוד נקודות = 255, אז מקצרים את האחרונה עד שהשם יוצא בדיוק 253
+        let longest = format!("{0}.{0}.{0}.{1}", label, repeat("a", MAX_DOMAIN_LEN - 3 * 64));
+        let accepted = [
+            "example.com",
+            "mail.example.co.il",
+            "localhost",
+            "Example.COM",
+            "xn--mnchen-3ya.de",
+            "münchen.de",
+            "דוגמה.ישראל",
+            "xn--4dbrk0ce",
+            label.as_str(),
+            longest.as_str(),
+        ];
+        for value in accepted {
+            assert_eq!(check_domain(value), Ok(()), "{}", value);
+        }
+
+        // 60 אותיות בעברית הן 120 בייטים, אבל ב-punycode התווית ארוכה מ-63
+        let long_hebrew = repeat("ש", 60);
+        let too_long_label = format!("{}a.com", label);
+        let too_long = format!("{}a", longest);
+        let rejected = [
+            "",
+            "https://example.com",
+            "example.com:8080",
+            "example.com/login",
+            "exa mple.com",
+            "dana@example.com",
+            "-example.com",
+            "example-.com",
+            "example..com",
+            ".example.com",
+            "example.com.",
+            "xn--a.com",
+            long_hebrew.as_str(),
+            too_long_label.as_str(),
+            too_long.as_str(),
+        ];
+        for value in rejected {
+            assert!(check_domain(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn text_is_trimmed_for_emptiness_and_counted_in_characters() {
+        let longest = repeat("ש", MAX_NAME_LEN);
+        let too_long = repeat("ש", MAX_NAME_LEN + 1);
+        let cases = [
+            ("GitHub", true),
+            ("  GitHub  ", true),
+            ("בנק הפועלים", true),
+            (longest.as_str(), true),
+            ("", false),
+            ("   ", false),
+            ("\t", false),
+            ("Git\nHub", false),
+            ("Git\u{0}Hub", false),
+            (too_long.as_str(), false),
+        ];
+        for (value, valid) in cases {
+            assert_eq!(check_text(value, MAX_NAME_LEN).is_ok(), valid, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn secrets_only_need_to_be_non_empty_and_short_enough() {
+        let longest = repeat("x", MAX_SECRET_LEN);
+        let too_long = repeat("x", MAX_SECRET_LEN + 1);
+        let cases = [
+            (" ", true),
+            ("pass word\n", true),
+            (longest.as_str(), true),
+            ("", false),
+            (too_long.as_str(), false),
+        ];
+        for (value, valid) in cases {
+            assert_eq!(check_secret(value, MAX_SECRET_LEN).is_ok(), valid, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn urls_need_a_scheme_and_no_whitespace() {
+        let longest = format!("https://example.com/{}", repeat("a", MAX_URL_LEN - 20));
+        let too_long = format!("{}a", longest);
+        let cases = [
+            ("https://example.com/login", true),
+            ("http://localhost:8080", true),
+            ("android://com.example.app", true),
+            ("git+ssh://example.com/repo", true),
+            (longest.as_str(), true),
+            ("example.com", false),
+            ("https://", false),
+            ("://example.com", false),
+            ("1http://example.com", false),
+            ("ht_tp://example.com", false),
+            ("https://exa mple.com", false),
+            ("https://example.com/\n", false),
+            (too_long.as_str(), false),
+        ];
+        for (value, valid) in cases {
+            assert_eq!(check_url(value).is_ok(), valid, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn phones_have_seven_to_fifteen_digits() {
+        let cases = [
+            ("+972 50-123-4567", true),
+            ("(03) 123-4567", true),
+            ("1234567", true),
+            ("+123456789012345", true),
+            ("123456", false),
+            ("+1234567890123456", false),
+            ("++972501234567", false),
+            ("972+501234567", false),
+            ("050-123-456a", false),
+            ("050.123.4567", false),
+        ];
+        for (value, valid) in cases {
+            assert_eq!(check_phone(value).is_ok(), valid, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn validator_reports_every_invalid_field_together() {
+        let result = Validator::new()
+            .text("name", "", MAX_NAME_LEN)
+            .domain("domain", "example.com")
+            .email("email", "not an email")
+            .optional_phone("phone", Some(""))
+            .optional_domain("url", None)
+            .id("category_id", 0)
+            .finish();
+        let message = result.unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "VALIDATION_FAILED: name: Must not be empty; email: Email address must contain @; category_id: Must be a positive id"
+        );
+        assert!(Validator::new().text("name", "GitHub", MAX_NAME_LEN).finish().is_ok());
+    }
+}
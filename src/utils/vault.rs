@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::sync::Mutex;
+
+use actix_web::{dev::Payload, web, FromRequest, HttpMessage, HttpRequest};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{NaiveDateTime, Utc};
+use rand_core::{OsRng, RngCore};
+use crate::utils::encryption::{
+    bound_only, decrypt_password, decrypt_with_vault_key, encrypt_password, encrypt_with_vault_key, open_with_vault_key, opens_with,
+    vault_escrow_aad, vault_key_aad,
+};
+use crate::utils::keys::derive_key;
+use crate::middleware::auth::AuthUser;
+use crate::errors::{ApiError, ErrorCode};
+
+const SALT_LEN: usize = 16;
+
+// ===================== VAULT KEY =====================
+/// מפתח ההצפנה האישי של משתמש (DEK). נשמר ב-DB רק כשהוא עטוף
+#[derive(Clone)]
+pub struct VaultKey([u8; 32]);
+
+impl VaultKey {
+    pub fn generate() -> Self {
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        VaultKey(key)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// המפתח העטוף כפי שהוא נשמר בטבלת users
+#[derive(Clone)]
+pub struct WrappedVaultKey {
+    pub salt: String,
+    pub wrapped: String,
+}
+
+/// מפתח עם המפתח שנגזר מסיסמת ההתחברות, לפני שיש user_id לקשור אליו (משתמש חדש).
+/// הגזירה האיטית נעשית כאן, וה-repository עוטף אחרי שה-user_id ידוע
+pub struct PendingVaultKey {
+    key: VaultKey,
+    salt: [u8; SALT_LEN],
+    kek: [u8; 32],
+}
+
+impl PendingVaultKey {
+    pub fn new(key: VaultKey, login_password: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let kek = derive_key(login_password.as_bytes(), &salt)?;
+        Ok(PendingVaultKey { key, salt, kek })
+    }
+
+    /// בלי הגזירה האיטית מסיסמה, לבדיקות שלא פותחות את המפתח העטוף
+    #[cfg(test)]
+    pub fn without_password(key: VaultKey) -> Self {
+        PendingVaultKey { key, salt: [0; SALT_LEN], kek: [0; 32] }
+    }
+
+    /// עוטף במפתח שנגזר מהסיסמה ואז שוב במפתח של השרת. שתי השכבות קשורות ל-user_id,
+    /// כך שמפתח עטוף שהועתק למשתמש אחר לא נפתח
+    pub fn wrap(&self, user_id: i64) -> Result<WrappedVaultKey, Box<dyn std::error::Error>> {
+        let aad = vault_key_aad(user_id);
+        let inner = encrypt_with_vault_key(&self.kek, &general_purpose::STANDARD.encode(self.key.as_bytes()), &aad)?;
+        Ok(WrappedVaultKey {
+            salt: general_purpose::STANDARD.encode(self.salt),
+            wrapped: encrypt_password(&inner, &aad)?,
+        })
+    }
+}
+
+pub fn wrap_vault_key(user_id: i64, key: &VaultKey, login_password: &str) -> Result<WrappedVaultKey, Box<dyn std::error::Error>> {
+    PendingVaultKey::new(key.clone(), login_password)?.wrap(user_id)
+}
+
+/// פותח את המפתח העטוף. סיסמה שגויה => שגיאה.
+/// true: המפתח נעטף לפני שהיה קשור ל-user_id, וצריך לעטוף אותו מחדש עכשיו כשהסיסמה ידועה
+pub fn unwrap_vault_key(
+    user_id: i64,
+    wrapped: &WrappedVaultKey,
+    login_password: &str,
+) -> Result<(VaultKey, bool), Box<dyn std::error::Error>> {
+    let salt = general_purpose::STANDARD.decode(&wrapped.salt)?;
+    let kek = derive_key(login_password.as_bytes(), &salt)?;
+
+    let aad = match vault_key_aad(user_id) {
+        aad if opens_with(&wrapped.wrapped, &aad) => aad,
+        _ if bound_only() => return Err("Refusing vault key that is not bound to its user".into()),
+        _ => Vec::new(),
+    };
+    let inner = decrypt_password(&wrapped.wrapped, &aad)?;
+    // סיסמה שגויה היא לא שגיאת integrity, לכן בלי לרשום אותה
+    let encoded = open_with_vault_key(&kek, &inner, &aad).ok_or("Wrong password for vault key")?;
+    let bytes: [u8; 32] = general_purpose::STANDARD
+        .decode(encoded)?
+        .try_into()
+        .map_err(|_| "Vault key has wrong length")?;
+    Ok((VaultKey(bytes), aad.is_empty()))
+}
+
+// ===================== ESCROW =====================
+/// עותק של המפתח שמוצפן רק במפתח השרת, כדי שאיפוס סיסמה לא יאבד את הכספת
+pub fn escrow_vault_key(user_id: i64, key: &VaultKey) -> Result<String, Box<dyn std::error::Error>> {
+    encrypt_password(&general_purpose::STANDARD.encode(key.as_bytes()), &vault_escrow_aad(user_id))
+}
+
+pub fn recover_vault_key(user_id: i64, escrow: &str) -> Result<VaultKey, Box<dyn std::error::Error>> {
+    let encoded = decrypt_password(escrow, &vault_escrow_aad(user_id))?;
+    let bytes: [u8; 32] = general_purpose::STANDARD
+        .decode(encoded)?
+        .try_into()
+        .map_err(|_| "Vault key has wrong length")?;
+    Ok(VaultKey(bytes))
+}
+
+// ===================== VAULT SESSIONS =====================
+struct VaultSession {
+    user_id: i64,
+    key: VaultKey,
+    expires_at: NaiveDateTime,
+}
+
+/// כספות פתוחות בזיכרון בלבד, לפי ה-session שנוצר ב-login
+#[derive(Default)]
+pub struct VaultSessions {
+    sessions: Mutex<HashMap<i64, VaultSession>>,
+}
+
+impl VaultSessions {
+    pub fn open(&self, session_id: i64, user_id: i64, key: VaultKey, expires_at: NaiveDateTime) {
+        let now = Utc::now().naive_utc();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(session_id, VaultSession { user_id, key, expires_at });
+    }
+
+    pub fn get(&self, session_id: i64) -> Option<UnlockedVault> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .get(&session_id)
+            .filter(|s| s.expires_at > Utc::now().naive_utc())
+            .map(|s| UnlockedVault { user_id: s.user_id, key: s.key.clone() })
+    }
+
+    /// מאריך את הכספת יחד עם ה-session (אחרי refresh)
+    pub fn extend(&self, session_id: i64, expires_at: NaiveDateTime) {
+        if let Some(s) = self.sessions.lock().unwrap().get_mut(&session_id) {
+            s.expires_at = expires_at;
+        }
+    }
+
+    pub fn close(&self, session_id: i64) {
+        self.sessions.lock().unwrap().remove(&session_id);
+    }
+
+    /// נועל את כל הכספות הפתוחות של משתמש (למשל אחרי מחיקה)
+    pub fn close_user(&self, user_id: i64) {
+        self.sessions.lock().unwrap().retain(|_, s| s.user_id != user_id);
+    }
+
+    /// נועל את כל הכספות של משתמש חוץ מזו של ה-session הנוכחי
+    pub fn close_user_except(&self, user_id: i64, keep_session_id: i64) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|id, s| s.user_id != user_id || *id == keep_session_id);
+    }
+}
+
+/// הכספת הפתוחה של ה-session ששלח את הבקשה
+pub struct UnlockedVault {
+    pub user_id: i64,
+    pub key: VaultKey,
+}
+
+impl UnlockedVault {
+    pub fn encrypt(&self, plain: &str, aad: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+        encrypt_with_vault_key(self.key.as_bytes(), plain, aad)
+    }
+
+    pub fn decrypt(&self, encoded: &str, aad: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+        decrypt_with_vault_key(self.key.as_bytes(), encoded, aad)
+    }
+}
+
+impl FromRequest for UnlockedVault {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user = req.extensions().get::<AuthUser>().copied();
+        let vault = user
+            .zip(req.app_data::<web::Data<VaultSessions>>())
+            .and_then(|(user, sessions)| sessions.get(user.session_id).filter(|v| v.user_id == user.user_id));
+
+        ready(vault.ok_or_else(|| ApiError::new(ErrorCode::VaultLocked, "Vault is locked, login again")))
+    }
+}